@@ -0,0 +1,157 @@
+//! The parts of GitHub API responses that gitdown reads.
+//!
+//! Fields gitdown does not use are left out, so additions to the API do not
+//! break parsing; fields it relies on are required, so a truncated or
+//! unexpected payload is reported instead of silently misread.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// `GET /repos/{owner}/{repo}/git/trees/{tree}?recursive=1`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TreeResponse {
+    pub sha: String,
+    pub tree: Vec<TreeEntry>,
+    /// Set when the tree was too large to list in full.
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// An entry of a tree listing.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TreeEntry {
+    pub path: String,
+    /// The file mode, e.g. `100644` or `100755`.
+    pub mode: String,
+    /// `blob`, `tree` or `commit` (for submodules).
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub sha: String,
+    /// The size of blobs; trees have none.
+    pub size: Option<usize>,
+}
+
+/// `GET /repos/{owner}/{repo}/commits/{ref}`, and the items of
+/// `GET /repos/{owner}/{repo}/commits`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommitResponse {
+    pub sha: String,
+    #[serde(default)]
+    pub commit: Option<CommitDetail>,
+}
+
+/// The git data of a commit.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommitDetail {
+    pub message: String,
+}
+
+/// `GET /repos/{owner}/{repo}/git/ref/tags/{tag}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct RefResponse {
+    pub object: RefObject,
+}
+
+/// What a ref points at.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RefObject {
+    pub sha: String,
+    /// `tag` for an annotated tag, `commit` for a lightweight one.
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// `GET /repos/{owner}/{repo}/git/tags/{sha}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TagResponse {
+    pub verification: Option<Verification>,
+}
+
+/// Whether GitHub could verify the signature of an object.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Verification {
+    pub verified: bool,
+    /// `valid`, `unsigned`, `bad_email`, `unknown_key`, ...
+    pub reason: String,
+}
+
+/// `GET /repos/{owner}/{repo}/compare/{base}...{head}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompareResponse {
+    #[serde(default)]
+    pub files: Vec<ComparedFile>,
+}
+
+/// A file changed between two commits.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ComparedFile {
+    pub filename: String,
+    /// `added`, `modified`, `removed`, `renamed`, ...
+    pub status: String,
+}
+
+/// `GET /search/repositories`
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchResponse {
+    pub items: Vec<FoundRepo>,
+}
+
+/// A repository matching a search.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FoundRepo {
+    /// `owner/name`
+    pub full_name: String,
+    pub description: Option<String>,
+    pub stargazers_count: u64,
+    pub default_branch: String,
+}
+
+/// The body of `GET /repos/{owner}/{repo}`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RepoInfo {
+    pub default_branch: String,
+}
+
+/// An item of `GET /users/{owner}/repos`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OwnedRepo {
+    pub name: String,
+}
+
+/// The body of an API error response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiError {
+    pub message: String,
+    pub documentation_url: Option<String>,
+}
+
+/// The body of a GraphQL response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GraphQlResponse<T> {
+    pub data: Option<T>,
+    #[serde(default)]
+    pub errors: Vec<ApiError>,
+}
+
+/// `repository { object(expression: ...) { ... on Commit { <alias>: history(first: 1, path: ...) } } }`
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistoryData {
+    pub repository: Option<HistoryRepository>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistoryRepository {
+    /// The histories, keyed by the alias each path was queried under.
+    pub object: Option<HashMap<String, History>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct History {
+    pub nodes: Vec<HistoryCommit>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistoryCommit {
+    #[serde(rename = "committedDate")]
+    pub committed_date: String,
+}
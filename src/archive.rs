@@ -0,0 +1,221 @@
+use crate::error::{Error, ErrorKind, Result};
+use flate2::read::GzDecoder;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use tar::EntryType;
+
+/// The archive a repository snapshot is fetched as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// A gzipped tarball.
+    Tar,
+    /// A zip archive, for environments that cannot handle tar.gz.
+    Zip,
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "tar" => Ok(ArchiveFormat::Tar),
+            "zip" => Ok(ArchiveFormat::Zip),
+            _ => Err(format!("unknown archive format {}", s)),
+        }
+    }
+}
+
+/// Extracts a GitHub archive of `format` into `dest`, returning the number
+/// of files written.
+pub fn extract(format: ArchiveFormat, bytes: &[u8], dest: &Path) -> Result<usize> {
+    match format {
+        ArchiveFormat::Tar => extract_tarball(bytes, dest),
+        ArchiveFormat::Zip => extract_zipball(bytes, dest),
+    }
+}
+
+/// The extensions of the snapshots [`extract_file`] reads.
+pub const LOCAL_EXTENSIONS: [&str; 6] = [".tar.gz", ".tgz", ".tar.zst", ".tzst", ".tar", ".zip"];
+
+/// Whether `path` names a snapshot on disk rather than a repository.
+pub fn is_local_archive(path: &str) -> bool {
+    LOCAL_EXTENSIONS.iter().any(|ext| path.ends_with(ext)) && Path::new(path).is_file()
+}
+
+/// Extracts a snapshot kept on disk into `dest`, returning the number of
+/// files written. The compression is told by the content, so tar.gz,
+/// tar.zst, plain tar and zip all work whatever they are called.
+///
+/// The snapshot is expected to be laid out like GitHub's (and
+/// [`pack_tarball`]'s), under a single top-level directory, which is
+/// stripped.
+pub fn extract_file(path: &Path, dest: &Path) -> Result<usize> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return Error::err(ErrorKind::ReadFailure {
+                path: path.display().to_string(),
+            })
+        }
+    };
+    match bytes.get(..4) {
+        Some([0x1f, 0x8b, ..]) => extract_tarball(&bytes, dest),
+        Some([0x28, 0xb5, 0x2f, 0xfd]) => extract_tar(zstd::Decoder::new(bytes.as_slice())?, dest),
+        Some(b"PK\x03\x04") => extract_zipball(&bytes, dest),
+        _ => extract_tar(bytes.as_slice(), dest),
+    }
+}
+
+/// Recompresses a GitHub tarball with zstd into `path`, which is created
+/// and must not exist unless `force`. The top-level directory is kept, so
+/// [`extract_file`] can read the result back.
+pub fn pack_tarball(bytes: &[u8], path: &Path, force: bool) -> Result<()> {
+    let file = if force {
+        File::create(path)
+    } else {
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+    };
+    let file = match file {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            return Error::err(ErrorKind::FileExists {
+                path: path.display().to_string(),
+            })
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut encoder = zstd::Encoder::new(file, ZSTD_LEVEL)?;
+    io::copy(&mut GzDecoder::new(bytes), &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// The zstd level of packed snapshots; zstd's default, which compresses
+/// about as well as gzip at several times the speed.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Extracts a GitHub tarball into `dest`, returning the number of files
+/// written.
+///
+/// GitHub wraps the snapshot in a single `<owner>-<repo>-<sha>/` directory,
+/// which is stripped. Entries that would escape `dest` are rejected.
+pub fn extract_tarball(bytes: &[u8], dest: &Path) -> Result<usize> {
+    extract_tar(GzDecoder::new(bytes), dest)
+}
+
+/// Extracts the tar stream `reader` like [`extract_tarball`].
+fn extract_tar<R: Read>(reader: R, dest: &Path) -> Result<usize> {
+    let mut archive = tar::Archive::new(reader);
+    let mut count = 0;
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        match entry.header().entry_type() {
+            EntryType::Regular | EntryType::Directory | EntryType::Symlink => {}
+            // Skips the pax header holding the commit SHA, among others.
+            _ => continue,
+        }
+
+        let path = entry.path()?.into_owned();
+        let rel = match strip_root(&path) {
+            Some(rel) => rel,
+            None => continue,
+        };
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+
+        let target = dest.join(&rel);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        entry.unpack(&target)?;
+        if entry.header().entry_type() != EntryType::Directory {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Extracts a GitHub zipball into `dest`, returning the number of files
+/// written.
+///
+/// Entries are read one after the other, without seeking to the central
+/// directory. As with tarballs, the `<owner>-<repo>-<sha>/` directory is
+/// stripped and entries that would escape `dest` are rejected.
+/// File modes live in the central directory, so files get default ones.
+pub fn extract_zipball(mut bytes: &[u8], dest: &Path) -> Result<usize> {
+    let mut count = 0;
+
+    loop {
+        let mut entry = match zip::read::read_zipfile_from_stream(&mut bytes) {
+            Ok(Some(entry)) => entry,
+            Ok(None) => break,
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e).into()),
+        };
+
+        let rel = match entry.enclosed_name().and_then(strip_root) {
+            Some(rel) => rel,
+            None => continue,
+        };
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+
+        let target = dest.join(&rel);
+        if entry.is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut contents = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut contents)?;
+        fs::write(&target, contents)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Drops the first component of `path`, returning `None` for anything that is
+/// not a plain relative path (absolute paths, `..`).
+fn strip_root(path: &Path) -> Option<PathBuf> {
+    let mut components = path.components();
+    components.next()?;
+
+    let mut rel = PathBuf::new();
+    for component in components {
+        match component {
+            Component::Normal(c) => rel.push(c),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(rel)
+}
+
+/// Fails unless `dir` is missing or empty, so snapshots never land on top of
+/// existing work.
+pub fn ensure_empty_dir(dir: &Path) -> Result<()> {
+    match fs::read_dir(dir) {
+        Ok(mut entries) => {
+            if entries.next().is_some() {
+                Error::err(ErrorKind::DirectoryNotEmpty {
+                    path: dir.display().to_string(),
+                })
+            } else {
+                Ok(())
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,148 @@
+//! Bundles: a lockfile together with the content of every file it records,
+//! in a single file, so a machine without access to GitHub can extract the
+//! files another one downloaded.
+//!
+//! A bundle is a zstd-compressed tar holding the lockfile as
+//! [`LOCKFILE`](crate::lockfile::LOCKFILE) and each blob under
+//! `blobs/<sha>`, once however many files share it.
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::hash;
+use crate::lockfile::{Lockfile, LOCKFILE};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// The directory of the blobs in a bundle.
+const BLOBS: &str = "blobs";
+
+/// The zstd level of bundles, as for packed snapshots.
+const ZSTD_LEVEL: i32 = 3;
+
+/// The content of a bundle.
+#[derive(Debug, Default)]
+pub struct Bundle {
+    /// Every file, each with the SHA of its blob.
+    pub lock: Lockfile,
+    /// The blobs, keyed by SHA.
+    pub blobs: BTreeMap<String, Vec<u8>>,
+}
+
+impl Bundle {
+    /// Writes the bundle to `path`, which must not exist unless `force`.
+    pub fn write(&self, path: &Path, force: bool) -> Result<()> {
+        let file = if force {
+            File::create(path)
+        } else {
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+        };
+        let file = match file {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                return Error::err(ErrorKind::FileExists {
+                    path: path.display().to_string(),
+                })
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let lock = match toml::to_string(&self.lock) {
+            Ok(lock) => lock,
+            Err(e) => return self.malformed(path, e.to_string()),
+        };
+
+        let mut tar = tar::Builder::new(zstd::Encoder::new(file, ZSTD_LEVEL)?);
+        let mut append = |name: &str, bytes: &[u8]| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, bytes)
+        };
+        append(LOCKFILE, lock.as_bytes())?;
+        for (sha, bytes) in &self.blobs {
+            append(&format!("{}/{}", BLOBS, sha), bytes)?;
+        }
+        tar.into_inner()?.finish()?;
+        Ok(())
+    }
+
+    /// Reads the bundle at `path`, checking that every blob is the one its
+    /// name says and that the lockfile only names blobs it holds.
+    pub fn read(path: &Path) -> Result<Bundle> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => {
+                return Error::err(ErrorKind::ReadFailure {
+                    path: path.display().to_string(),
+                })
+            }
+        };
+        let mut bundle = Bundle::default();
+        let mut lock = None;
+        let mut tar = tar::Archive::new(zstd::Decoder::new(file)?);
+        for entry in tar.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes)?;
+
+            if name == LOCKFILE {
+                let text = String::from_utf8_lossy(&bytes);
+                match toml::from_str(&text) {
+                    Ok(parsed) => lock = Some(parsed),
+                    Err(e) => return bundle.malformed(path, e.to_string()),
+                }
+            } else if let Some(sha) = name.strip_prefix(&format!("{}/", BLOBS)) {
+                if hash::git_blob_sha(&bytes) != sha {
+                    return bundle.malformed(path, format!("the blob {} is corrupt", sha));
+                }
+                bundle.blobs.insert(sha.to_string(), bytes);
+            }
+        }
+
+        bundle.lock = match lock {
+            Some(lock) => lock,
+            None => return bundle.malformed(path, format!("it has no {}", LOCKFILE)),
+        };
+        for file in &bundle.lock.files {
+            let held = file
+                .sha
+                .as_ref()
+                .is_some_and(|sha| bundle.blobs.contains_key(sha));
+            if !held {
+                let reason = format!("it does not hold the content of {}", file.dest.display());
+                return bundle.malformed(path, reason);
+            }
+            if relative_dest(&file.dest).is_none() {
+                let reason = format!("{} is not a relative path", file.dest.display());
+                return bundle.malformed(path, reason);
+            }
+        }
+        Ok(bundle)
+    }
+
+    fn malformed<T>(&self, path: &Path, reason: String) -> Result<T> {
+        Error::err(ErrorKind::MalformedBundle {
+            path: path.display().to_string(),
+            reason,
+        })
+    }
+}
+
+/// `dest` without a leading `./`, or `None` if it is absolute or leaves
+/// the directory it is extracted into.
+pub fn relative_dest(dest: &Path) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in dest.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!relative.as_os_str().is_empty()).then_some(relative)
+}
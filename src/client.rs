@@ -0,0 +1,812 @@
+//! A client of the GitHub API.
+
+use crate::api::{
+    ApiError, CommitResponse, CompareResponse, FoundRepo, GraphQlResponse, HistoryData, OwnedRepo,
+    RefResponse, RepoInfo, SearchResponse, TagResponse, TreeEntry, TreeResponse, Verification,
+};
+use crate::archive::ArchiveFormat;
+use crate::error::{Error, ErrorKind, Result};
+use crate::pathname;
+use crate::throttle;
+use crate::usage;
+use reqwest::{header, StatusCode};
+use reqwest::{Client as ReqwestClient, Proxy, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// The GitHub API endpoint for repositories.
+pub const API_URL: &str = "https://api.github.com/repos";
+
+/// The endpoint serving raw file contents.
+pub const RAW_URL: &str = "https://raw.githubusercontent.com";
+
+/// The GraphQL endpoint, which only answers authenticated requests.
+pub const GRAPHQL_URL: &str = "https://api.github.com/graphql";
+
+/// The repository search endpoint.
+pub const SEARCH_URL: &str = "https://api.github.com/search/repositories";
+
+/// The users endpoint, which lists the repositories of an owner.
+pub const USERS_URL: &str = "https://api.github.com/users";
+
+/// How many paths one GraphQL query asks the history of.
+const HISTORY_CHUNK: usize = 50;
+
+/// The most files GitHub lists in one page of a comparison.
+const COMPARE_PAGE_FILES: usize = 300;
+
+/// A GitHub directory entry.
+///
+///
+#[derive(Debug, Clone)]
+pub struct GitHubDirEntry {
+    /// The path in the repository (not to be confused with the url)
+    pub path: Option<String>,
+    /// The blob SHA of the entry.
+    pub sha: Option<String>,
+    /// The size of the entry.
+    pub size: Option<usize>,
+    /// The file mode, e.g. `100644` or `100755` for an executable.
+    pub mode: Option<String>,
+    /// The raw githubusercontent url
+    pub raw_path: Option<String>,
+    /// Where the entry is written locally.
+    pub dest: Option<PathBuf>,
+}
+
+impl From<TreeEntry> for GitHubDirEntry {
+    fn from(entry: TreeEntry) -> Self {
+        GitHubDirEntry {
+            path: Some(entry.path),
+            sha: Some(entry.sha),
+            size: entry.size,
+            mode: Some(entry.mode),
+            raw_path: None,
+            dest: None,
+        }
+    }
+}
+
+/// A client of the GitHub API, created with [`Client::builder`].
+pub struct Client {
+    client: ReqwestClient,
+    base_url: String,
+    raw_url: String,
+    graphql_url: String,
+    search_url: String,
+    users_url: String,
+    token: Option<String>,
+    retry: RetryPolicy,
+}
+
+/// How requests that failed on the way, or with a server error, are retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// How many times a request is retried.
+    pub retries: u32,
+    /// How long to wait before the first retry; each retry waits twice as
+    /// long as the previous one.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub fn none() -> Self {
+        RetryPolicy {
+            retries: 0,
+            backoff: Duration::from_secs(0),
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 2,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Configures a [`Client`].
+///
+/// Both endpoints are configurable so the client can be pointed at a GitHub
+/// Enterprise instance, or at a mock server in tests.
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    base_url: String,
+    raw_url: String,
+    graphql_url: String,
+    search_url: String,
+    users_url: String,
+    token: Option<String>,
+    user_agent: String,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    proxy: Option<String>,
+    retry: RetryPolicy,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        ClientBuilder {
+            base_url: API_URL.to_string(),
+            raw_url: RAW_URL.to_string(),
+            graphql_url: GRAPHQL_URL.to_string(),
+            search_url: SEARCH_URL.to_string(),
+            users_url: USERS_URL.to_string(),
+            token: None,
+            user_agent: "gitdown".to_string(),
+            timeout: None,
+            connect_timeout: None,
+            proxy: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+impl ClientBuilder {
+    /// Points every API endpoint at the API root `url`, such as
+    /// `https://github.example.com/api/v3` for GitHub Enterprise, whose
+    /// GraphQL endpoint is then `/api/graphql`.
+    pub fn api_root(mut self, url: &str) -> Self {
+        let root = url.trim_end_matches('/');
+        self.base_url = format!("{}/repos", root);
+        self.search_url = format!("{}/search/repositories", root);
+        self.users_url = format!("{}/users", root);
+        self.graphql_url = match root.strip_suffix("/v3") {
+            Some(api) => format!("{}/graphql", api),
+            None => format!("{}/graphql", root),
+        };
+        self
+    }
+
+    /// The repositories API, [`API_URL`] by default.
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = url.into();
+        self
+    }
+
+    /// The endpoint serving raw file contents, [`RAW_URL`] by default.
+    pub fn raw_url(mut self, url: impl Into<String>) -> Self {
+        self.raw_url = url.into();
+        self
+    }
+
+    /// The GraphQL endpoint, [`GRAPHQL_URL`] by default.
+    pub fn graphql_url(mut self, url: impl Into<String>) -> Self {
+        self.graphql_url = url.into();
+        self
+    }
+
+    /// The repository search endpoint, [`SEARCH_URL`] by default.
+    pub fn search_url(mut self, url: impl Into<String>) -> Self {
+        self.search_url = url.into();
+        self
+    }
+
+    /// The users endpoint, [`USERS_URL`] by default.
+    pub fn users_url(mut self, url: impl Into<String>) -> Self {
+        self.users_url = url.into();
+        self
+    }
+
+    /// Authenticates API requests with `token`.
+    pub fn token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Limits how long a whole request, including its body, may take.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Limits how long connecting may take.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sends every request through the proxy at `url`. Without one, the
+    /// usual `HTTPS_PROXY` and friends are honoured.
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let mut b = ReqwestClient::builder().user_agent(self.user_agent);
+        if let Some(timeout) = self.timeout {
+            b = b.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            b = b.connect_timeout(timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            b = b.proxy(Proxy::all(proxy.as_str())?);
+        }
+
+        Ok(Client {
+            client: b.build()?,
+            base_url: self.base_url.trim_end_matches('/').to_string(),
+            raw_url: self.raw_url.trim_end_matches('/').to_string(),
+            graphql_url: self.graphql_url,
+            search_url: self.search_url,
+            users_url: self.users_url.trim_end_matches('/').to_string(),
+            token: self.token,
+            retry: self.retry,
+        })
+    }
+}
+
+impl Client {
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// The underlying HTTP client, for requests outside the API.
+    pub(crate) fn http(&self) -> &ReqwestClient {
+        &self.client
+    }
+
+    /// The repositories API.
+    pub fn api_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// The endpoint serving raw file contents.
+    pub fn raw_root(&self) -> &str {
+        &self.raw_url
+    }
+
+    /// Builds the raw url of `path` at `rev`.
+    pub fn raw_url(&self, username: &str, repo: &str, rev: &str, path: &str) -> String {
+        format!(
+            "{}/{}/{}/{}/{}",
+            self.raw_url,
+            username,
+            repo,
+            rev,
+            pathname::url_path(path)
+        )
+    }
+
+    /// Sends an API request, retrying it as the retry policy allows.
+    pub async fn send(&self, mut req: RequestBuilder) -> Result<Response> {
+        req = req.header("Content-Type", "application/vnd.github.v3+json");
+        if let Some(token) = &self.token {
+            req = req.bearer_auth(token);
+        }
+        let req = req.build()?;
+
+        let mut attempt = 0;
+        let mut limited = 0;
+        let res = loop {
+            // API requests have no body, so they can always be cloned.
+            let res = self.client.execute(req.try_clone().unwrap()).await;
+            if let Ok(res) = &res {
+                usage::record_api(res.headers());
+                let wait = throttle::secondary_limit_wait(res.status(), res.headers());
+                if let Some(wait) = wait.filter(|w| *w <= throttle::MAX_WAIT) {
+                    if limited < throttle::RETRIES {
+                        limited += 1;
+                        warn!(url = %req.url(), ?wait, "hit a secondary rate limit, waiting");
+                        tokio::time::sleep(wait).await;
+                        continue;
+                    }
+                }
+            }
+            let retryable = match &res {
+                Ok(res) => res.status().is_server_error(),
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+            if !retryable || attempt >= self.retry.retries {
+                break res?;
+            }
+            let wait = self.retry.backoff * 2u32.pow(attempt);
+            attempt += 1;
+            debug!(url = %req.url(), attempt, ?wait, "retrying");
+            tokio::time::sleep(wait).await;
+        };
+        if res.status() == StatusCode::OK {
+            Ok(res)
+        } else {
+            Err(status_error(res).await)
+        }
+    }
+
+    /// Parses the body of a successful response as `T`.
+    async fn json<T: DeserializeOwned>(res: Response) -> Result<T> {
+        let url = res.url().to_string();
+        let text = res.text().await?;
+        serde_json::from_str(&text).or_else(|e| {
+            Error::err(ErrorKind::MalformedResponse {
+                url,
+                reason: e.to_string(),
+            })
+        })
+    }
+
+    pub async fn get_dentries(
+        &self,
+        username: &str,
+        repo: &str,
+        tree: Option<&str>,
+    ) -> Result<Vec<GitHubDirEntry>> {
+        let tree = tree.unwrap_or("main").to_string();
+        let mut query = format!("{}/{}/git/trees/{}", username, repo, tree);
+
+        // This option recursively walks the tree of the repository,
+        // yielding all blobs (and even trees).
+        query.push_str("?recursive=1");
+
+        let url = format!("{}/{}", self.base_url, query);
+        let req = self.client.get(url.as_str());
+
+        let res = self
+            .send(req)
+            .await
+            .map_err(|e| missing_tree(e, &tree, username, repo))?;
+
+        let body: TreeResponse = Self::json(res).await?;
+        if body.truncated {
+            warn!(
+                "the tree of {}/{} is too large to list in full; some files are missing",
+                username, repo
+            );
+        }
+
+        // Earlier, we yielded everything, but really we only want blobs.
+        Ok(body
+            .tree
+            .into_iter()
+            .filter(|d| d.ty == "blob")
+            .map(GitHubDirEntry::from)
+            .collect())
+    }
+
+    /// Downloads the gzipped tarball of the repository at `tree`, or at the
+    /// default branch when `tree` is `None`.
+    pub async fn get_tarball(
+        &self,
+        username: &str,
+        repo: &str,
+        tree: Option<&str>,
+    ) -> Result<Vec<u8>> {
+        self.get_archive(username, repo, tree, ArchiveFormat::Tar)
+            .await
+    }
+
+    /// Downloads the repository at `tree` as an archive of `format`.
+    pub async fn get_archive(
+        &self,
+        username: &str,
+        repo: &str,
+        tree: Option<&str>,
+        format: ArchiveFormat,
+    ) -> Result<Vec<u8>> {
+        let endpoint = match format {
+            ArchiveFormat::Tar => "tarball",
+            ArchiveFormat::Zip => "zipball",
+        };
+        let mut url = format!("{}/{}/{}/{}", self.base_url, username, repo, endpoint);
+        if let Some(tree) = tree {
+            url.push('/');
+            url.push_str(tree);
+        }
+        let req = self.client.get(url.as_str());
+
+        let res = self
+            .send(req)
+            .await
+            .map_err(|e| missing_tree(e, tree.unwrap_or("(default branch)"), username, repo))?;
+
+        Ok(res.bytes().await?.to_vec())
+    }
+
+    /// Downloads the content of a file through the API rather than the raw
+    /// endpoint: the blob `sha` names if it is known, or else `path` at
+    /// `tree` from the contents endpoint.
+    pub async fn get_content(
+        &self,
+        username: &str,
+        repo: &str,
+        tree: &str,
+        path: &str,
+        sha: Option<&str>,
+    ) -> Result<Vec<u8>> {
+        let url = match sha {
+            Some(sha) => format!("{}/{}/{}/git/blobs/{}", self.base_url, username, repo, sha),
+            None => format!(
+                "{}/{}/{}/contents/{}",
+                self.base_url,
+                username,
+                repo,
+                pathname::url_path(path)
+            ),
+        };
+        let mut req = self
+            .client
+            .get(url.as_str())
+            .header(header::ACCEPT, "application/vnd.github.raw");
+        if sha.is_none() {
+            req = req.query(&[("ref", tree)]);
+        }
+        let res = self.send(req).await?;
+        Ok(res.bytes().await?.to_vec())
+    }
+
+    /// Finds the latest commit on `tree` made before `date` (`YYYY-MM-DD`,
+    /// optionally followed by a time), if there is one.
+    pub async fn commit_before(
+        &self,
+        username: &str,
+        repo: &str,
+        tree: &str,
+        date: &str,
+    ) -> Result<Option<String>> {
+        let until = if date.contains('T') {
+            date.to_string()
+        } else {
+            format!("{}T00:00:00Z", date)
+        };
+        let url = format!("{}/{}/{}/commits", self.base_url, username, repo);
+        let req = self.client.get(url.as_str()).query(&[
+            ("sha", tree),
+            ("until", until.as_str()),
+            ("per_page", "1"),
+        ]);
+
+        let res = self
+            .send(req)
+            .await
+            .map_err(|e| missing_tree(e, tree, username, repo))?;
+
+        let commits: Vec<CommitResponse> = Self::json(res).await?;
+        Ok(commits.into_iter().next().map(|c| c.sha))
+    }
+
+    /// Returns the repositories matching `query`, best match first, limited
+    /// to those written mostly in `language` if given.
+    pub async fn search_repositories(
+        &self,
+        query: &str,
+        language: Option<&str>,
+    ) -> Result<Vec<FoundRepo>> {
+        self.search_repositories_by(query, language, None).await
+    }
+
+    /// Like [`Client::search_repositories`], but in the order of `sort`
+    /// (`stars`, `forks` or `updated`), highest first, rather than by
+    /// relevance.
+    pub async fn search_repositories_by(
+        &self,
+        query: &str,
+        language: Option<&str>,
+        sort: Option<&str>,
+    ) -> Result<Vec<FoundRepo>> {
+        let q = match language {
+            Some(language) => format!("{} language:{}", query, language),
+            None => query.to_string(),
+        };
+        let mut req = self
+            .client
+            .get(self.search_url.as_str())
+            .query(&[("q", q.as_str()), ("per_page", "50")]);
+        if let Some(sort) = sort {
+            req = req.query(&[("sort", sort), ("order", "desc")]);
+        }
+        let res = self.send(req).await?;
+        let found: SearchResponse = Self::json(res).await?;
+        Ok(found.items)
+    }
+
+    /// Whether `username/repo` exists and is visible to this client.
+    pub async fn repository_exists(&self, username: &str, repo: &str) -> Result<bool> {
+        let url = format!("{}/{}/{}", self.base_url, username, repo);
+        match self.send(self.client.get(url.as_str())).await {
+            Ok(_) => Ok(true),
+            Err(e) if matches!(e.kind(), ErrorKind::NotFound { .. }) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the branch `username/repo` is downloaded from by default on
+    /// GitHub, which may not be `main`.
+    pub async fn default_branch(&self, username: &str, repo: &str) -> Result<String> {
+        let url = format!("{}/{}/{}", self.base_url, username, repo);
+        let res = self.send(self.client.get(url.as_str())).await?;
+        let info: RepoInfo = Self::json(res).await?;
+        Ok(info.default_branch)
+    }
+
+    /// Returns the names of the public repositories of `owner` (a user or
+    /// an organization), up to the first 100.
+    pub async fn owner_repositories(&self, owner: &str) -> Result<Vec<String>> {
+        let url = format!("{}/{}/repos", self.users_url, owner);
+        let req = self
+            .client
+            .get(url.as_str())
+            .query(&[("per_page", "100"), ("sort", "pushed")]);
+        let res = self.send(req).await?;
+        let repos: Vec<OwnedRepo> = Self::json(res).await?;
+        Ok(repos.into_iter().map(|r| r.name).collect())
+    }
+
+    /// Returns how GitHub verified the signature of the tag `tag`, or
+    /// `None` if it is a lightweight tag, which cannot be signed.
+    pub async fn tag_verification(
+        &self,
+        username: &str,
+        repo: &str,
+        tag: &str,
+    ) -> Result<Option<Verification>> {
+        let url = format!(
+            "{}/{}/{}/git/ref/tags/{}",
+            self.base_url, username, repo, tag
+        );
+        let res = self.send(self.client.get(url.as_str())).await?;
+        let tag_ref: RefResponse = Self::json(res).await?;
+        if tag_ref.object.ty != "tag" {
+            return Ok(None);
+        }
+
+        let url = format!(
+            "{}/{}/{}/git/tags/{}",
+            self.base_url, username, repo, tag_ref.object.sha
+        );
+        let res = self.send(self.client.get(url.as_str())).await?;
+        let tag: TagResponse = Self::json(res).await?;
+        Ok(Some(tag.verification.unwrap_or(Verification {
+            verified: false,
+            reason: "unsigned".to_string(),
+        })))
+    }
+
+    /// Returns the paths added or modified between `since` and `tree`.
+    ///
+    /// `since` is either a commit (anything `compare` accepts) or a date such
+    /// as `2022-05-01`, in which case the last commit on `tree` before that
+    /// date is compared against. `None` means no commit is older than
+    /// `since`, or the comparison lists more files than GitHub returns, so
+    /// every path counts as changed.
+    pub async fn changed_since(
+        &self,
+        username: &str,
+        repo: &str,
+        tree: &str,
+        since: &str,
+    ) -> Result<Option<HashSet<String>>> {
+        let base = if is_date(since) {
+            match self.commit_before(username, repo, tree, since).await? {
+                Some(sha) => sha,
+                None => return Ok(None),
+            }
+        } else {
+            since.to_string()
+        };
+
+        let url = format!(
+            "{}/{}/{}/compare/{}...{}",
+            self.base_url, username, repo, base, tree
+        );
+        let mut changed = HashSet::new();
+        for page in 1.. {
+            let req = self
+                .client
+                .get(url.as_str())
+                .query(&[("page", page.to_string()), ("per_page", "100".to_string())]);
+            let res = self
+                .send(req)
+                .await
+                .map_err(|e| missing_tree(e, since, username, repo))?;
+
+            let body: CompareResponse = Self::json(res).await?;
+            if body.files.is_empty() {
+                // The page before was full, and GitHub stops listing files
+                // at that cap, so the changes may run past what was listed.
+                // Treating every path as changed selects too much rather
+                // than silently leaving files out.
+                return Ok(if page == 1 { Some(changed) } else { None });
+            }
+            let full = body.files.len() >= COMPARE_PAGE_FILES;
+            changed.extend(
+                body.files
+                    .into_iter()
+                    .filter(|f| f.status != "removed")
+                    .map(|f| f.filename),
+            );
+            if !full {
+                break;
+            }
+        }
+        Ok(Some(changed))
+    }
+
+    /// Looks up when each of `paths` was last changed on `tree`, as ISO 8601
+    /// dates. Paths without history on `tree` are left out.
+    ///
+    /// This asks the GraphQL API, a chunk of paths per query, so it needs a
+    /// token.
+    pub async fn last_commit_dates(
+        &self,
+        username: &str,
+        repo: &str,
+        tree: &str,
+        paths: &[String],
+    ) -> Result<HashMap<String, String>> {
+        let mut dates = HashMap::new();
+        for chunk in paths.chunks(HISTORY_CHUNK) {
+            // GraphQL strings are escaped like JSON ones.
+            let quote = |s: &str| serde_json::to_string(s).unwrap_or_default();
+            let histories: String = chunk
+                .iter()
+                .enumerate()
+                .map(|(i, path)| {
+                    format!(
+                        "p{}: history(first: 1, path: {}) {{ nodes {{ committedDate }} }} ",
+                        i,
+                        quote(path)
+                    )
+                })
+                .collect();
+            let query = format!(
+                "query {{ repository(owner: {}, name: {}) {{ object(expression: {}) {{ ... on Commit {{ {}}} }} }} }}",
+                quote(username),
+                quote(repo),
+                quote(tree),
+                histories
+            );
+
+            let req = self
+                .client
+                .post(self.graphql_url.as_str())
+                .json(&serde_json::json!({ "query": query }));
+            let res = self.send(req).await?;
+            let body: GraphQlResponse<HistoryData> = Self::json(res).await?;
+            if let Some(e) = body.errors.into_iter().next() {
+                return Error::err(ErrorKind::GraphQlFailure { msg: e.message });
+            }
+
+            let histories = body
+                .data
+                .and_then(|d| d.repository)
+                .and_then(|r| r.object)
+                .unwrap_or_default();
+            for (i, path) in chunk.iter().enumerate() {
+                let date = histories
+                    .get(&format!("p{}", i))
+                    .and_then(|h| h.nodes.first())
+                    .map(|c| c.committed_date.clone());
+                if let Some(date) = date {
+                    dates.insert(path.clone(), date);
+                }
+            }
+        }
+        Ok(dates)
+    }
+
+    /// Resolves `tree` (a branch, tag or SHA) to the SHA of the commit it
+    /// currently points at.
+    pub async fn resolve_commit(&self, username: &str, repo: &str, tree: &str) -> Result<String> {
+        Ok(self.get_commit(username, repo, tree).await?.sha)
+    }
+
+    /// Returns the commit `tree` (a branch, tag or SHA) currently points at.
+    pub async fn get_commit(
+        &self,
+        username: &str,
+        repo: &str,
+        tree: &str,
+    ) -> Result<CommitResponse> {
+        let url = format!("{}/{}/{}/commits/{}", self.base_url, username, repo, tree);
+        let req = self.client.get(url.as_str());
+
+        let res = self
+            .send(req)
+            .await
+            .map_err(|e| missing_tree(e, tree, username, repo))?;
+
+        Self::json(res).await
+    }
+}
+
+/// Whether `since` is a date (`YYYY-MM-DD`, optionally followed by a time)
+/// rather than a commit.
+pub fn is_date(since: &str) -> bool {
+    let date = since.split('T').next().unwrap_or_default();
+    let parts: Vec<&str> = date.split('-').collect();
+    parts.len() == 3
+        && [4, 2, 2]
+            .iter()
+            .zip(&parts)
+            .all(|(len, part)| part.len() == *len && part.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Whether `tree` is a full commit SHA, which never moves.
+pub fn is_commit_sha(tree: &str) -> bool {
+    tree.len() == 40 && tree.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Turns an unsuccessful response into the error it stands for.
+pub(crate) async fn status_error(res: Response) -> Box<Error> {
+    let status = res.status();
+    let url = res.url().to_string();
+    let header = |name: &str| {
+        res.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+    };
+    let retry_after = throttle::secondary_limit_wait(status, res.headers());
+    let rate_limited = status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::FORBIDDEN
+            && header("x-ratelimit-remaining").as_deref() == Some("0"));
+    let reset = header("x-ratelimit-reset").and_then(|r| r.parse().ok());
+
+    // Error bodies are `{"message": ..., "documentation_url": ...}`, but
+    // anything is possible.
+    let text = res.text().await.unwrap_or_default();
+    let (msg, documentation_url) = match serde_json::from_str::<ApiError>(&text) {
+        Ok(e) => (e.message, e.documentation_url),
+        Err(_) => (text, None),
+    };
+
+    let secondary = retry_after.is_some()
+        || (status == StatusCode::FORBIDDEN && msg.contains("secondary rate limit"));
+
+    Error::new(match status {
+        _ if secondary => ErrorKind::SecondaryRateLimited {
+            status,
+            retry_after: retry_after.map(|w| w.as_secs()),
+            documentation_url,
+        },
+        _ if rate_limited => ErrorKind::RateLimited {
+            status,
+            reset,
+            documentation_url,
+        },
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorKind::AuthFailure {
+            status,
+            msg,
+            documentation_url,
+        },
+        StatusCode::NOT_FOUND => ErrorKind::NotFound {
+            url,
+            msg,
+            documentation_url,
+        },
+        _ => ErrorKind::GitHubStatusFailure { status, msg },
+    })
+}
+
+/// A request about `tree` that the API answers with "not found" (or, for
+/// commits, "unprocessable") means the tree does not exist; any other
+/// failure is passed on as is.
+fn missing_tree(e: Box<Error>, tree: &str, username: &str, repo: &str) -> Box<Error> {
+    match e.kind() {
+        ErrorKind::NotFound { .. } => {}
+        ErrorKind::GitHubStatusFailure { status, .. }
+            if *status == StatusCode::UNPROCESSABLE_ENTITY => {}
+        _ => return e,
+    }
+    Error::new(ErrorKind::TreeDoesNotExist {
+        tree: tree.to_string(),
+        repo: format!("{}/{}", username, repo),
+    })
+}
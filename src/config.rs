@@ -0,0 +1,134 @@
+use crate::error::{Error, ErrorKind, Result};
+use crate::hooks::Hooks;
+use globset::{GlobBuilder, GlobMatcher};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The user configuration, read from `<config dir>/gitdown/config.toml`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Commands run around downloads.
+    pub hooks: Hooks,
+    /// Check once a day for a newer release and mention it after a run.
+    pub update_check: bool,
+    /// Ask before `--all` downloads more files than this (500 by default).
+    pub confirm_all_above: Option<usize>,
+    /// Ask before downloading a selection larger than this size, like `2G`
+    /// (1 GiB by default).
+    pub confirm_above: Option<String>,
+    /// Settings for the repositories matching a `user/repo` glob, like
+    /// `[repos."mycorp/*"]`.
+    pub repos: BTreeMap<String, RepoConfig>,
+    /// Where the config was read from; `None` if it was not.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+/// The settings of `[repos."PATTERN"]`, which apply when the repositories a
+/// run names on the command line match `PATTERN`. Flags still win over them,
+/// but they win over the environment.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepoConfig {
+    /// The environment variable holding the token, instead of
+    /// `GITDOWN_TOKEN` or `GITHUB_TOKEN`.
+    pub token_env: Option<String>,
+    /// The API root, like `https://github.example.com/api/v3`.
+    pub api_url: Option<String>,
+    /// The endpoint serving raw file contents.
+    pub raw_url: Option<String>,
+    /// The `--provider` to list and fetch with.
+    pub provider: Option<String>,
+    /// The `--jobs` to download with.
+    pub jobs: Option<usize>,
+    /// The `--out` directory to write to; a leading `~/` is the home
+    /// directory.
+    pub out_dir: Option<PathBuf>,
+}
+
+impl Config {
+    /// The default location of the config file, if the platform has one.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("gitdown").join("config.toml"))
+    }
+
+    /// Loads the config from `path`, or from the default location when `path`
+    /// is `None`. A missing default config file is not an error.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => {
+                return Ok(Config::default())
+            }
+            Err(_) => {
+                return Error::err(ErrorKind::ReadFailure {
+                    path: path.display().to_string(),
+                })
+            }
+        };
+
+        let malformed = |reason: String| {
+            Error::err(ErrorKind::MalformedConfig {
+                path: path.display().to_string(),
+                reason,
+            })
+        };
+        let config: Config = match toml::from_str(&text) {
+            Ok(config) => config,
+            Err(e) => return malformed(e.to_string()),
+        };
+        for (pattern, repo) in &config.repos {
+            if let Err(e) = repo_glob(pattern) {
+                return malformed(format!("[repos.\"{}\"]: {}", pattern, e));
+            }
+            if repo.jobs == Some(0) {
+                return malformed(format!("[repos.\"{}\"]: jobs must be at least 1", pattern));
+            }
+        }
+        Ok(Config {
+            path: Some(path),
+            ..config
+        })
+    }
+
+    /// The settings for `user/repo`: those of the longest pattern matching
+    /// it, so `mycorp/tools` can refine `mycorp/*`.
+    pub fn repo(&self, user: &str, repo: &str) -> Option<(&str, &RepoConfig)> {
+        let name = format!("{}/{}", user, repo);
+        self.repos
+            .iter()
+            .filter(|(pattern, _)| repo_glob(pattern).is_ok_and(|glob| glob.is_match(&name)))
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(pattern, repo)| (pattern.as_str(), repo))
+    }
+}
+
+impl RepoConfig {
+    /// The output directory, with `~/` expanded.
+    pub fn out_dir(&self) -> Option<PathBuf> {
+        let dir = self.out_dir.as_ref()?;
+        match (dir.strip_prefix("~"), dirs::home_dir()) {
+            (Ok(rest), Some(home)) => Some(home.join(rest)),
+            _ => Some(dir.clone()),
+        }
+    }
+}
+
+/// Compiles a `[repos]` pattern, in which `*` does not match `/`.
+fn repo_glob(pattern: &str) -> std::result::Result<GlobMatcher, globset::Error> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+}
@@ -0,0 +1,106 @@
+use crate::error::Result;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+/// How the user chose to settle a conflict with an existing local file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// Replace the local file with the remote one.
+    Overwrite,
+    /// Keep the local file and drop the remote one.
+    Skip,
+    /// Write the remote file to another path instead.
+    Rename(PathBuf),
+}
+
+/// Asks the user, one file at a time, what to do with local files that differ
+/// from the ones being downloaded.
+///
+/// Downloads run concurrently, so prompts are serialized behind a lock; once
+/// the user answers "all", every later conflict is overwritten silently.
+#[derive(Debug, Default)]
+pub struct ConflictPrompt {
+    overwrite_all: Mutex<bool>,
+}
+
+impl ConflictPrompt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prompts for `dest`, whose remote content is `remote`. A path given to
+    /// rename to is taken relative to `out_dir`.
+    pub fn resolve(&self, dest: &Path, remote: &[u8], out_dir: &Path) -> Result<Resolution> {
+        let mut overwrite_all = self.overwrite_all.lock().unwrap();
+        if *overwrite_all {
+            return Ok(Resolution::Overwrite);
+        }
+
+        loop {
+            let answer = ask(&format!(
+                "{} differs from the remote file. [o]verwrite, [s]kip, [d]iff, [r]ename, overwrite [a]ll? ",
+                dest.display()
+            ))?;
+
+            match answer.as_deref().map(str::trim) {
+                Some("o") => return Ok(Resolution::Overwrite),
+                // A closed stdin is treated as "skip" so user edits are never lost.
+                Some("s") | Some("") | None => return Ok(Resolution::Skip),
+                Some("d") => show_diff(dest, remote)?,
+                Some("r") => {
+                    let question = format!("Write the remote file to (in {}): ", out_dir.display());
+                    if let Some(path) = ask(&question)? {
+                        let path = path.trim();
+                        if !path.is_empty() {
+                            return Ok(Resolution::Rename(out_dir.join(path)));
+                        }
+                    }
+                }
+                Some("a") => {
+                    *overwrite_all = true;
+                    return Ok(Resolution::Overwrite);
+                }
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+/// Asks a yes/no `question`, where anything but `y` (including EOF) is no.
+pub fn confirm(question: &str) -> Result<bool> {
+    let answer = ask(&format!("{} [y/N] ", question))?;
+    Ok(matches!(
+        answer.as_deref().map(str::trim),
+        Some("y") | Some("Y")
+    ))
+}
+
+/// Prints `question` and reads a line of input, returning `None` on EOF.
+fn ask(question: &str) -> Result<Option<String>> {
+    eprint!("{}", question);
+    io::stderr().flush()?;
+
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(line))
+    }
+}
+
+/// Shows a unified diff of the local file against the remote content.
+fn show_diff(local: &Path, remote: &[u8]) -> Result<()> {
+    let mut child = Command::new("diff")
+        .args(["-u", "--label", "local", "--label", "remote"])
+        .arg(local)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    // `diff` exits with 1 when the inputs differ, so only I/O errors matter.
+    child.stdin.take().unwrap().write_all(remote)?;
+    child.wait()?;
+    Ok(())
+}
@@ -0,0 +1,44 @@
+//! The time budget of a run, `--max-time`.
+//!
+//! Downloads still running at the deadline are cancelled and reported as
+//! failed, so the files that did arrive are kept, recorded and reported
+//! like in any partly failed run. Whatever else is still running then, such
+//! as a listing, is given up on [`GRACE`] later, when the run as a whole
+//! fails with [`ErrorKind::OutOfTime`](crate::error::ErrorKind::OutOfTime).
+
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long the run has past the deadline to wrap up the cancelled
+/// downloads.
+pub const GRACE: Duration = Duration::from_secs(2);
+
+static DEADLINE: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Starts the budget of `limit` from now.
+pub fn start(limit: Duration) {
+    *DEADLINE.lock().unwrap() = Some(Instant::now() + limit);
+}
+
+/// When the budget runs out, if there is one.
+pub fn deadline() -> Option<Instant> {
+    *DEADLINE.lock().unwrap()
+}
+
+/// Runs `future` until it completes or the budget runs out, whichever is
+/// first; `None` means the budget ran out.
+pub async fn within<F: Future>(future: F) -> Option<F::Output> {
+    match deadline() {
+        Some(deadline) => tokio::time::timeout_at(deadline.into(), future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Completes [`GRACE`] after the deadline, or never without a budget.
+pub async fn expired() {
+    match deadline() {
+        Some(deadline) => tokio::time::sleep_until((deadline + GRACE).into()).await,
+        None => std::future::pending().await,
+    }
+}
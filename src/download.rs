@@ -0,0 +1,258 @@
+//! Downloading a repository as a stream of events, for front-ends that embed
+//! gitdown and render their own progress.
+
+use crate::client::{Client, GitHubDirEntry};
+use crate::error::{Error, Result};
+use crate::longpath;
+use crate::overwrite;
+use crate::progress::Throughput;
+use crate::usage;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use reqwest::Response;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Something that happened while downloading.
+#[derive(Debug)]
+pub enum Event {
+    /// The repository was listed at `commit`, which every file is then
+    /// downloaded from; this is always the first event.
+    Listed {
+        files: usize,
+        bytes: usize,
+        commit: String,
+    },
+    /// The download of a file started.
+    Started { path: String, size: Option<usize> },
+    /// Part of a file was received and written, with the file's current
+    /// rate and, if its size is known, the time its download has left.
+    Chunk {
+        path: String,
+        bytes: usize,
+        bytes_per_sec: Option<f64>,
+        eta: Option<Duration>,
+    },
+    /// A file was written to `dest`.
+    Finished {
+        path: String,
+        dest: PathBuf,
+        bytes: usize,
+    },
+    /// A file could not be downloaded; the other downloads carry on.
+    Failed { path: String, error: Box<Error> },
+}
+
+/// Downloads the files of a repository, reporting its progress as events.
+pub struct Downloader<'c> {
+    client: &'c Client,
+    concurrency: usize,
+}
+
+impl<'c> Downloader<'c> {
+    pub fn new(client: &'c Client) -> Self {
+        Self {
+            client,
+            concurrency: 4,
+        }
+    }
+
+    /// Sets how many files are downloaded at once (4 by default).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Lists `user/repo` at `tree` (the default branch when `None`) and
+    /// returns the events of downloading every file into `out`.
+    ///
+    /// Only a failure to list the repository is returned as an error; the
+    /// failure of a single file is reported as [`Event::Failed`].
+    pub async fn download<'s>(
+        &'s self,
+        user: &'s str,
+        repo: &'s str,
+        tree: Option<&'s str>,
+        out: &'s Path,
+    ) -> Result<BoxStream<'s, Event>> {
+        let tree = match tree {
+            Some(tree) => tree.to_string(),
+            None => self.client.default_branch(user, repo).await?,
+        };
+        // A branch that moves mid-download would otherwise mix two trees.
+        let commit = self.client.resolve_commit(user, repo, &tree).await?;
+        let entries = self.client.get_dentries(user, repo, Some(&commit)).await?;
+        let listed = Event::Listed {
+            files: entries.len(),
+            bytes: entries.iter().filter_map(|e| e.size).sum(),
+            commit: commit.clone(),
+        };
+
+        let files = entries.into_iter().filter_map(move |entry| {
+            let GitHubDirEntry { path, size, .. } = entry;
+            let path = path?;
+            Some(FileJob {
+                url: self.client.raw_url(user, repo, &commit, &path),
+                dest: out.join(&path),
+                path,
+                size,
+            })
+        });
+        let downloads = stream::iter(files)
+            .map(move |job| Box::pin(self.file_events(job)))
+            .flatten_unordered(self.concurrency);
+
+        Ok(stream::once(async { listed }).chain(downloads).boxed())
+    }
+
+    /// The events of downloading a single file.
+    fn file_events(&self, job: FileJob) -> impl Stream<Item = Event> + '_ {
+        stream::unfold(FileState::Start(job), move |state| async move {
+            match state {
+                FileState::Start(job) => Some(self.start(job).await),
+                FileState::Body(body) => Some(body.next_chunk().await),
+                FileState::Done => None,
+            }
+        })
+    }
+
+    async fn start(&self, job: FileJob) -> (Event, FileState) {
+        let FileJob {
+            path,
+            url,
+            dest,
+            size,
+        } = job;
+        let failed = |path, error| (Event::Failed { path, error }, FileState::Done);
+
+        usage::record_raw();
+        let res = match self
+            .client
+            .http()
+            .get(url.as_str())
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+        {
+            Ok(res) => res,
+            Err(e) => return failed(path, e.into()),
+        };
+        let io_dest = longpath::for_io(&dest).into_owned();
+        let part = overwrite::part_path(&io_dest);
+        let file = match io_dest
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| File::create(&part))
+        {
+            Ok(file) => file,
+            Err(e) => return failed(path, e.into()),
+        };
+
+        let started = Event::Started {
+            path: path.clone(),
+            size,
+        };
+        let body = Body {
+            path,
+            dest,
+            io_dest,
+            part,
+            file,
+            res,
+            size,
+            written: 0,
+            throughput: Throughput::new(),
+        };
+        (started, FileState::Body(Box::new(body)))
+    }
+}
+
+/// A file to download.
+struct FileJob {
+    path: String,
+    url: String,
+    dest: PathBuf,
+    size: Option<usize>,
+}
+
+enum FileState {
+    Start(FileJob),
+    Body(Box<Body>),
+    Done,
+}
+
+/// A download in progress, written to `part` until it completes.
+struct Body {
+    path: String,
+    dest: PathBuf,
+    /// `dest` as file system calls take it.
+    io_dest: PathBuf,
+    part: PathBuf,
+    file: File,
+    res: Response,
+    size: Option<usize>,
+    written: usize,
+    throughput: Throughput,
+}
+
+impl Body {
+    async fn next_chunk(mut self: Box<Self>) -> (Event, FileState) {
+        let chunk = match self.res.chunk().await {
+            Ok(chunk) => chunk,
+            Err(e) => return self.fail(e.into()),
+        };
+        match chunk {
+            Some(bytes) => {
+                if let Err(e) = self.file.write_all(&bytes) {
+                    return self.fail(e.into());
+                }
+                self.written += bytes.len();
+                self.throughput.record(bytes.len() as u64);
+                let remaining = self.size.map(|s| s.saturating_sub(self.written) as u64);
+                let event = Event::Chunk {
+                    path: self.path.clone(),
+                    bytes: bytes.len(),
+                    bytes_per_sec: self.throughput.bytes_per_sec(),
+                    eta: remaining.and_then(|r| self.throughput.eta(r)),
+                };
+                (event, FileState::Body(self))
+            }
+            None => {
+                let Body {
+                    path,
+                    dest,
+                    io_dest,
+                    part,
+                    file,
+                    written,
+                    ..
+                } = *self;
+                drop(file);
+                if let Err(e) = fs::rename(&part, &io_dest) {
+                    let _ = fs::remove_file(&part);
+                    let event = Event::Failed {
+                        path,
+                        error: e.into(),
+                    };
+                    return (event, FileState::Done);
+                }
+                let event = Event::Finished {
+                    path,
+                    dest,
+                    bytes: written,
+                };
+                (event, FileState::Done)
+            }
+        }
+    }
+
+    fn fail(self: Box<Self>, error: Box<Error>) -> (Event, FileState) {
+        let _ = fs::remove_file(&self.part);
+        let event = Event::Failed {
+            path: self.path,
+            error,
+        };
+        (event, FileState::Done)
+    }
+}
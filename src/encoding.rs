@@ -0,0 +1,33 @@
+//! Compressed transfers of raw file contents.
+//!
+//! Text compresses well, so downloads ask for gzip or brotli and decode the
+//! body themselves, which keeps the size on the wire known.
+
+use flate2::read::GzDecoder;
+use std::io::{self, Read};
+
+/// The `Accept-Encoding` of raw downloads.
+pub const ACCEPT_ENCODING: &str = "gzip, br";
+
+/// Decodes a body sent with `Content-Encoding: encoding`; bodies without an
+/// encoding, or with `identity`, are returned as they are.
+pub fn decode(encoding: Option<&str>, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(bytes.len() * 4);
+    match encoding.map(str::trim) {
+        None | Some("") | Some("identity") => return Ok(bytes),
+        Some("gzip") | Some("x-gzip") => {
+            GzDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+        }
+        Some("br") => {
+            brotli_decompressor::Decompressor::new(bytes.as_slice(), 4096)
+                .read_to_end(&mut decoded)?;
+        }
+        Some(other) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported content encoding {}", other),
+            ))
+        }
+    }
+    Ok(decoded)
+}
@@ -8,28 +8,235 @@ pub struct Error {
 
 #[derive(Debug)]
 pub enum ErrorKind {
+    AuthFailure {
+        status: reqwest::StatusCode,
+        msg: String,
+        documentation_url: Option<String>,
+    },
+    CannotBundle {
+        lockfile: String,
+        reason: String,
+    },
+    ChecksumMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+    ConflictingOptions {
+        option: String,
+        with: String,
+    },
+    ConflictingRepoSections {
+        first: String,
+        second: String,
+    },
+    DirectoryNotEmpty {
+        path: String,
+    },
     DownloadFailure {
         path: String,
     },
+    EditorFailure {
+        command: String,
+        status: String,
+    },
     EmptyText,
+    FileExists {
+        path: String,
+    },
     GitHubStatusFailure {
         status: reqwest::StatusCode,
         msg: String,
     },
+    GitFailure {
+        command: String,
+        output: String,
+    },
+    GitRepositoryExists {
+        path: String,
+    },
+    GraphQlFailure {
+        msg: String,
+    },
+    HookFailure {
+        command: String,
+        status: String,
+    },
     Interrupted,
     MalformedRepo {
         repo: String
     },
+    MalformedBundle {
+        path: String,
+        reason: String,
+    },
+    MalformedCache {
+        path: String,
+        reason: String,
+    },
+    MalformedConfig {
+        path: String,
+        reason: String,
+    },
+    MalformedDefine {
+        define: String,
+    },
+    MalformedDest {
+        dest: String,
+        reason: String,
+    },
+    MalformedFilter {
+        filter: String,
+        reason: String,
+    },
+    MalformedHistory {
+        path: String,
+        reason: String,
+    },
+    MalformedIgnore {
+        path: String,
+        reason: String,
+    },
+    MalformedLockfile {
+        path: String,
+        reason: String,
+    },
+    MalformedManifest {
+        line: usize,
+        reason: String,
+    },
+    MalformedMirror {
+        path: String,
+        reason: String,
+    },
+    MalformedOption {
+        option: String,
+        value: String,
+        /// What the option takes, e.g. "a number of seconds".
+        expected: String,
+    },
+    MalformedRename {
+        rule: String,
+        reason: String,
+    },
+    MalformedRender {
+        render: String,
+        reason: String,
+    },
+    MalformedReport {
+        path: String,
+        reason: String,
+    },
+    MalformedSelections {
+        path: String,
+        reason: String,
+    },
+    MissingOption {
+        option: String,
+        /// What else has to be given, e.g. "--ref to name a tag".
+        needs: String,
+    },
+    MissingProgram {
+        program: String,
+        purpose: String,
+    },
+    NoCommitBefore {
+        date: String,
+        tree: String,
+        repo: String,
+    },
+    NoConfigDir,
+    NoLastSelection {
+        repo: String,
+    },
+    NoRepositoriesFound {
+        query: String,
+    },
+    NoSuchProvider {
+        name: String,
+    },
+    NoSuchSelection {
+        name: String,
+    },
+    NotFound {
+        url: String,
+        msg: String,
+        documentation_url: Option<String>,
+    },
+    OutOfTime {
+        /// The `--max-time` budget, in seconds.
+        limit: u64,
+    },
+    PathDoesNotExist {
+        path: String,
+        repo: String,
+        /// Paths of the tree close to `path`, nearest first.
+        suggestions: Vec<String>,
+    },
+    PreflightFailure {
+        problems: usize,
+        files: usize,
+    },
+    ProviderFailure {
+        provider: String,
+        method: String,
+        reason: String,
+    },
+    RateLimited {
+        status: reqwest::StatusCode,
+        /// When the limit resets, in seconds since the epoch.
+        reset: Option<u64>,
+        documentation_url: Option<String>,
+    },
     ReadFailure {
         path: String,
     },
-    ResponseKeyError {
-        key: String
+    RepoDoesNotExist {
+        repo: String,
+        /// Repositories of the same owner close to `repo`, nearest first.
+        suggestions: Vec<String>,
+    },
+    MalformedResponse {
+        url: String,
+        reason: String,
+    },
+    SecondaryRateLimited {
+        status: reqwest::StatusCode,
+        /// How long GitHub asked to wait, in seconds.
+        retry_after: Option<u64>,
+        documentation_url: Option<String>,
+    },
+    SelectionTooLarge {
+        size: String,
+        limit: String,
     },
     TreeDoesNotExist {
         tree: String,
         repo: String
     },
+    UncommittedChanges {
+        paths: Vec<String>,
+    },
+    UnknownSubcommand {
+        command: String,
+        name: Option<String>,
+    },
+    UnsupportedOption {
+        option: String,
+        command: String,
+    },
+    UnverifiedTag {
+        tag: String,
+        repo: String,
+        reason: String,
+    },
+    UpdateFailure {
+        reason: String,
+    },
+    WikiUnavailable {
+        repo: String,
+        reason: String,
+    },
     HttpClientError(reqwest::Error),
     IoError(io::Error),
     Other {
@@ -89,39 +296,335 @@ impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use ErrorKind::*;
         match self {
+            AuthFailure {
+                status,
+                msg,
+                documentation_url,
+            } => {
+                if *status == reqwest::StatusCode::UNAUTHORIZED {
+                    write!(
+                        f,
+                        "GitHub rejected the credentials ({}): {}. Check that GITHUB_TOKEN holds a valid, unexpired token.",
+                        status, msg
+                    )?;
+                } else {
+                    write!(
+                        f,
+                        "GitHub denied access ({}): {}. Private repositories need GITHUB_TOKEN set to a token with access to them.",
+                        status, msg
+                    )?;
+                }
+                write_documentation_url(f, documentation_url)
+            }
+            CannotBundle { lockfile, reason } => write!(
+                f,
+                "Cannot bundle the files of {}: {}.",
+                lockfile, reason
+            ),
+            ChecksumMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The checksum of {} is {}, but {} was expected",
+                name, actual, expected
+            ),
+            ConflictingOptions { option, with } => {
+                write!(f, "{} cannot be used with {}.", option, with)
+            }
+            ConflictingRepoSections { first, second } => write!(
+                f,
+                "{} and {} match different [repos] sections of the config; download them separately.",
+                first, second
+            ),
+            DirectoryNotEmpty { path } => write!(
+                f,
+                "The directory {} is not empty; pass --force to write into it anyway",
+                path
+            ),
             DownloadFailure { path } => write!(
                 f,
                 "Downloading from {} caused an error",
                 path
             ),
+            EditorFailure { command, status } => {
+                write!(f, "{} exited with {}.", command, status)
+            }
             EmptyText => write!(f, "Text was not provided"),
+            FileExists { path } => {
+                write!(f, "{} exists; pass --force to replace it.", path)
+            }
             GitHubStatusFailure { status, msg } => write!(
                 f,
                 "GitHub API failure with response status {}: {}",
                 status, msg
             ),
-            Interrupted => write!(f, "Fzf was Interrupted"),
+            GitFailure { command, output } => {
+                write!(f, "`{}` failed", command)?;
+                if !output.is_empty() {
+                    write!(f, ": {}", output)?;
+                }
+                Ok(())
+            }
+            GitRepositoryExists { path } => {
+                write!(f, "{} is already a git repository.", path)
+            }
+            GraphQlFailure { msg } => write!(f, "GitHub GraphQL query failure: {}", msg),
+            HookFailure { command, status } => write!(
+                f,
+                "The hook `{}` failed with {}",
+                command, status
+            ),
+            Interrupted => write!(f, "Interrupted"),
             MalformedRepo { repo } => write!(
                 f,
                 "The given repo {} is malformed.",
                 repo 
             ),
+            MalformedBundle { path, reason } => write!(
+                f,
+                "The bundle {} is malformed: {}",
+                path, reason
+            ),
+            MalformedCache { path, reason } => write!(
+                f,
+                "The cache {} is malformed: {}",
+                path, reason
+            ),
+            MalformedConfig { path, reason } => write!(
+                f,
+                "The config file {} is malformed: {}",
+                path, reason
+            ),
+            MalformedDefine { define } => write!(
+                f,
+                "The definition {} is malformed; expected name=value",
+                define
+            ),
+            MalformedDest { dest, reason } => write!(
+                f,
+                "The destination {} is malformed: {}",
+                dest, reason
+            ),
+            MalformedFilter { filter, reason } => write!(
+                f,
+                "The filter {} is malformed: {}",
+                filter, reason
+            ),
+            MalformedHistory { path, reason } => write!(
+                f,
+                "The selection history {} is malformed: {}",
+                path, reason
+            ),
+            MalformedIgnore { path, reason } => write!(
+                f,
+                "The ignore file {} is malformed: {}",
+                path, reason
+            ),
+            MalformedLockfile { path, reason } => write!(
+                f,
+                "The lockfile {} is malformed: {}",
+                path, reason
+            ),
+            MalformedManifest { line, reason } => write!(
+                f,
+                "Line {} of the manifest is malformed: {}",
+                line, reason
+            ),
+            MalformedMirror { path, reason } => write!(
+                f,
+                "The mirror manifest {} is malformed: {}",
+                path, reason
+            ),
+            MalformedOption {
+                option,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid {} {}: expected {}.",
+                option, value, expected
+            ),
+            MalformedRename { rule, reason } => write!(
+                f,
+                "The rename rule {} is malformed: {}",
+                rule, reason
+            ),
+            MalformedRender { render, reason } => write!(
+                f,
+                "The rendering {} is malformed: {}",
+                render, reason
+            ),
+            MalformedReport { path, reason } => write!(
+                f,
+                "The error log {} is malformed: {}",
+                path, reason
+            ),
+            MalformedSelections { path, reason } => write!(
+                f,
+                "The saved selections {} are malformed: {}",
+                path, reason
+            ),
+            RateLimited {
+                status,
+                reset,
+                documentation_url,
+            } => {
+                write!(f, "GitHub's rate limit was exceeded ({})", status)?;
+                if let Some(reset) = reset {
+                    let now = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or_default();
+                    let minutes = reset.saturating_sub(now).div_ceil(60);
+                    write!(f, "; it resets in {} minute(s)", minutes)?;
+                }
+                write!(f, ". Set GITHUB_TOKEN to raise the limit, or try again later.")?;
+                write_documentation_url(f, documentation_url)
+            }
             ReadFailure { path } => write!(
                 f,
                 "Reading from {} caused an error",
                 path
             ),
-            ResponseKeyError { key } => write!(
+            MalformedResponse { url, reason } => write!(
+                f,
+                "The response from {} is malformed: {}",
+                url, reason
+            ),
+            MissingOption { option, needs } => write!(f, "{} needs {}.", option, needs),
+            MissingProgram { program, purpose } => write!(
                 f,
-                "The response is missing the key: {}",
-                key 
+                "{} is needed {}, but it is not installed.",
+                program, purpose
+            ),
+            NoCommitBefore { date, tree, repo } => write!(
+                f,
+                "There is no commit on {} of repo {} before {}.",
+                tree, repo, date
+            ),
+            NoConfigDir => write!(
+                f,
+                "There is no config directory on this platform to keep selections in."
+            ),
+            NoLastSelection { repo } => write!(
+                f,
+                "Nothing was picked from {} before; run once without --last.",
+                repo
+            ),
+            NoRepositoriesFound { query } => {
+                write!(f, "No repositories match {}.", query)
+            }
+            NoSuchProvider { name } => write!(
+                f,
+                "There is no provider {}; install an executable named gitdown-provider-{} on PATH.",
+                name, name
+            ),
+            NoSuchSelection { name } => write!(
+                f,
+                "There is no selection saved as {}; `gitdown load` lists the saved ones.",
+                name
+            ),
+            NotFound {
+                url,
+                msg,
+                documentation_url,
+            } => {
+                write!(
+                    f,
+                    "GitHub could not find {} ({}). Check the owner, repo and ref names; private repositories are only visible with GITHUB_TOKEN set.",
+                    url, msg
+                )?;
+                write_documentation_url(f, documentation_url)
+            }
+            OutOfTime { limit } => write!(
+                f,
+                "The run did not finish within --max-time {}s; files still downloading were given up on.",
+                limit
+            ),
+            PathDoesNotExist {
+                path,
+                repo,
+                suggestions,
+            } => {
+                write!(f, "The path {} does not exist in repo {}.", path, repo)?;
+                write_suggestions(f, suggestions)
+            }
+            PreflightFailure { problems, files } => write!(
+                f,
+                "The preflight found problems with {} of {} files; nothing was downloaded.",
+                problems, files
+            ),
+            ProviderFailure {
+                provider,
+                method,
+                reason,
+            } => write!(
+                f,
+                "The provider {} failed to {}: {}",
+                provider, method, reason
+            ),
+            RepoDoesNotExist { repo, suggestions } => {
+                write!(f, "The repo {} does not exist, or is private.", repo)?;
+                write_suggestions(f, suggestions)
+            }
+            SecondaryRateLimited {
+                status,
+                retry_after,
+                documentation_url,
+            } => {
+                write!(
+                    f,
+                    "GitHub's secondary rate limit was hit ({}) and kept being hit after waiting",
+                    status
+                )?;
+                if let Some(secs) = retry_after {
+                    write!(f, "; it asked to wait {} second(s)", secs)?;
+                }
+                write!(f, ". Download fewer files at once, or try again later.")?;
+                write_documentation_url(f, documentation_url)
+            }
+            SelectionTooLarge { size, limit } => write!(
+                f,
+                "The selection is {}, more than the {} of --confirm-above; pass --yes to download it anyway.",
+                size, limit
             ),
             TreeDoesNotExist { tree, repo } => write!(
                 f,
-                "The tree {} does not exist for repo {}. If you did not specify a tree, specify master (by default, the tree is main).",
+                "The tree {} does not exist for repo {}. If you did not specify a tree, pass --ref master (by default, the tree is main).",
                 tree,
                 repo
             ),
+            UncommittedChanges { paths } => write!(
+                f,
+                "Refusing to overwrite uncommitted changes to {}; commit or stash them, or pass --force.",
+                paths.join(", ")
+            ),
+            UnknownSubcommand { command, name: Some(name) } => write!(
+                f,
+                "{} has no subcommand {}; see {} --help.",
+                command, name, command
+            ),
+            UnknownSubcommand { command, name: None } => write!(
+                f,
+                "{} needs a subcommand; see {} --help.",
+                command, command
+            ),
+            UnsupportedOption { option, command } => write!(
+                f,
+                "{} is not supported by gitdown {}.",
+                option, command
+            ),
+            UnverifiedTag { tag, repo, reason } => write!(
+                f,
+                "Refusing to download {} of {}: its signature could not be verified ({}).",
+                tag, repo, reason
+            ),
+            UpdateFailure { reason } => write!(f, "Could not update gitdown: {}", reason),
+            WikiUnavailable { repo, reason } => {
+                write!(f, "Could not list the wiki of {}: {}", repo, reason)
+            }
             HttpClientError(_) => write!(f, "Network request failure"),
             IoError(_) => write!(f, "I/O failure"),
             Other { status } => write!(f, "An error occured: {}", status),
@@ -129,6 +632,21 @@ impl fmt::Display for ErrorKind {
     }
 }
 
+fn write_documentation_url(f: &mut fmt::Formatter, url: &Option<String>) -> fmt::Result {
+    match url {
+        Some(url) => write!(f, " See {}", url),
+        None => Ok(()),
+    }
+}
+
+fn write_suggestions(f: &mut fmt::Formatter, suggestions: &[String]) -> fmt::Result {
+    match suggestions {
+        [] => Ok(()),
+        [only] => write!(f, " Did you mean {}?", only),
+        [rest @ .., last] => write!(f, " Did you mean {} or {}?", rest.join(", "), last),
+    }
+}
+
 impl From<reqwest::Error> for Box<Error> {
     fn from(err: reqwest::Error) -> Box<Error> {
         Error::new(ErrorKind::HttpClientError(err))
@@ -0,0 +1,91 @@
+//! The `ETag` and `Last-Modified` of downloaded files, so later runs can ask
+//! for them conditionally and skip those that did not change.
+
+use crate::error::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a file answered with last time, kept in the user cache dir.
+///
+/// Raw urls name the commit, which changes with every push, so validators are
+/// kept by file rather than by url. The `ETag` of a raw file is of its
+/// content, so it still matches at another commit if the file did not change.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Validators {
+    /// Keyed by [`Validators::key`].
+    #[serde(default)]
+    pub files: BTreeMap<String, Validator>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validator {
+    /// Where the content was written.
+    pub dest: PathBuf,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// The blob SHA of what was written, so a local change is noticed.
+    pub sha: String,
+}
+
+impl Validators {
+    /// Where the validators are kept, if there is a cache dir.
+    pub fn path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|d| d.join("gitdown").join("etags.json"))
+    }
+
+    /// Loads the validators at `path`; a missing file has none.
+    pub fn load(path: &Path) -> Result<Validators> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Validators::default()),
+            Err(_) => {
+                return Error::err(ErrorKind::ReadFailure {
+                    path: path.display().to_string(),
+                })
+            }
+        };
+
+        serde_json::from_str(&text).or_else(|e| {
+            Error::err(ErrorKind::MalformedCache {
+                path: path.display().to_string(),
+                reason: e.to_string(),
+            })
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = match serde_json::to_string_pretty(self) {
+            Ok(text) => text,
+            Err(e) => {
+                return Error::err(ErrorKind::MalformedCache {
+                    path: path.display().to_string(),
+                    reason: e.to_string(),
+                })
+            }
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text + "\n")?;
+        Ok(())
+    }
+
+    /// What the file at `path` of `repo` (`user/repo`) is kept under once
+    /// written to `dest`.
+    pub fn key(repo: &str, path: &str, dest: &Path) -> String {
+        format!("{}:{} -> {}", repo, path, dest.display())
+    }
+
+    /// The validator of `key`, if its content was written to `dest`. It only
+    /// applies if the file there is still what was written, which the caller
+    /// checks, as it means reading and hashing the file.
+    pub fn get(&self, key: &str, dest: &Path) -> Option<&Validator> {
+        self.files.get(key).filter(|v| v.dest == dest)
+    }
+
+    pub fn record(&mut self, key: String, validator: Validator) {
+        self.files.insert(key, validator);
+    }
+}
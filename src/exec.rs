@@ -0,0 +1,56 @@
+use crate::error::{Error, ErrorKind, Result};
+use crate::shell;
+use std::path::PathBuf;
+
+/// A command run on downloaded files, in the style of `find -exec`.
+///
+/// `{}` is replaced by the (quoted) path of each file and the command runs
+/// once per file; `{+}` is replaced by all paths and the command runs once.
+/// A command with neither gets the path appended.
+#[derive(Debug, Clone)]
+pub struct ExecHook {
+    command: String,
+}
+
+impl ExecHook {
+    pub fn new(command: &str) -> Self {
+        let command = if command.contains("{}") || command.contains("{+}") {
+            command.to_string()
+        } else {
+            format!("{} {{}}", command)
+        };
+        Self { command }
+    }
+
+    /// Runs the hook over `files`, stopping at the first failing command.
+    pub fn run(&self, files: &[PathBuf]) -> Result<()> {
+        if files.is_empty() {
+            return Ok(());
+        }
+
+        let quoted: Vec<String> = files
+            .iter()
+            .map(|f| shell::quote(&f.to_string_lossy()))
+            .collect();
+
+        if self.command.contains("{+}") {
+            self.spawn(&self.command.replace("{+}", &quoted.join(" ")))
+        } else {
+            quoted
+                .iter()
+                .try_for_each(|file| self.spawn(&self.command.replace("{}", file)))
+        }
+    }
+
+    fn spawn(&self, command: &str) -> Result<()> {
+        let status = shell::command(command).status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Error::err(ErrorKind::HookFailure {
+                command: command.to_string(),
+                status: status.to_string(),
+            })
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! The exit status of gitdown, which scripts can branch on:
+//!
+//! | code | meaning                                                     |
+//! |------|-------------------------------------------------------------|
+//! | 0    | everything selected was downloaded (or skipped on purpose)  |
+//! | 1    | any other error                                             |
+//! | 2    | the run finished, but some files failed to download, or it  |
+//! |      | ran out of `--max-time`                                     |
+//! | 3    | the repository, ref or path does not exist                  |
+//! | 4    | GitHub refused the request: bad credentials or rate limited |
+//! | 130  | interrupted, e.g. the picker was cancelled with Ctrl-C      |
+
+use crate::error::{Error, ErrorKind};
+use crate::summary::Summary;
+
+pub const SUCCESS: i32 = 0;
+pub const FAILURE: i32 = 1;
+pub const PARTIAL_FAILURE: i32 = 2;
+pub const NOT_FOUND: i32 = 3;
+pub const AUTH_OR_RATE_LIMIT: i32 = 4;
+pub const INTERRUPTED: i32 = 130;
+
+/// The exit status of a run that failed with `error`.
+pub fn for_error(error: &Error) -> i32 {
+    use ErrorKind::*;
+    match error.kind() {
+        TreeDoesNotExist { .. }
+        | PathDoesNotExist { .. }
+        | RepoDoesNotExist { .. }
+        | NoCommitBefore { .. }
+        | NotFound { .. } => NOT_FOUND,
+        AuthFailure { .. } | RateLimited { .. } | SecondaryRateLimited { .. } => AUTH_OR_RATE_LIMIT,
+        Interrupted => INTERRUPTED,
+        OutOfTime { .. } => PARTIAL_FAILURE,
+        _ => FAILURE,
+    }
+}
+
+/// The exit status of a run that completed with `summary`.
+pub fn for_summary(summary: &Summary) -> i32 {
+    if summary.failed > 0 {
+        PARTIAL_FAILURE
+    } else {
+        SUCCESS
+    }
+}
@@ -0,0 +1,335 @@
+//! Selection expressions, `--where`: conditions on the path, extension, size
+//! and age of listed files, combined with `&&`, `||`, `!` and parentheses.
+//!
+//! ```text
+//! ext==md && size<100kb && path~docs/
+//! !(ext==lock || size>=1M) && age<30d
+//! ```
+//!
+//! `path`, `name` and `ext` (without the dot, case-insensitive) compare with
+//! `==` and `!=`, or match a regular expression with `~`. `size` takes sizes
+//! like `500`, `100kb` or `2M` and `age`, the time since the file last
+//! changed, durations like `90m`, `12h`, `30d`, `2w`, `6mo` or `1y`; both
+//! compare with `==`, `!=`, `<`, `<=`, `>` and `>=`. A file whose size or age
+//! is unknown fails every condition on it.
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::output;
+use regex::Regex;
+use std::path::Path;
+
+/// A parsed selection expression.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Text(TextField, TextTest),
+    Number(NumberField, Op, u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    Path,
+    Name,
+    Ext,
+}
+
+#[derive(Debug, Clone)]
+pub enum TextTest {
+    Equals(String),
+    NotEquals(String),
+    Matches(Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberField {
+    /// In bytes.
+    Size,
+    /// In seconds.
+    Age,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// What an expression is evaluated against.
+#[derive(Debug, Clone, Copy)]
+pub struct Facts<'a> {
+    pub path: &'a str,
+    pub size: Option<u64>,
+    /// Seconds since the file last changed.
+    pub age: Option<u64>,
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr> {
+        let malformed = |reason: String| {
+            Error::err(ErrorKind::MalformedFilter {
+                filter: text.to_string(),
+                reason,
+            })
+        };
+        let tokens = match tokenize(text) {
+            Ok(tokens) => tokens,
+            Err(reason) => return malformed(reason),
+        };
+        let mut parser = Parser { tokens, at: 0 };
+        match parser.or() {
+            Ok(expr) if parser.at == parser.tokens.len() => Ok(expr),
+            Ok(_) => malformed(format!("unexpected {}", parser.tokens[parser.at])),
+            Err(reason) => malformed(reason),
+        }
+    }
+
+    /// Whether the expression looks at `age`, which takes extra requests to
+    /// find out.
+    pub fn uses_age(&self) -> bool {
+        match self {
+            Expr::And(a, b) | Expr::Or(a, b) => a.uses_age() || b.uses_age(),
+            Expr::Not(e) => e.uses_age(),
+            Expr::Text(..) => false,
+            Expr::Number(field, ..) => *field == NumberField::Age,
+        }
+    }
+
+    pub fn is_match(&self, facts: &Facts) -> bool {
+        match self {
+            Expr::And(a, b) => a.is_match(facts) && b.is_match(facts),
+            Expr::Or(a, b) => a.is_match(facts) || b.is_match(facts),
+            Expr::Not(e) => !e.is_match(facts),
+            Expr::Text(field, test) => {
+                let path = Path::new(facts.path);
+                let value = match field {
+                    TextField::Path => facts.path.to_string(),
+                    TextField::Name => path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    TextField::Ext => path
+                        .extension()
+                        .map(|e| e.to_string_lossy().to_lowercase())
+                        .unwrap_or_default(),
+                };
+                match test {
+                    TextTest::Equals(s) => value == *s,
+                    TextTest::NotEquals(s) => value != *s,
+                    TextTest::Matches(re) => re.is_match(&value),
+                }
+            }
+            Expr::Number(field, op, bound) => {
+                let value = match field {
+                    NumberField::Size => facts.size,
+                    NumberField::Age => facts.age,
+                };
+                value.is_some_and(|value| match op {
+                    Op::Eq => value == *bound,
+                    Op::Ne => value != *bound,
+                    Op::Lt => value < *bound,
+                    Op::Le => value <= *bound,
+                    Op::Gt => value > *bound,
+                    Op::Ge => value >= *bound,
+                })
+            }
+        }
+    }
+}
+
+/// Parses a duration like `30d` into seconds.
+pub fn parse_age(text: &str) -> Option<u64> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let unit = match unit.to_ascii_lowercase().as_str() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "mo" => 30 * 24 * 60 * 60,
+        "y" => 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    number.checked_mul(unit)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Op(&'static str),
+    Word(String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::And => write!(f, "`&&`"),
+            Token::Or => write!(f, "`||`"),
+            Token::Not => write!(f, "`!`"),
+            Token::Open => write!(f, "`(`"),
+            Token::Close => write!(f, "`)`"),
+            Token::Op(op) => write!(f, "`{}`", op),
+            Token::Word(word) => write!(f, "`{}`", word),
+        }
+    }
+}
+
+/// The operators, longest first so `<=` is not read as `<`.
+const OPS: [&str; 7] = ["==", "!=", "<=", ">=", "<", ">", "~"];
+
+fn tokenize(text: &str) -> std::result::Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let (token, len) = if let Some(op) = OPS.iter().find(|op| rest.starts_with(*op)) {
+            (Token::Op(op), op.len())
+        } else if rest.starts_with("&&") {
+            (Token::And, 2)
+        } else if rest.starts_with("||") {
+            (Token::Or, 2)
+        } else if c == '!' {
+            (Token::Not, 1)
+        } else if c == '(' {
+            (Token::Open, 1)
+        } else if c == ')' {
+            (Token::Close, 1)
+        } else if c == '\'' || c == '"' {
+            let end = rest[1..]
+                .find(c)
+                .ok_or_else(|| format!("unterminated {}", c))?;
+            (Token::Word(rest[1..=end].to_string()), end + 2)
+        } else {
+            let len = rest
+                .find(|c: char| c.is_whitespace() || "()!&|=<>~'\"".contains(c))
+                .unwrap_or(rest.len());
+            if len == 0 {
+                return Err(format!("unexpected `{}`", c));
+            }
+            (Token::Word(rest[..len].to_string()), len)
+        };
+        tokens.push(token);
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    at: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.at).cloned();
+        self.at += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.at) == Some(token) {
+            self.at += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> std::result::Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> std::result::Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> std::result::Result<Expr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                if !self.eat(&Token::Close) {
+                    return Err("missing `)`".to_string());
+                }
+                Ok(expr)
+            }
+            Some(Token::Word(field)) => self.condition(&field),
+            Some(token) => Err(format!("expected a condition, not {}", token)),
+            None => Err("expected a condition".to_string()),
+        }
+    }
+
+    fn condition(&mut self, field: &str) -> std::result::Result<Expr, String> {
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => return Err(format!("expected an operator after `{}`", field)),
+        };
+        let value = match self.next() {
+            Some(Token::Word(value)) => value,
+            _ => return Err(format!("expected a value after `{} {}`", field, op)),
+        };
+
+        let text = match field {
+            "path" => Some(TextField::Path),
+            "name" => Some(TextField::Name),
+            "ext" => Some(TextField::Ext),
+            _ => None,
+        };
+        if let Some(text) = text {
+            let value = if text == TextField::Ext {
+                value.trim_start_matches('.').to_lowercase()
+            } else {
+                value
+            };
+            let test = match op {
+                "==" => TextTest::Equals(value),
+                "!=" => TextTest::NotEquals(value),
+                "~" => TextTest::Matches(Regex::new(&value).map_err(|e| e.to_string())?),
+                _ => return Err(format!("`{}` cannot be compared with `{}`", field, op)),
+            };
+            return Ok(Expr::Text(text, test));
+        }
+
+        let (number, bound) = match field {
+            "size" => (NumberField::Size, output::parse_size(&value)),
+            "age" => (NumberField::Age, parse_age(&value)),
+            _ => {
+                return Err(format!(
+                    "unknown field `{}`; expected path, name, ext, size or age",
+                    field
+                ))
+            }
+        };
+        let bound = bound.ok_or_else(|| format!("invalid {} `{}`", field, value))?;
+        let op = match op {
+            "==" => Op::Eq,
+            "!=" => Op::Ne,
+            "<" => Op::Lt,
+            "<=" => Op::Le,
+            ">" => Op::Gt,
+            ">=" => Op::Ge,
+            _ => return Err(format!("`{}` cannot be matched with `{}`", field, op)),
+        };
+        Ok(Expr::Number(number, op, bound))
+    }
+}
@@ -0,0 +1,197 @@
+use crate::error::{Error, ErrorKind, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The name of the ignore files read from the current directory and the
+/// config directory.
+pub const IGNORE_FILE: &str = ".gitdownignore";
+
+/// An include or exclude glob.
+///
+/// Globs follow rsync: a pattern without a `/` matches the file name at any
+/// depth, one with a `/` is anchored at the repository root, and a trailing
+/// `/` matches everything below a directory.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    include: bool,
+    matcher: GlobMatcher,
+}
+
+impl Rule {
+    pub fn include(pattern: &str) -> Result<Rule> {
+        Self::new(true, pattern)
+    }
+
+    pub fn exclude(pattern: &str) -> Result<Rule> {
+        Self::new(false, pattern)
+    }
+
+    fn new(include: bool, pattern: &str) -> Result<Rule> {
+        let mut glob = pattern.trim().to_string();
+        if glob.ends_with('/') {
+            glob.push_str("**");
+        }
+        glob = match glob.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if !glob.contains('/') => format!("**/{}", glob),
+            None => glob,
+        };
+
+        match GlobBuilder::new(&glob).literal_separator(true).build() {
+            Ok(glob) => Ok(Rule {
+                include,
+                matcher: glob.compile_matcher(),
+            }),
+            Err(e) => Error::err(ErrorKind::MalformedFilter {
+                filter: pattern.to_string(),
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    /// Parses a line of a filter file: `+ PATTERN` (or `include PATTERN`)
+    /// and `- PATTERN` (or `exclude PATTERN`). Blank lines and lines starting
+    /// with `#` yield `None`.
+    pub fn parse_line(line: &str) -> Result<Option<Rule>> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+
+        let (kind, pattern) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let pattern = pattern.trim();
+        match kind {
+            _ if pattern.is_empty() => {}
+            "+" | "include" => return Self::include(pattern).map(Some),
+            "-" | "exclude" => return Self::exclude(pattern).map(Some),
+            _ => {}
+        }
+
+        Error::err(ErrorKind::MalformedFilter {
+            filter: line.to_string(),
+            reason: "expected `+ PATTERN` or `- PATTERN`".to_string(),
+        })
+    }
+
+    /// Reads the rules of a filter file, in order.
+    pub fn from_file(path: &Path) -> Result<Vec<Rule>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => {
+                return Error::err(ErrorKind::ReadFailure {
+                    path: path.display().to_string(),
+                })
+            }
+        };
+
+        let mut rules = Vec::new();
+        for line in text.lines() {
+            rules.extend(Self::parse_line(line)?);
+        }
+        Ok(rules)
+    }
+}
+
+/// Decides which repository paths are listed and downloaded.
+///
+/// A path is dropped if it is outside the subtree, if any, or if an ignore
+/// file matches it. Otherwise the first
+/// include/exclude rule matching it decides; paths no rule matches are kept
+/// unless there is at least one include rule.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    ignore: Gitignore,
+    rules: Vec<Rule>,
+    subtree: Option<String>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            ignore: Gitignore::empty(),
+            rules: Vec::new(),
+            subtree: None,
+        }
+    }
+}
+
+impl Filter {
+    /// The ignore files that apply, in increasing order of precedence: the
+    /// one in the config directory, then the one in the current directory.
+    pub fn ignore_files() -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        if let Some(dir) = dirs::config_dir() {
+            files.push(dir.join("gitdown").join(IGNORE_FILE));
+        }
+        files.push(PathBuf::from(IGNORE_FILE));
+        files
+    }
+
+    /// Builds a filter from gitignore-syntax `files`; missing files are
+    /// skipped.
+    pub fn from_ignore_files(files: &[PathBuf]) -> Result<Filter> {
+        // Patterns are matched against repository paths, which are relative
+        // to the repository root, so the builder root is irrelevant.
+        let mut builder = GitignoreBuilder::new("");
+        for file in files.iter().filter(|f| f.is_file()) {
+            if let Some(e) = builder.add(file) {
+                return Error::err(ErrorKind::MalformedIgnore {
+                    path: file.display().to_string(),
+                    reason: e.to_string(),
+                });
+            }
+        }
+
+        match builder.build() {
+            Ok(ignore) => Ok(Filter {
+                ignore,
+                ..Filter::default()
+            }),
+            Err(e) => Error::err(ErrorKind::MalformedIgnore {
+                path: IGNORE_FILE.to_string(),
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    /// Appends include/exclude rules, which are checked in order.
+    pub fn with_rules<I: IntoIterator<Item = Rule>>(mut self, rules: I) -> Self {
+        self.rules.extend(rules);
+        self
+    }
+
+    /// Restricts the filter to paths below the directory `subtree`.
+    pub fn with_subtree(mut self, subtree: &str) -> Self {
+        let subtree = subtree.trim_matches('/');
+        self.subtree = if subtree.is_empty() {
+            None
+        } else {
+            Some(format!("{}/", subtree))
+        };
+        self
+    }
+
+    /// Whether the blob at `path` should be offered and downloaded.
+    pub fn is_match(&self, path: &str) -> bool {
+        if let Some(subtree) = &self.subtree {
+            if !path.starts_with(subtree.as_str()) {
+                return false;
+            }
+        }
+
+        if self
+            .ignore
+            .matched_path_or_any_parents(Path::new(path), false)
+            .is_ignore()
+        {
+            return false;
+        }
+
+        match self.rules.iter().find(|r| r.matcher.is_match(path)) {
+            Some(rule) => rule.include,
+            None => !self.rules.iter().any(|r| r.include),
+        }
+    }
+}
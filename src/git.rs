@@ -0,0 +1,145 @@
+//! Running git on the output directory.
+
+use crate::error::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::process::Command;
+
+/// Runs `git args...` in `dir`, returning its stdout.
+pub fn run(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git").current_dir(dir).args(args).output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Error::err(ErrorKind::GitFailure {
+            command: format!("git {}", args.join(" ")),
+            output: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
+
+/// Creates a repository in `dir`.
+pub fn init(dir: &Path) -> Result<()> {
+    run(dir, &["init", "--quiet"]).map(|_| ())
+}
+
+/// Whether `dir` has a repository of its own (rather than being inside one).
+pub fn is_repo_root(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
+
+/// Creates a repository in `dir` and commits everything in it with
+/// `message`.
+pub fn init_and_commit(dir: &Path, message: &str) -> Result<()> {
+    init(dir)?;
+    run(dir, &["add", "--all"])?;
+    run(dir, &["commit", "--quiet", "--message", message]).map(|_| ())
+}
+
+/// The path of `dir` relative to the top of its worktree, e.g. `vendor/`
+/// (empty at the top); fails if `dir` is not in a worktree.
+pub fn prefix(dir: &Path) -> Result<String> {
+    Ok(run(dir, &["rev-parse", "--show-prefix"])?
+        .trim()
+        .to_string())
+}
+
+/// The paths under `dir` with uncommitted changes, including untracked
+/// files, relative to `dir`.
+pub fn dirty_paths(dir: &Path) -> Result<Vec<String>> {
+    let prefix = prefix(dir)?;
+    let status = run(
+        dir,
+        &[
+            "status",
+            "--porcelain",
+            "-z",
+            "--untracked-files=all",
+            "--",
+            ".",
+        ],
+    )?;
+    let mut paths = Vec::new();
+    let mut entries = status.split('\0').filter(|e| !e.is_empty());
+    while let Some(entry) = entries.next() {
+        let (code, path) = entry.split_at(entry.len().min(3));
+        // A rename is followed by the path it was renamed from.
+        if code.starts_with('R') || code.starts_with('C') {
+            entries.next();
+        }
+        if let Some(path) = path.strip_prefix(prefix.as_str()) {
+            paths.push(path.to_string());
+        }
+    }
+    Ok(paths)
+}
+
+/// The paths of a list written by git, like `git diff --name-only` or
+/// `git ls-files`: one per line, or separated by NULs with `-z`. Paths git
+/// quoted for their unusual characters are unquoted.
+pub fn path_list(text: &str) -> Vec<String> {
+    let separator = if text.contains('\0') { '\0' } else { '\n' };
+    text.split(separator)
+        .map(|path| path.strip_suffix('\r').unwrap_or(path))
+        .filter(|path| !path.is_empty())
+        .map(|path| {
+            if separator == '\n' && path.len() > 1 && path.starts_with('"') && path.ends_with('"') {
+                unquote(&path[1..path.len() - 1])
+            } else {
+                path.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Undoes the C-style quoting git applies to paths under `core.quotePath`,
+/// where bytes outside ASCII are written as octal escapes.
+fn unquote(quoted: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut chars = quoted.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('a') => bytes.push(0x07),
+            Some('b') => bytes.push(0x08),
+            Some('f') => bytes.push(0x0c),
+            Some('v') => bytes.push(0x0b),
+            Some('r') => bytes.push(b'\r'),
+            Some(d @ '0'..='7') => {
+                let mut byte = d.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            byte = byte * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                bytes.push(byte as u8);
+            }
+            Some(other) => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(other.encode_utf8(&mut buf).as_bytes());
+            }
+            None => bytes.push(b'\\'),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Stages `paths`, relative to `dir`.
+pub fn add(dir: &Path, paths: &[String]) -> Result<()> {
+    // Keep well below the limits on the length of a command line.
+    for chunk in paths.chunks(200) {
+        let mut args = vec!["add", "--"];
+        args.extend(chunk.iter().map(String::as_str));
+        run(dir, &args)?;
+    }
+    Ok(())
+}
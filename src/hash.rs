@@ -0,0 +1,165 @@
+use futures::stream::StreamExt;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc;
+
+/// A digest printed for each downloaded file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    Sha256,
+    Sha1,
+    /// The git blob SHA-1, as listed by the API.
+    Git,
+}
+
+impl FromStr for Checksum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(Checksum::Sha256),
+            "sha1" => Ok(Checksum::Sha1),
+            "git" => Ok(Checksum::Git),
+            _ => Err(format!("unknown checksum {}", s)),
+        }
+    }
+}
+
+impl Checksum {
+    /// The hex digest of `bytes`.
+    pub fn digest(self, bytes: &[u8]) -> String {
+        match self {
+            Checksum::Sha256 => to_hex(&Sha256::digest(bytes)),
+            Checksum::Sha1 => to_hex(&Sha1::digest(bytes)),
+            Checksum::Git => git_blob_sha(bytes),
+        }
+    }
+}
+
+/// Computes the git blob SHA-1 of `bytes`, i.e. the object id git would give
+/// the content (`sha1("blob <len>\0" + bytes)`).
+pub fn git_blob_sha(bytes: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", bytes.len()).as_bytes());
+    hasher.update(bytes);
+    to_hex(&hasher.finalize())
+}
+
+/// Computes the git blob SHA-1 of the file at `path`.
+pub fn file_blob_sha(path: &Path) -> io::Result<String> {
+    Ok(git_blob_sha(&fs::read(path)?))
+}
+
+/// The git blob SHAs of the files at `paths`, in order, with `None` for
+/// those that cannot be read. They are hashed in parallel on the blocking
+/// pool, as many at a time as there are cores.
+pub async fn file_blob_shas(paths: Vec<PathBuf>) -> Vec<Option<String>> {
+    let parallel = std::thread::available_parallelism().map_or(4, |n| n.get());
+    futures::stream::iter(paths)
+        .map(|path| async move {
+            tokio::task::spawn_blocking(move || file_blob_sha(&path).ok())
+                .await
+                .expect("hashing panicked")
+        })
+        .buffered(parallel)
+        .collect()
+        .await
+}
+
+/// The hashes of a file's content: the git blob SHA it is verified and
+/// recorded by, and its `--print-checksum` digest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hashes {
+    pub blob: String,
+    pub checksum: Option<String>,
+}
+
+impl Hashes {
+    pub fn of(bytes: &[u8], checksum: Option<Checksum>) -> Hashes {
+        let blob = git_blob_sha(bytes);
+        let checksum = checksum.map(|checksum| match checksum {
+            Checksum::Git => blob.clone(),
+            checksum => checksum.digest(bytes),
+        });
+        Hashes { blob, checksum }
+    }
+
+    /// [`Hashes::of`] on the blocking pool, so hashing a large file does not
+    /// hold up the downloads sharing its runtime thread. `bytes` is handed
+    /// back.
+    pub async fn off_runtime(bytes: Vec<u8>, checksum: Option<Checksum>) -> (Vec<u8>, Hashes) {
+        tokio::task::spawn_blocking(move || {
+            let hashes = Hashes::of(&bytes, checksum);
+            (bytes, hashes)
+        })
+        .await
+        .expect("hashing panicked")
+    }
+}
+
+/// Hashes a body as its chunks arrive, on the blocking pool, so the hashes
+/// are ready about when the last chunk is instead of after it. The length
+/// has to be known up front, as the blob SHA starts with it.
+pub struct StreamHasher {
+    len: usize,
+    chunks: mpsc::Sender<Vec<u8>>,
+    hashes: tokio::task::JoinHandle<Hashes>,
+}
+
+impl StreamHasher {
+    pub fn start(len: usize, checksum: Option<Checksum>) -> StreamHasher {
+        let (chunks, received) = mpsc::channel::<Vec<u8>>();
+        let hashes = tokio::task::spawn_blocking(move || {
+            let mut blob = Sha1::new();
+            blob.update(format!("blob {}\0", len).as_bytes());
+            let (mut sha256, mut sha1) = match checksum {
+                Some(Checksum::Sha256) => (Some(Sha256::new()), None),
+                Some(Checksum::Sha1) => (None, Some(Sha1::new())),
+                _ => (None, None),
+            };
+            for chunk in received {
+                blob.update(&chunk);
+                if let Some(hasher) = &mut sha256 {
+                    hasher.update(&chunk);
+                }
+                if let Some(hasher) = &mut sha1 {
+                    hasher.update(&chunk);
+                }
+            }
+            let blob = to_hex(&blob.finalize());
+            let checksum = match checksum {
+                Some(Checksum::Git) => Some(blob.clone()),
+                _ => sha256
+                    .map(|h| to_hex(&h.finalize()))
+                    .or_else(|| sha1.map(|h| to_hex(&h.finalize()))),
+            };
+            Hashes { blob, checksum }
+        });
+        StreamHasher {
+            len,
+            chunks,
+            hashes,
+        }
+    }
+
+    pub fn feed(&self, chunk: &[u8]) {
+        // The receiver only goes away with the task, after every chunk.
+        let _ = self.chunks.send(chunk.to_vec());
+    }
+
+    /// The hashes of the `len` bytes fed, or `None` if a different number
+    /// was, so the promised length was wrong.
+    pub async fn finish(self, len: usize) -> Option<Hashes> {
+        drop(self.chunks);
+        let hashes = self.hashes.await.expect("hashing panicked");
+        (len == self.len).then_some(hashes)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,69 @@
+//! The paths last picked from each repository, for `--last`.
+
+use crate::error::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The last selection of every repository, kept in the user data dir.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct History {
+    /// The selected paths, keyed by `user/repo`.
+    #[serde(default)]
+    pub repos: BTreeMap<String, Vec<String>>,
+}
+
+impl History {
+    /// Where the history is kept, if there is a data dir.
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|d| d.join("gitdown").join("history.json"))
+    }
+
+    /// Loads the history at `path`; a missing file is an empty history.
+    pub fn load(path: &Path) -> Result<History> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(History::default()),
+            Err(_) => {
+                return Error::err(ErrorKind::ReadFailure {
+                    path: path.display().to_string(),
+                })
+            }
+        };
+
+        serde_json::from_str(&text).or_else(|e| {
+            Error::err(ErrorKind::MalformedHistory {
+                path: path.display().to_string(),
+                reason: e.to_string(),
+            })
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = match serde_json::to_string_pretty(self) {
+            Ok(text) => text,
+            Err(e) => {
+                return Error::err(ErrorKind::MalformedHistory {
+                    path: path.display().to_string(),
+                    reason: e.to_string(),
+                })
+            }
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text + "\n")?;
+        Ok(())
+    }
+
+    /// The paths last picked from `repo`.
+    pub fn last(&self, repo: &str) -> Option<&[String]> {
+        self.repos.get(repo).map(Vec::as_slice)
+    }
+
+    /// Remembers `paths` as the last selection of `repo`.
+    pub fn record(&mut self, repo: &str, paths: &[String]) {
+        self.repos.insert(repo.to_string(), paths.to_vec());
+    }
+}
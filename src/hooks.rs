@@ -0,0 +1,72 @@
+use crate::error::{Error, ErrorKind, Result};
+use crate::shell;
+use serde::Deserialize;
+use std::io;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+
+/// Shell commands run at fixed points of a download, configured under
+/// `[hooks]`.
+///
+/// Hooks receive metadata through environment variables: every hook gets
+/// `GITDOWN_OWNER`, `GITDOWN_REPO`, `GITDOWN_REF` and `GITDOWN_DEST_DIR`;
+/// `post_download` also gets `GITDOWN_FILE_PATH`, `GITDOWN_FILE_DEST`,
+/// `GITDOWN_FILE_URL`, `GITDOWN_FILE_SHA` and `GITDOWN_FILE_SIZE`; `post_run`
+/// gets `GITDOWN_FILES` (newline-separated destinations) and
+/// `GITDOWN_FILE_COUNT`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    /// Runs once before anything is fetched; a failure aborts the run.
+    pub pre_download: Option<String>,
+    /// Runs after each file is written.
+    pub post_download: Option<String>,
+    /// Runs once after every download finished.
+    pub post_run: Option<String>,
+}
+
+/// Environment variables passed to a hook.
+pub type HookEnv = Vec<(&'static str, String)>;
+
+/// Runs `command` through the shell with `env` added to its environment.
+pub async fn run(command: &str, env: &[(&'static str, String)]) -> Result<()> {
+    let mut child = tokio::process::Command::from(shell::command(command));
+    child.envs(env.iter().map(|(k, v)| (k, v)));
+
+    let status = child.status().await?;
+    if status.success() {
+        Ok(())
+    } else {
+        Error::err(ErrorKind::HookFailure {
+            command: command.to_string(),
+            status: status.to_string(),
+        })
+    }
+}
+
+/// Runs `command` like [`run`], writing `input` to its stdin.
+pub async fn pipe(command: &str, env: &[(&'static str, String)], input: &[u8]) -> Result<()> {
+    let mut child = tokio::process::Command::from(shell::command(command));
+    child
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::piped());
+
+    let mut child = child.spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    // A command may stop reading before the end, like `head` does.
+    match stdin.write_all(input).await {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        res => res?,
+    }
+    drop(stdin);
+
+    let status = child.wait().await?;
+    if status.success() {
+        Ok(())
+    } else {
+        Error::err(ErrorKind::HookFailure {
+            command: command.to_string(),
+            status: status.to_string(),
+        })
+    }
+}
@@ -0,0 +1,111 @@
+use crate::pathname::NameStrategy;
+use crate::rename::{Rename, RenameVars};
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+/// Decides where selected files are written locally.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    /// The directory everything is written under.
+    pub out_dir: PathBuf,
+    /// Drop the directory structure and keep only the file names.
+    pub flatten: bool,
+    /// A rule rewriting each destination relative to `out_dir`.
+    pub rename: Option<Rename>,
+    /// A directory of the repository whose contents are written directly
+    /// into `out_dir`.
+    pub strip_prefix: Option<String>,
+    /// How names that cannot be written as they are get written.
+    pub names: NameStrategy,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            out_dir: PathBuf::from("."),
+            flatten: false,
+            rename: None,
+            strip_prefix: None,
+            names: NameStrategy::default(),
+        }
+    }
+}
+
+impl Layout {
+    /// Maps repository paths to destination paths, in the same order.
+    ///
+    /// Names are made writable as `names` says, and nothing can end up
+    /// outside `out_dir`.
+    ///
+    /// Flattening or renaming can make destinations clash; clashing file
+    /// names get a `-2`, `-3`, ... suffix. The suffixes are assigned in path
+    /// order, so they do not depend on the order the files were selected in.
+    pub fn destinations(&self, paths: &[&str], vars: &RenameVars<'_>) -> Vec<PathBuf> {
+        let rels: Vec<PathBuf> = paths
+            .iter()
+            .map(|&p| {
+                let p = self.strip(p);
+                let rel = if self.flatten {
+                    Path::new(p)
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                } else {
+                    p.to_string()
+                };
+                let rel = match &self.rename {
+                    Some(rename) => rename.apply(&rel, vars),
+                    None => rel,
+                };
+                self.names.local_path(&rel)
+            })
+            .collect();
+
+        let mut order: Vec<usize> = (0..paths.len()).collect();
+        order.sort_by_key(|&i| paths[i]);
+
+        let mut taken = HashSet::new();
+        let mut dests = vec![PathBuf::new(); paths.len()];
+        for i in order {
+            let name = rels[i].file_name().unwrap_or_default();
+            let rel = (1..)
+                .map(|n| rels[i].with_file_name(numbered(name, n)))
+                .find(|candidate| !taken.contains(candidate))
+                .unwrap();
+            dests[i] = self.out_dir.join(&rel);
+            taken.insert(rel);
+        }
+        dests
+    }
+
+    /// Removes `strip_prefix` from the front of `path`, if it is there.
+    fn strip<'p>(&self, path: &'p str) -> &'p str {
+        match &self.strip_prefix {
+            Some(prefix) => {
+                let prefix = prefix.trim_matches('/');
+                path.strip_prefix(prefix)
+                    .and_then(|rest| rest.strip_prefix('/'))
+                    .unwrap_or(path)
+            }
+            None => path,
+        }
+    }
+}
+
+/// Returns `name` for `n == 1`, and `<stem>-<n><ext>` otherwise.
+fn numbered(name: &OsStr, n: usize) -> OsString {
+    if n == 1 {
+        return name.to_os_string();
+    }
+
+    let path = Path::new(name);
+    let mut numbered = path.file_stem().unwrap_or_default().to_os_string();
+    numbered.push(format!("-{}", n));
+    if let Some(ext) = path.extension() {
+        numbered.push(".");
+        numbered.push(ext);
+    }
+    numbered
+}
@@ -1 +1,46 @@
+pub mod api;
+pub mod archive;
+pub mod bundle;
+pub mod client;
+pub mod config;
+pub mod conflict;
+pub mod deadline;
+pub mod download;
+pub mod encoding;
 pub mod error;
+pub mod etag;
+pub mod exec;
+pub mod exit;
+pub mod expr;
+pub mod filter;
+pub mod git;
+pub mod hash;
+pub mod history;
+pub mod hooks;
+pub mod layout;
+pub mod lockfile;
+pub mod logging;
+pub mod longpath;
+pub mod mirror;
+pub mod output;
+pub mod overwrite;
+pub mod pathname;
+pub mod permalink;
+pub mod picker;
+pub mod progress;
+pub mod provider;
+pub mod rename;
+pub mod render;
+pub mod report;
+pub mod script;
+pub mod selection;
+pub mod shell;
+pub mod sink;
+pub mod suggest;
+pub mod summary;
+pub mod template;
+pub mod throttle;
+pub mod treecache;
+pub mod update;
+pub mod usage;
+pub mod wiki;
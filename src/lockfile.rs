@@ -0,0 +1,80 @@
+use crate::error::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The default name of the lockfile.
+pub const LOCKFILE: &str = "gitdown.lock";
+
+/// Records which upstream blob every downloaded file came from, so later runs
+/// can refresh or verify them.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(rename = "file", default)]
+    pub files: Vec<LockedFile>,
+}
+
+/// A downloaded file in the lockfile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedFile {
+    /// The repository, as `user/repo`.
+    pub repo: String,
+    /// The ref the file was downloaded from.
+    #[serde(rename = "ref")]
+    pub tree: String,
+    /// The commit the ref pointed at when the file was downloaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// The path in the repository.
+    pub path: String,
+    /// Where the file was written.
+    pub dest: PathBuf,
+    /// The blob SHA of the downloaded content.
+    pub sha: Option<String>,
+}
+
+impl Lockfile {
+    /// Loads the lockfile at `path`; a missing file is an empty lockfile.
+    pub fn load(path: &Path) -> Result<Lockfile> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Lockfile::default()),
+            Err(_) => {
+                return Error::err(ErrorKind::ReadFailure {
+                    path: path.display().to_string(),
+                })
+            }
+        };
+
+        toml::from_str(&text).or_else(|e| {
+            Error::err(ErrorKind::MalformedLockfile {
+                path: path.display().to_string(),
+                reason: e.to_string(),
+            })
+        })
+    }
+
+    /// Writes the lockfile to `path`, sorted by destination.
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        self.files.sort_by(|a, b| a.dest.cmp(&b.dest));
+        let text = match toml::to_string(self) {
+            Ok(text) => text,
+            Err(e) => {
+                return Error::err(ErrorKind::MalformedLockfile {
+                    path: path.display().to_string(),
+                    reason: e.to_string(),
+                })
+            }
+        };
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Adds `file`, replacing any entry with the same destination.
+    pub fn upsert(&mut self, file: LockedFile) {
+        match self.files.iter_mut().find(|f| f.dest == file.dest) {
+            Some(existing) => *existing = file,
+            None => self.files.push(file),
+        }
+    }
+}
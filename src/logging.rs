@@ -0,0 +1,56 @@
+//! Diagnostics on stderr, filtered by `-v`/`-q` or `RUST_LOG`.
+
+use std::str::FromStr;
+use tracing_subscriber::EnvFilter;
+
+/// How log events are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable lines.
+    Text,
+    /// One JSON object per event, for wrappers and CI systems.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format {}", s)),
+        }
+    }
+}
+
+/// Installs the global subscriber.
+///
+/// `verbosity` counts `-v` flags minus `-q` flags: warnings and errors are
+/// shown by default, `-q` leaves only errors and each `-v` adds a level.
+/// JSON output starts one level higher, so per-file events are included.
+/// `RUST_LOG`, when set, takes precedence, e.g. `RUST_LOG=gitdown=trace`.
+/// `ansi` colors text output.
+pub fn init(verbosity: i64, format: LogFormat, ansi: bool) {
+    let verbosity = match format {
+        LogFormat::Text => verbosity,
+        LogFormat::Json => verbosity + 1,
+    };
+    let level = match verbosity {
+        i64::MIN..=-1 => "error",
+        0 => "warn",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_target(false);
+    match format {
+        LogFormat::Text => builder.with_ansi(ansi).without_time().init(),
+        LogFormat::Json => builder.json().with_current_span(true).init(),
+    }
+}
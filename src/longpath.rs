@@ -0,0 +1,104 @@
+//! Destinations longer than Windows' `MAX_PATH`.
+//!
+//! Deeply nested repositories easily produce paths over 260 characters,
+//! which the Windows file APIs reject unless the path is absolute and starts
+//! with the `\\?\` extended-length prefix. Such paths are passed verbatim,
+//! so they must use backslashes and contain no `.` or `..` components.
+
+use std::borrow::Cow;
+use std::path::Path;
+#[cfg(windows)]
+use std::path::PathBuf;
+
+/// The longest path the Windows APIs accept without the prefix.
+pub const MAX_PATH: usize = 260;
+
+/// Directories are limited further, to leave room for an 8.3 file name.
+const MAX_DIR_PATH: usize = MAX_PATH - 12;
+
+const PREFIX: &str = r"\\?\";
+
+/// Whether `path` is too long to be created without the prefix.
+pub fn is_long(path: &str) -> bool {
+    path.chars().count() >= MAX_DIR_PATH
+}
+
+/// Turns an absolute Windows path (`C:\...`, `C:/...` or `\\server\share\...`)
+/// into its extended-length form, resolving `.` and `..` on the way. Paths
+/// that already have the prefix are returned as they are; `None` means
+/// `path` is not absolute.
+pub fn extended(path: &str) -> Option<String> {
+    if path.starts_with(PREFIX) {
+        return Some(path.to_string());
+    }
+
+    let (root, rest) =
+        if let Some(unc) = path.strip_prefix(r"\\").or_else(|| path.strip_prefix("//")) {
+            let mut parts = unc.splitn(3, ['\\', '/']);
+            let server = parts.next().filter(|s| !s.is_empty())?;
+            let share = parts.next().filter(|s| !s.is_empty())?;
+            (
+                format!(r"{}UNC\{}\{}", PREFIX, server, share),
+                parts.next().unwrap_or(""),
+            )
+        } else {
+            let bytes = path.as_bytes();
+            let is_drive = bytes.len() >= 3
+                && bytes[0].is_ascii_alphabetic()
+                && bytes[1] == b':'
+                && (bytes[2] == b'\\' || bytes[2] == b'/');
+            if !is_drive {
+                return None;
+            }
+            (format!("{}{}", PREFIX, &path[..2]), &path[3..])
+        };
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in rest.split(['\\', '/']) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    let mut extended = root;
+    for component in components {
+        extended.push('\\');
+        extended.push_str(component);
+    }
+    Some(extended)
+}
+
+/// The form of `path` to hand to file system calls: on Windows, long paths
+/// are made absolute and given the extended-length prefix. Elsewhere, and
+/// for short paths, `path` itself.
+pub fn for_io(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        let text = path.to_string_lossy();
+        if is_long(&text) || is_long(&absolute(path).to_string_lossy()) {
+            match extended(&absolute(path).to_string_lossy()) {
+                Some(extended) => return Cow::Owned(PathBuf::from(extended)),
+                None => tracing::warn!(
+                    path = %path.display(),
+                    "the path is longer than MAX_PATH and may not be writable; \
+                     enable long paths in Windows or use a shorter --out"
+                ),
+            }
+        }
+    }
+    Cow::Borrowed(path)
+}
+
+#[cfg(windows)]
+fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+}
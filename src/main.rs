@@ -1,112 +1,164 @@
+use futures::future::FutureExt;
 use futures::stream::StreamExt;
+use gitdown::archive::ArchiveFormat;
+use gitdown::bundle::{self, Bundle};
+use gitdown::client::{self, Client, GitHubDirEntry};
+use gitdown::config::{Config, RepoConfig};
+use gitdown::conflict::{self, ConflictPrompt, Resolution};
+use gitdown::deadline;
+use gitdown::encoding;
 use gitdown::error::{Error, ErrorKind, Result};
-use log::error;
-use reqwest::StatusCode;
-use reqwest::{Client as ReqwestClient, RequestBuilder, Response};
-use serde::Deserialize;
+use gitdown::etag::{Validator, Validators};
+use gitdown::exec::ExecHook;
+use gitdown::exit;
+use gitdown::expr::{Expr, Facts};
+use gitdown::filter::{Filter, Rule};
+use gitdown::git;
+use gitdown::hash::{Checksum, Hashes, StreamHasher};
+use gitdown::history::History;
+use gitdown::hooks::{self, HookEnv, Hooks};
+use gitdown::layout::Layout;
+use gitdown::lockfile::{LockedFile, Lockfile, LOCKFILE};
+use gitdown::logging::{self, LogFormat};
+use gitdown::longpath;
+use gitdown::mirror::{Mirror, MANIFEST};
+use gitdown::output::{self, ColorChoice, Status};
+use gitdown::overwrite::{self, Action, OverwritePolicy};
+use gitdown::pathname::{self, NameStrategy};
+use gitdown::permalink::{self, Permalink, RawLink};
+use gitdown::picker::{self, IconSet, SortKey};
+use gitdown::progress::{self, Progress, Throughput};
+use gitdown::provider::Provider;
+use gitdown::rename::{Rename, RenameVars};
+use gitdown::render::Render;
+use gitdown::report::{self, ErrorReport, Failure};
+use gitdown::script::{self, ScriptFile};
+use gitdown::selection::{Selection, Selections};
+use gitdown::sink::Sink;
+use gitdown::suggest;
+use gitdown::summary::Summary;
+use gitdown::template::Template;
+use gitdown::throttle::{self, Throttle};
+use gitdown::treecache::CachedTree;
+use gitdown::update;
+use gitdown::usage;
+use gitdown::wiki;
+use reqwest::header;
+use reqwest::Client as ReqwestClient;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
 use std::io;
 use std::io::prelude::*;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
-/// A GitHub directory entry.
-///
-///
-#[derive(Debug, Deserialize, Clone)]
-pub struct GitHubDirEntry {
-    /// The path in the repository (not to be confused with the url)
-    path: Option<String>,
-    /// The file type -- can be `blob` or `tree`.
-    #[serde(rename = "type")]
-    ty: String,
-    /// The size of the entry.
-    #[allow(dead_code)]
-    size: Option<usize>,
-    /// The raw githubusercontent url
-    #[serde(skip_serializing)]
-    raw_path: Option<String>,
+/// The `[repos."PATTERN"]` section the repositories named on the command
+/// line match.
+static REPO_CONFIG: OnceLock<RepoSection> = OnceLock::new();
+
+/// A `[repos]` section, with its pattern and the config file it is in.
+struct RepoSection {
+    pattern: String,
+    config: PathBuf,
+    repo: RepoConfig,
 }
 
-pub struct Client<'a> {
-    client: ReqwestClient,
-    base_url: &'a str,
+/// A setting of the API client, with where it came from.
+struct Sourced {
+    value: String,
+    source: String,
 }
 
-impl<'a> Client<'a> {
-    pub fn from_url(base_url: &'a str) -> Result<Self> {
-        let b = ReqwestClient::builder().user_agent("gitdown");
+/// The token and endpoints of the API client: `GITDOWN_TOKEN` or else
+/// `GITHUB_TOKEN`, `GITDOWN_API_URL` and `GITDOWN_RAW_URL`, when they are
+/// set. The [`REPO_CONFIG`] of the run takes precedence over all three.
+struct ClientSettings {
+    token: Option<Sourced>,
+    api_url: Option<Sourced>,
+    raw_url: Option<Sourced>,
+}
 
-        Ok(Self {
-            client: b.build()?,
-            base_url,
+impl ClientSettings {
+    fn get() -> Result<ClientSettings> {
+        let var = |name: &str| {
+            let value = std::env::var(name).ok().filter(|v| !v.is_empty())?;
+            Some(Sourced {
+                value,
+                source: name.to_string(),
+            })
+        };
+        let (section, repo, config) = match REPO_CONFIG.get() {
+            Some(section) => (
+                format!("[repos.\"{}\"]", section.pattern),
+                section.repo.clone(),
+                section.config.display().to_string(),
+            ),
+            None => (String::new(), RepoConfig::default(), String::new()),
+        };
+        let from_section = |value: Option<String>| {
+            value.map(|value| Sourced {
+                value,
+                source: section.clone(),
+            })
+        };
+        let token = match &repo.token_env {
+            Some(name) => match var(name) {
+                Some(token) => Some(Sourced {
+                    source: format!("{}, as {} says", name, section),
+                    ..token
+                }),
+                None => {
+                    return Error::err(ErrorKind::MalformedConfig {
+                        path: config,
+                        reason: format!(
+                            "{} takes the token from {}, which is not set",
+                            section, name
+                        ),
+                    })
+                }
+            },
+            None => var("GITDOWN_TOKEN").or_else(|| var("GITHUB_TOKEN")),
+        };
+        Ok(ClientSettings {
+            token,
+            api_url: from_section(repo.api_url).or_else(|| var("GITDOWN_API_URL")),
+            raw_url: from_section(repo.raw_url).or_else(|| var("GITDOWN_RAW_URL")),
         })
     }
+}
 
-    pub async fn send(&self, mut req: RequestBuilder) -> Result<Response> {
-        req = req.header("Content-Type", "application/vnd.github.v3+json");
-
-        let res = req.send().await?;
-        let status = res.status();
-
-        if status == StatusCode::OK {
-            Ok(res)
-        } else {
-            Error::err(ErrorKind::GitHubStatusFailure {
-                status,
-                msg: res.text().await.unwrap(),
-            })
-        }
+/// Creates the API client with the [`ClientSettings`] of the run.
+fn api_client() -> Result<Client> {
+    let settings = ClientSettings::get()?;
+    let mut builder = Client::builder().token(settings.token.map(|t| t.value));
+    if let Some(url) = settings.api_url {
+        builder = builder.api_root(&url.value);
     }
-
-    pub async fn get_dentries(
-        &self,
-        username: &str,
-        repo: &str,
-        tree: Option<&str>,
-    ) -> Result<Vec<GitHubDirEntry>> {
-        let tree = if let Some(t) = tree { t } else { "main" }.to_string();
-        let mut query = format!("{}/{}/git/trees/{}", username, repo, tree);
-
-        // This option recursively walks the tree of the repository,
-        // yielding all blobs (and even trees).
-        query.push_str("?recursive=1");
-
-        let url = format!("{}/{}", self.base_url, query);
-        let req = self.client.get(url.as_str());
-
-        let res = if let Ok(r) = self.send(req).await {
-            r
-        } else {
-            return Error::err(ErrorKind::TreeDoesNotExist {
-                tree,
-                repo: format!("{}/{}", username, repo),
-            });
-        };
-
-        let text = res.text().await?;
-        let body: serde_json::Value = serde_json::from_str(&text).unwrap();
-        if let Some(dentries) = body.get("tree") {
-            let dentries: Vec<GitHubDirEntry> =
-                serde_json::from_value(dentries.to_owned()).unwrap();
-
-            // Earlier, we yielded everything, but really we only want blobs.
-            Ok(dentries.into_iter().filter(|d| d.ty == "blob").collect())
-        } else {
-            Error::err(ErrorKind::ResponseKeyError {
-                key: "tree".to_string(),
-            })
-        }
+    if let Some(url) = settings.raw_url {
+        builder = builder.raw_url(url.value);
     }
+    builder.build()
 }
 
-fn get_from_fzf<I, D>(items: I) -> Result<Option<Vec<String>>>
+/// Text shown in front of picker entries, keyed by the entry.
+type Labels = HashMap<String, String>;
+
+fn get_from_fzf<I, D>(items: I, labels: &Labels) -> Result<Option<Vec<String>>>
 where
     I: IntoIterator<Item = D>,
     D: Display,
 {
-    let mut command = Command::new("fzf");
+    // Any picker that takes fzf's options will do, e.g. `sk`, with extra
+    // options of its own after its name.
+    let picker = std::env::var("GITDOWN_PICKER").unwrap_or_default();
+    let mut picker = picker.split_whitespace();
+    let mut command = Command::new(picker.next().unwrap_or("fzf"));
     command.stdin(Stdio::piped()).stdout(Stdio::piped());
-    command.args(&[
+    command.args([
         "-m",
         "--bind=ctrl-z:ignore",
         "--exit-0",
@@ -116,6 +168,13 @@ where
         "--reverse",
         "--select-1",
     ]);
+    // Labels go in a field of their own, which is neither matched on nor
+    // part of what is picked.
+    let width = labels.values().map(|l| l.chars().count()).max();
+    if width.is_some() {
+        command.args(["--delimiter=\t", "--nth=2.."]);
+    }
+    command.args(picker);
 
     let mut child = command.spawn()?;
     {
@@ -124,7 +183,14 @@ where
         let mut stdin = io::BufWriter::new(child.stdin.as_mut().unwrap());
 
         for item in items.into_iter() {
-            writeln!(&mut stdin, "{}", item)?;
+            match width {
+                Some(width) => {
+                    let item = item.to_string();
+                    let label = labels.get(&item).map_or("", String::as_str);
+                    writeln!(&mut stdin, "{:>width$}\t{}", label, item, width = width)?;
+                }
+                None => writeln!(&mut stdin, "{}", item)?,
+            }
         }
     }
 
@@ -139,7 +205,10 @@ where
             .split("\n")
             .collect::<Vec<&str>>()
             .iter()
-            .map(|&s| s.into())
+            .map(|&s| match width {
+                Some(_) => s.split_once('\t').map_or(s, |(_, item)| item).into(),
+                None => s.into(),
+            })
             .collect();
 
         Ok(Some(vec))
@@ -147,8 +216,9 @@ where
         // On Unix, the `status.code()` will be `None` if the process was
         // terminated by a signal. So the `gitdown` process was either killed
         // by a signal or a file wasn't chosen.
+        // fzf itself exits with 130 when cancelled with Ctrl-C or Esc.
         match status.code() {
-            None => Error::err(ErrorKind::Interrupted),
+            None | Some(130) => Error::err(ErrorKind::Interrupted),
             Some(_) => Error::err(ErrorKind::Other {
                 status: format!(
                     "An error occured; likely, a file was not chosen: {}",
@@ -159,109 +229,5057 @@ where
     }
 }
 
-use clap::arg;
+/// Opens the picker on `items`, or navigates them a directory at a time
+/// with `--navigate`.
+fn pick(args: &Args, items: Vec<String>, labels: &Labels) -> Result<Option<Vec<String>>> {
+    if args.navigate {
+        navigate(&items, labels)
+    } else {
+        get_from_fzf(items, labels)
+    }
+}
+
+/// The entry of `--navigate` that ends it, followed by the number of files
+/// picked.
+const NAVIGATE_DONE: &str = "[done]";
+
+/// The entry of `--navigate` that goes back to the parent directory.
+const NAVIGATE_UP: &str = "../";
+
+/// Picks from `paths` a directory at a time, for `--navigate`: each picker
+/// shows the directories and files of one directory, picking a directory
+/// enters it and picking files adds them to the selection, until
+/// [`NAVIGATE_DONE`] is picked.
+fn navigate(paths: &[String], labels: &Labels) -> Result<Option<Vec<String>>> {
+    let mut picked: Vec<String> = Vec::new();
+    let mut dir = String::new();
+    loop {
+        let mut dirs: Vec<String> = Vec::new();
+        let mut files = Vec::new();
+        for path in paths.iter().filter(|p| p.starts_with(&dir)) {
+            match path[dir.len()..].split_once('/') {
+                Some((name, _)) => {
+                    let name = format!("{}/", name);
+                    if !dirs.contains(&name) {
+                        dirs.push(name);
+                    }
+                }
+                None => files.push(&path[dir.len()..]),
+            }
+        }
+
+        let mut items = Vec::new();
+        if !picked.is_empty() {
+            items.push(format!("{} {} picked", NAVIGATE_DONE, picked.len()));
+        }
+        if !dir.is_empty() {
+            items.push(NAVIGATE_UP.to_string());
+        }
+        items.extend(dirs);
+        let mut level_labels = Labels::new();
+        for file in files {
+            let path = format!("{}{}", dir, file);
+            let label = labels.get(&path).map_or("", String::as_str);
+            if picked.contains(&path) {
+                level_labels.insert(file.to_string(), format!("+ {}", label));
+            } else if !label.is_empty() {
+                level_labels.insert(file.to_string(), label.to_string());
+            }
+            items.push(file.to_string());
+        }
+
+        let chosen = match get_from_fzf(items, &level_labels)? {
+            Some(chosen) => chosen,
+            None => return Ok(None),
+        };
+        let mut done = false;
+        let mut next = None;
+        for item in chosen.iter().filter(|i| !i.is_empty()) {
+            if item.starts_with(NAVIGATE_DONE) {
+                done = true;
+            } else if item == NAVIGATE_UP || item.ends_with('/') {
+                next = next.or(Some(item.as_str()));
+            } else {
+                let path = format!("{}{}", dir, item);
+                if !picked.contains(&path) {
+                    picked.push(path);
+                }
+            }
+        }
+        if done {
+            return Ok(Some(picked));
+        }
+        match next {
+            Some(NAVIGATE_UP) => {
+                let parent = dir.trim_end_matches('/').rfind('/').map_or(0, |i| i + 1);
+                dir.truncate(parent);
+            }
+            Some(sub) => dir.push_str(sub),
+            None => {}
+        }
+    }
+}
+
+use clap::{arg, Arg, ArgGroup};
+use clap_complete::Shell;
+
+/// A repository (and optionally a ref) to download from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Spec {
+    user: String,
+    repo: String,
+    tree: Option<String>,
+    /// The file a permalink pointed at, downloaded without the picker.
+    path: Option<String>,
+    /// The directory a link pointed at, which the listing is limited to.
+    dir: Option<String>,
+    /// The line range a permalink highlighted.
+    lines: Option<(usize, usize)>,
+    /// The commit `tree` pointed at when the run started, which listing and
+    /// downloading are pinned to so a moving branch cannot mix two trees.
+    commit: Option<String>,
+}
+
+impl Spec {
+    /// The revision to list and download: the pinned commit, if resolved.
+    fn rev(&self) -> &str {
+        self.commit
+            .as_deref()
+            .or(self.tree.as_deref())
+            .unwrap_or("main")
+    }
+}
+
+impl Display for Spec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.user, self.repo)?;
+        if let Some(tree) = &self.tree {
+            write!(f, "@{}", tree)?;
+        }
+        Ok(())
+    }
+}
+
+/// How files are applied onto a git worktree, for `--apply-to`.
+#[derive(Debug, Clone, Copy)]
+struct Apply {
+    /// Overwrite files with uncommitted changes.
+    force: bool,
+}
+
+/// The download options collected from the command line.
+#[derive(Clone)]
+struct Args {
+    policy: OverwritePolicy,
+    /// Download files even if the local copy already has the same blob.
+    refetch: bool,
+    layout: Layout,
+    /// Which listed paths to keep.
+    filter: Filter,
+    /// The `--where` expression listed files must also pass.
+    condition: Option<Expr>,
+    /// Only keep paths changed since this commit or date.
+    since: Option<String>,
+    /// Use the tree as it was at this date.
+    at: Option<String>,
+    /// Only download at a tag with a verified signature.
+    verify_tag: bool,
+    /// Commit the downloaded files to a new repository in the output
+    /// directory.
+    init_git: bool,
+    /// Write the files of each repository under `<owner>-<repo>[-<ref>]`.
+    prefix_repo: bool,
+    /// Write into the git worktree of the output directory and stage the
+    /// files.
+    apply: Option<Apply>,
+    /// Cut files down to the lines their permalink highlights.
+    lines_only: bool,
+    /// Print those lines instead of writing them.
+    print_lines: bool,
+    /// Take every file instead of asking the picker.
+    all: bool,
+    /// Ask before `--all` takes more files than this.
+    confirm_all_above: Option<usize>,
+    /// Ask before downloading a selection larger than this many bytes.
+    confirm_above: Option<u64>,
+    /// Show how long ago each file changed in the picker.
+    show_age: bool,
+    /// The order files are listed in; API order when `None`.
+    sort: Option<SortKey>,
+    /// Mark file types in the picker with these icons.
+    icons: Option<IconSet>,
+    /// Take the paths picked last time instead of asking the picker.
+    last: bool,
+    /// Pick a directory level at a time.
+    navigate: bool,
+    /// Open the picker from the cached listing while the repository is
+    /// listed again.
+    stale_while_revalidate: bool,
+    /// The paths to download instead of asking the picker, for `load`.
+    selection: Option<Vec<String>>,
+    /// The refs to download the selection at, each into a directory of its
+    /// own, when `--ref` is given more than once.
+    refs: Vec<String>,
+    /// Placeholders to substitute, for `template`.
+    template: Option<Template>,
+    /// The formats to pretty-print or convert as they are written.
+    render: Option<Render>,
+    /// Print how each repository is resolved.
+    explain: bool,
+    dry_run: bool,
+    print_urls: bool,
+    emit_script: Option<PathBuf>,
+    /// Print this digest of each written file.
+    checksum: Option<Checksum>,
+    /// Send each file here instead of writing it: the `--pipe` command or
+    /// the `--dest` target.
+    sink: Option<Sink>,
+    /// How long a single file may take before it is requeued.
+    file_timeout: Option<Duration>,
+    /// Check every selected file with a cheap request before downloading.
+    preflight: bool,
+    /// List and fetch repositories with this provider instead of GitHub.
+    provider: Option<Provider>,
+    /// How many files are downloaded at once.
+    jobs: usize,
+    exec: Option<ExecHook>,
+    /// Where to record downloaded files, if anywhere.
+    lockfile: Option<PathBuf>,
+    /// Where to report the files that failed.
+    error_log: Option<PathBuf>,
+    config: Config,
+    /// What was downloaded so far in this run, shared by the clones of the
+    /// arguments, so nothing is fetched twice.
+    fetched: Arc<Mutex<HashSet<String>>>,
+    /// What `--emit-script` has written so far in this run, so that the
+    /// script covers every repository and ref rather than only the last.
+    emitted: Arc<Mutex<EmittedScript>>,
+}
+
+/// The commits and files of the script written with `--emit-script`.
+#[derive(Debug, Default)]
+struct EmittedScript {
+    /// `user/repo@commit` of each selection in the script.
+    origins: Vec<String>,
+    /// The pinned url, destination and blob SHA of each file.
+    files: Vec<(String, PathBuf, Option<String>)>,
+}
+
+/// Adds the arguments shared by every subcommand that downloads from a repo.
+fn download_args(command: clap::Command<'static>) -> clap::Command<'static> {
+    command
+        .arg(arg!(-r --ref <REF> "The branch, tag or commit to download from (main by default)").required(false))
+        .arg(arg!(--force "Overwrite existing local files without asking"))
+        .arg(
+            Arg::new("skip-existing")
+                .long("skip-existing")
+                .help("Skip files that already exist locally; with `same`, only those whose blob SHA matches")
+                .takes_value(true)
+                .value_name("MODE")
+                .possible_values(["any", "same"])
+                .min_values(0)
+                .max_values(1)
+                .require_equals(true)
+                .default_missing_value("any"),
+        )
+        .arg(arg!(--backup "Move replaced local files to <name>.bak, or <name>.bak.<n> if taken"))
+        .arg(arg!(--refetch "Download files even if the local copy is already up to date"))
+        .group(ArgGroup::new("overwrite").args(&["force", "skip-existing", "backup"]))
+        .arg(arg!(-i --include <GLOB> "Only download paths matching GLOB").required(false).multiple_occurrences(true))
+        .arg(arg!(-e --exclude <GLOB> "Do not download paths matching GLOB").required(false).multiple_occurrences(true))
+        .arg(arg!(--"filter-from" <FILE> "Read include (`+ GLOB`) and exclude (`- GLOB`) rules from FILE").required(false).multiple_occurrences(true))
+        .arg(arg!(--where <EXPR> "Only download files passing an expression on their path, name, ext, size and age, like 'ext==md && size<100kb && path~docs/'").required(false))
+        .arg(arg!(-p --path <DIR> "Only download files below DIR in the repository").required(false))
+        .arg(arg!(--"verify-tag" "Refuse to download unless --ref is a tag whose signature GitHub verified"))
+        .arg(arg!(--since <REV> "Only download files changed since a commit or a date (YYYY-MM-DD)").required(false))
+        .arg(arg!(--at <DATE> "Download the files as they were at a date (YYYY-MM-DD), using the last commit before it").required(false))
+        .arg(arg!(--"no-ignore" "Do not read .gitdownignore files"))
+        .arg(arg!(-o --out <DIR> "The directory to write files to (the current directory by default)").required(false).env("GITDOWN_OUT_DIR"))
+        .arg(arg!(--"prefix-repo" "Write each repository's files under a directory of its own, <owner>-<repo>[-<ref>], in the output directory"))
+        .arg(arg!(--flatten "Write every file directly into the output directory, dropping its parent directories"))
+        .arg(arg!(--"as-project" "Treat the --path directory (or the directory a tree link names) as the project root, writing its contents directly into the output directory"))
+        .arg(arg!(--names <STRATEGY> "How to write file names this system cannot hold, like control characters or, on Windows, `:`: percent-escape, replace with _, or keep them").required(false).possible_values(["escape", "replace", "keep"]).default_value("escape").env("GITDOWN_NAMES"))
+        .arg(arg!(--rename <RULE> "Rewrite destination paths with a template like '{stem}-{ref}{ext}' or a sed-style 's/regex/replacement/g'").required(false))
+        .arg(arg!(--render <FORMATS> "Pretty-print or convert files of known formats as they are written: json, csv (to a Markdown table), ipynb or ipynb:md (to Markdown) and ipynb:py (to a script), separated by commas").required(false).env("GITDOWN_RENDER"))
+        .arg(arg!(--"no-render" "Write files as they are upstream, even if GITDOWN_RENDER is set"))
+        .arg(
+            Arg::new("lock")
+                .long("lock")
+                .help("Record downloaded files in a lockfile (gitdown.lock by default)")
+                .takes_value(true)
+                .value_name("FILE")
+                .min_values(0)
+                .max_values(1)
+                .require_equals(true)
+                .default_missing_value(LOCKFILE),
+        )
+        .arg(arg!(-j --jobs <N> "How many files to download at once").required(false).default_value("4").env("GITDOWN_JOBS"))
+        .arg(arg!(--"file-timeout" <SECONDS> "Give up on a file that has not arrived after SECONDS, retrying it after the rest of the selection and reporting it failed if it times out again").required(false).env("GITDOWN_FILE_TIMEOUT"))
+        .arg(arg!(--"error-log" <FILE> "Write the files that failed, with their urls, statuses and errors, to FILE as JSON").required(false))
+        .arg(arg!(--pipe <COMMAND> "Feed each file to a shell command on stdin instead of writing it; GITDOWN_FILE_PATH names the file").required(false).conflicts_with_all(&["exec", "lock", "print-checksum"]))
+        .arg(arg!(--dest <TARGET> "Upload each file to object storage instead of writing it, as s3://bucket/prefix/<destination path> (with the AWS CLI)").required(false).conflicts_with_all(&["pipe", "exec", "lock", "print-checksum"]))
+        .arg(arg!(-x --exec <COMMAND> "Run a command on each downloaded file ({} is the path), or once on all of them with {+}").required(false))
+        .arg(arg!(--provider <NAME> "List and fetch repositories with the executable gitdown-provider-NAME instead of from GitHub").required(false).conflicts_with_all(&["verify-tag", "since", "at", "preflight", "print-urls", "emit-script"]).env("GITDOWN_PROVIDER"))
+        .arg(arg!(--preflight "Check that every selected file exists, with the listed size, using HEAD requests, and give up before downloading anything if one does not"))
+        .arg(arg!(-n --"dry-run" "Print what would be downloaded without fetching or writing anything"))
+        .arg(arg!(--explain "Print how each repository was resolved, from the config section and provider to the API, ref, commit and raw urls, before downloading"))
+        .arg(arg!(--"print-checksum" <ALGORITHM> "Print the sha256, sha1 or git blob digest of each file as it is written, in sha256sum's format").required(false).possible_values(["sha256", "sha1", "git"]))
+        .arg(arg!(--"print-urls" "Print the raw urls of the selected files, pinned to the resolved commit, and exit"))
+        .arg(arg!(--"emit-script" <FILE> "Write a shell script that reproduces the download with curl or wget, and exit").required(false))
+}
+
+/// Lets `--ref` be given more than once, for subcommands that can download
+/// a selection at several refs.
+fn compared_refs(arg: Arg<'static>) -> Arg<'static> {
+    arg.multiple_occurrences(true).help(
+        "The branch, tag or commit to download from (main by default); given more than once, the selection is downloaded at each, into a directory named after the ref",
+    )
+}
+
+/// Adds the arguments of subcommands that open the picker.
+fn picker_args(command: clap::Command<'static>) -> clap::Command<'static> {
+    command
+        .arg(arg!(-a --all "Download every file that passed the filters instead of opening the picker"))
+        .arg(arg!(-y --yes "Do not ask before downloading a very large selection"))
+        .arg(arg!(--"confirm-above" <SIZE> "Ask before downloading a selection larger than SIZE, like 500M or 2G (1G by default); without a terminal to ask on, refuse unless --yes is given").required(false).env("GITDOWN_CONFIRM_ABOVE"))
+        .arg(arg!(--"show-age" "Show how long ago each file last changed (needs GITHUB_TOKEN)"))
+        .arg(
+            Arg::new("icons")
+                .long("icons")
+                .help("Prefix files with an icon of their type: Nerd Font devicons, or plain ASCII with `ascii`")
+                .takes_value(true)
+                .value_name("SET")
+                .possible_values(["nerd", "ascii"])
+                .min_values(0)
+                .max_values(1)
+                .require_equals(true)
+                .default_missing_value("nerd"),
+        )
+        .arg(arg!(--last "Download the files picked from the repository last time, without opening the picker").conflicts_with("all"))
+        .arg(arg!(--"paths-from" <FILE> "Download the paths listed in FILE (- for stdin), as written by `git diff --name-only` or `git ls-files`, without opening the picker").required(false).conflicts_with_all(&["all", "last"]))
+        .arg(arg!(--navigate "Pick a directory at a time: enter directories (and go back up with ../), pick files from each, and end with [done]").conflicts_with_all(&["all", "last", "paths-from"]))
+        .arg(arg!(--cache <POLICY> "With stale-while-revalidate, open the picker at once from the listing cached last time while the repository is listed again, and download the picked files from the fresh listing").required(false).possible_values(["refresh", "stale-while-revalidate"]).default_value("refresh").env("GITDOWN_CACHE"))
+        .arg(arg!(--sort <KEY> "List files by path, size (largest first), ext or depth (shallowest first) instead of in API order").required(false).possible_values(["path", "size", "ext", "depth"]))
+}
+
+/// The arguments of `find` and `discover`.
+struct FindArgs {
+    query: String,
+    language: Option<String>,
+    /// List the most starred first rather than the most relevant.
+    by_stars: bool,
+    /// The ref to download from; each repository's default branch when
+    /// `None`.
+    tree: Option<String>,
+}
+
+/// The arguments of `open`.
+struct OpenArgs {
+    spec: Spec,
+    /// The file to open; picked from the listing when `None`.
+    path: Option<String>,
+    /// Open with `$PAGER` instead of `$EDITOR`.
+    read_only: bool,
+}
+
+/// The arguments of `save`.
+struct SaveArgs {
+    name: String,
+    /// The repository to pick from; `None` forgets the selection instead.
+    spec: Option<Spec>,
+    subtree: Option<String>,
+    /// The filter rules, as lines of a filter file.
+    rules: Vec<String>,
+    no_ignore: bool,
+    /// Save the rules alone, so every path they keep is downloaded.
+    all: bool,
+    /// Save the paths picked from the repository last time.
+    last: bool,
+}
+
+/// Where `new` takes a snapshot from.
+enum Snapshot {
+    Repo {
+        user: String,
+        repo: String,
+        tree: Option<String>,
+    },
+    /// An archive on disk.
+    File(PathBuf),
+}
+
+/// The arguments of `new`.
+struct NewArgs {
+    snapshot: Snapshot,
+    /// The directory to extract into, or with `pack`, the archive to write.
+    target: PathBuf,
+    /// Write the snapshot as a .tar.zst archive instead of extracting it.
+    pack: bool,
+    force: bool,
+    git_init: bool,
+    format: ArchiveFormat,
+}
+
+/// The arguments of `watch`.
+struct WatchArgs {
+    /// The repos to watch; the lockfile is watched when there are none.
+    specs: Vec<Spec>,
+    interval: std::time::Duration,
+    once: bool,
+}
+
+/// The subcommand to run.
+enum Cmd {
+    Download(Vec<Spec>, Box<Args>),
+    Find(FindArgs, Box<Args>),
+    Batch(Option<PathBuf>, Box<Args>),
+    /// `urls`, with the urls given as arguments and the file to read more
+    /// from.
+    Urls(Vec<String>, Option<PathBuf>, Box<Args>),
+    Watch(WatchArgs, Box<Args>),
+    Sync(Spec, bool, Box<Args>),
+    /// `mirror`, with the path of its manifest.
+    Mirror(PathBuf, Box<Args>),
+    Wiki(Spec, Box<Args>),
+    Completions(Shell),
+    Manpage(Option<PathBuf>),
+    SelfUpdate {
+        check: bool,
+    },
+    New(NewArgs),
+    Open(OpenArgs),
+    Save(SaveArgs),
+    /// `load` without a name, which lists the saved selections.
+    ListSelections,
+    /// `bundle create`, with the bundle to write and the lockfile of the
+    /// files to put in it.
+    BundleCreate {
+        bundle: PathBuf,
+        lock: PathBuf,
+        force: bool,
+    },
+    /// `bundle extract`, with the bundle and the directory to extract it
+    /// into.
+    BundleExtract {
+        bundle: PathBuf,
+        out_dir: PathBuf,
+        force: bool,
+    },
+}
+
+/// Splits a `user/repo[@ref]` spec.
+fn parse_repo_spec(text: &str) -> Result<Spec> {
+    if let Some(link) = permalink::parse(text) {
+        return Ok(Spec {
+            user: link.user,
+            repo: link.repo,
+            tree: link.tree,
+            path: link.path,
+            dir: link.dir,
+            lines: link.lines,
+            commit: None,
+        });
+    }
+
+    let (name, tree) = match text.split_once('@') {
+        Some((name, tree)) => (name, Some(tree.to_string())),
+        None => (text, None),
+    };
+
+    match name.split_once('/') {
+        Some((user, repo)) if !user.is_empty() && !repo.is_empty() && !repo.contains('/') => {
+            Ok(Spec {
+                user: user.to_string(),
+                repo: repo.to_string(),
+                tree,
+                path: None,
+                dir: None,
+                lines: None,
+                commit: None,
+            })
+        }
+        _ => Error::err(ErrorKind::MalformedRepo {
+            repo: text.to_string(),
+        }),
+    }
+}
+
+/// Shown after the options of `gitdown --help`.
+const AFTER_HELP: &str = "EXIT STATUS:
+    0    success
+    1    any other error
+    2    some files failed to download, or --max-time ran out
+    3    the repository, ref or path does not exist
+    4    bad credentials or rate limited
+    130  interrupted
+
+ENVIRONMENT:
+    GITDOWN_TOKEN, GITHUB_TOKEN    the token to call the API with
+    GITDOWN_API_URL                the API root, e.g. https://github.example.com/api/v3
+    GITDOWN_RAW_URL                where raw file contents are served from
+    GITDOWN_PICKER                 the picker to run instead of fzf, with options
+    Options marked [env: ...] take their value from that variable unless given.";
 
-fn parse_argv() -> Result<(String, String)> {
-    let matches = clap::Command::new("gitdown")
+/// The command line definition, shared by parsing and completions.
+fn cli() -> clap::Command<'static> {
+    clap::Command::new("gitdown")
         .author("steven-mathew")
         .version("v0.1.0")
         .about("Download specific files from a repository (taken from clipboard by default)")
+        .after_help(AFTER_HELP)
         .subcommand_required(true)
         .arg_required_else_help(true)
         .allow_external_subcommands(true)
         .allow_invalid_utf8_for_external_subcommands(true)
+        .arg(arg!(--config <FILE> "Read the configuration from FILE instead of the default location").required(false).global(true).env("GITDOWN_CONFIG"))
+        .arg(arg!(-v --verbose "Log more about what happens; repeat for more detail").multiple_occurrences(true).global(true))
+        .arg(arg!(-q --quiet "Only print failures and a final summary line, for scripts and makefiles; logs only errors").multiple_occurrences(true).global(true))
+        .arg(arg!(--"log-format" <FORMAT> "How to write log events to stderr").required(false).possible_values(["text", "json"]).default_value("text").global(true).env("GITDOWN_LOG_FORMAT"))
+        .arg(arg!(--color <WHEN> "When to color output; NO_COLOR is respected by auto").required(false).possible_values(["auto", "always", "never"]).default_value("auto").global(true).env("GITDOWN_COLOR"))
+        .arg(arg!(--"max-time" <SECONDS> "Give the whole run SECONDS: downloads still running then are cancelled and reported failed, keeping the files that arrived, and gitdown exits with 2").required(false).global(true).env("GITDOWN_MAX_TIME"))
+        .arg(arg!(--"no-update-check" "Do not check for a newer release, even if the config enables it").global(true))
+        .subcommand(
+            picker_args(download_args(clap::Command::new("repo").about("Repository downloading from")))
+                .mut_arg("force", |a| a.short('f'))
+                .mut_arg("ref", compared_refs)
+                .arg(arg!(--"init-git" "Make the output directory a git repository and commit the downloaded files, noting the upstream commit"))
+                .arg(arg!(--"apply-to" <WORKTREE> "Write the files into an existing git worktree and stage them, refusing to overwrite uncommitted changes unless --force").required(false).conflicts_with("init-git"))
+                .arg(arg!(<REPO>... "The repos to download from, as user/repo[@ref] or a github.com file or directory link"))
+                .arg(arg!(--"lines-only" "Keep only the lines a #L10-L42 link highlights, printing them unless --out is given"))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            picker_args(download_args(
+                clap::Command::new("template")
+                    .about("Scaffold a project from a repository, substituting {{placeholders}} in paths and contents"),
+            ))
+            .mut_arg("force", |a| a.short('f'))
+            .mut_arg("ref", compared_refs)
+            .arg(arg!(--"init-git" "Make the output directory a git repository and commit the scaffolded files, noting the upstream commit"))
+            .arg(arg!(--"apply-to" <WORKTREE> "Write the files into an existing git worktree and stage them, refusing to overwrite uncommitted changes unless --force").required(false).conflicts_with("init-git"))
+            .arg(arg!(<REPO> "The repo to download from"))
+            .arg(arg!(-D --define <DEFINITION> "Define a placeholder as name=value").required(false).multiple_occurrences(true))
+            .arg_required_else_help(true),
+        )
+        .subcommand(
+            picker_args(download_args(
+                clap::Command::new("find")
+                    .about("Search GitHub for repositories, pick some and download from them"),
+            ))
+            .mut_arg("force", |a| a.short('f'))
+            .arg(arg!(<QUERY> "What to search for, in GitHub's search syntax"))
+            .arg(arg!(-l --language <LANGUAGE> "Only find repositories written mostly in LANGUAGE").required(false))
+            .arg_required_else_help(true),
+        )
+        .subcommand(
+            picker_args(download_args(
+                clap::Command::new("discover")
+                    .about("Pick from the most starred repositories created this week, on some topics or in a language, and download from them"),
+            ))
+            .mut_arg("force", |a| a.short('f'))
+            .arg(arg!(-t --topic <TOPIC> "Only list repositories tagged with TOPIC; given more than once, with all of them").required(false).multiple_occurrences(true))
+            .arg(arg!(-l --language <LANGUAGE> "Only list repositories written mostly in LANGUAGE").required(false))
+            .arg(arg!(--days <N> "Only list repositories created in the last N days; 0 for any age").required(false).default_value("7")),
+        )
+        .subcommand(
+            picker_args(download_args(
+                clap::Command::new("batch")
+                    .about("Download from every repository listed in a manifest, one `user/repo[@ref] [GLOB...]` per line, picking from all of them at once")
+                    .long_about("Download from every repository listed in a manifest, one `user/repo[@ref] [GLOB...]` per line.\n\nThe repositories are listed concurrently and their files offered in a single picker, as `user/repo:path`. Without a terminal to pick in, such as when the manifest is read from stdin, every file is downloaded, as with --all."),
+            ))
+            .arg(arg!(-f --file <MANIFEST> "Read the manifest from MANIFEST instead of stdin").required(false)),
+        )
+        .subcommand(
+            download_args(
+                clap::Command::new("urls")
+                    .about("Download raw file urls, of raw.githubusercontent.com, gists or github.com blob links, into paths named after them"),
+            )
+            .arg(arg!([URL] ... "The urls to download; without any, they are read one per line from --file or stdin"))
+            .arg(arg!(-f --file <FILE> "Read the urls from FILE, one per line").required(false)),
+        )
+        .subcommand(
+            download_args(
+                clap::Command::new("watch")
+                    .about("Poll repositories (or the files in a lockfile) and re-download files when they change upstream"),
+            )
+            .arg(arg!([REPO]... "The repos to watch, as user/repo[@ref]; without any, the lockfile is watched"))
+            .arg(arg!(--interval <SECONDS> "How long to wait between checks").required(false).default_value("300"))
+            .arg(arg!(--once "Check once and exit")),
+        )
+        .subcommand(
+            download_args(
+                clap::Command::new("sync")
+                    .about("Make a local directory match a directory of a repository"),
+            )
+            .mut_arg("out", |a| a.required(true))
+            .arg(arg!(<REPO> "The repo to sync from, as user/repo[@ref]"))
+            .arg(arg!(--delete "Delete local files that no longer exist upstream"))
+            .arg_required_else_help(true),
+        )
+        .subcommand(
+            download_args(
+                clap::Command::new("mirror")
+                    .about("Keep a directory in sync with paths of several repositories, listed in a manifest, and record their blobs in its gitdown.lock"),
+            )
+            .arg(arg!(-m --manifest <FILE> "The manifest listing the repositories, refs and path globs to mirror").required(false).default_value(MANIFEST)),
+        )
+        .subcommand(
+            download_args(
+                clap::Command::new("wiki")
+                    .about("Download pages from the wiki of a repository"),
+            )
+            .mut_arg("force", |a| a.short('f'))
+            .arg(arg!(<REPO> "The repo whose wiki to download from"))
+            .arg(arg!(-a --all "Download every page instead of opening the picker"))
+            .arg_required_else_help(true),
+        )
+        .subcommand(
+            clap::Command::new("save")
+                .about("Pick files from a repository and save the selection under a name, for `load`")
+                .arg(arg!(<NAME> "The name to save the selection as"))
+                .arg(arg!([REPO] "The repo to pick from, as user/repo[@ref] or a github.com file or directory link; without a ref, `load` uses the default branch").required_unless_present("forget"))
+                .arg(arg!(-r --ref <REF> "The branch, tag or commit to always load from").required(false))
+                .arg(arg!(-i --include <GLOB> "Only offer paths matching GLOB").required(false).multiple_occurrences(true))
+                .arg(arg!(-e --exclude <GLOB> "Do not offer paths matching GLOB").required(false).multiple_occurrences(true))
+                .arg(arg!(--"filter-from" <FILE> "Read include (`+ GLOB`) and exclude (`- GLOB`) rules from FILE; the rules are saved, not the file name").required(false).multiple_occurrences(true))
+                .arg(arg!(-p --path <DIR> "Only offer files below DIR in the repository").required(false))
+                .arg(arg!(--"no-ignore" "Do not read .gitdownignore files when loading"))
+                .arg(arg!(-a --all "Save the filters alone instead of picking, so `load` takes every file they keep, new ones included"))
+                .arg(arg!(--last "Save the files picked from the repository last time instead of opening the picker").conflicts_with("all"))
+                .arg(arg!(--forget "Delete the selection saved as NAME").conflicts_with_all(&["REPO", "all", "last"]))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            download_args(
+                clap::Command::new("load")
+                    .about("Download a selection saved with `save` from the repository's latest commit, or list the saved selections"),
+            )
+            .mut_arg("force", |a| a.short('f'))
+            .arg(arg!([NAME] "The selection to download; without one, the saved selections are listed")),
+        )
+        .subcommand(
+            clap::Command::new("new")
+                .about("Copy a snapshot of a repository, without its history, into a new directory")
+                .arg(arg!(<REPO> "The repo to copy, as user/repo[@ref], or a .tar.gz, .tar.zst or .zip snapshot on disk"))
+                .arg(arg!(<DIR> "The directory to create, or with --pack, the archive to write"))
+                .arg(arg!(-f --force "Extract even if the directory is not empty, or with --pack, replace the archive"))
+                .arg(arg!(--"git-init" "Run git init in the new directory"))
+                .arg(arg!(--"archive-format" <FORMAT> "Fetch the snapshot as a tar.gz or a zip archive").required(false).possible_values(["tar", "zip"]).default_value("tar"))
+                .arg(arg!(--pack "Write the snapshot to DIR as a .tar.zst archive, for `new` to extract later, instead of extracting it").conflicts_with("git-init"))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            clap::Command::new("bundle")
+                .about("Pack the files a lockfile records into one file, or extract such a bundle, to move downloads to a machine without network access")
+                .subcommand(
+                    clap::Command::new("create")
+                        .about("Write the files a lockfile records, with the lockfile, into a bundle")
+                        .arg(arg!(<BUNDLE> "The bundle to write"))
+                        .arg(arg!(--lock <FILE> "The lockfile of the files to bundle").required(false).default_value(LOCKFILE))
+                        .arg(arg!(-f --force "Replace the bundle if it exists")),
+                )
+                .subcommand(
+                    clap::Command::new("extract")
+                        .about("Write the files of a bundle, checked against their blob SHAs, and record them in the lockfile")
+                        .arg(arg!(<BUNDLE> "The bundle to extract"))
+                        .arg(arg!(-o --out <DIR> "The directory to extract into").required(false).default_value("."))
+                        .arg(arg!(-f --force "Overwrite files that differ from the bundled ones")),
+                )
+                .subcommand_required(true)
+                .arg_required_else_help(true),
+        )
         .subcommand(
-            clap::Command::new("repo")
-                .about("Repository downloading from")
-                .arg(arg!(<REPO> "The repo to download from"))
+            clap::Command::new("open")
+                .about("Download a file into a temporary directory and open it in $EDITOR")
+                .arg(arg!(<REPO> "The repo to open a file of, as user/repo[@ref] or a github.com file link"))
+                .arg(arg!([PATH] "The file to open; without one, it is picked"))
+                .arg(arg!(-r --ref <REF> "The branch, tag or commit to open the file at (main by default)").required(false))
+                .arg(arg!(--"read-only" "Open the file in $PAGER instead"))
                 .arg_required_else_help(true),
         )
-        .get_matches();
+        .subcommand(
+            clap::Command::new("self-update")
+                .about("Replace this binary by the latest release, if it is newer")
+                .arg(arg!(--check "Only report whether a newer release exists")),
+        )
+        .subcommand(
+            clap::Command::new("manpage")
+                .about("Print the man page, or write one per subcommand into a directory")
+                .arg(arg!([DIR] "Write gitdown.1 and gitdown-<subcommand>.1 into DIR")),
+        )
+        .subcommand(
+            clap::Command::new("completions")
+                .about("Print a completion script for a shell")
+                .arg(arg!(<SHELL> "The shell to complete for").possible_values(Shell::possible_values())),
+        )
+}
 
-    match matches.subcommand() {
-        Some(("repo", sub_matches)) => {
-            let text = if let Some(text) = sub_matches.value_of("REPO") {
-                text.to_string()
-            } else {
+/// The options that apply to every subcommand.
+struct Globals {
+    /// Whether to check for a newer release after the run.
+    update_check: bool,
+    /// The number of `-v` minus the number of `-q`.
+    verbosity: i64,
+    log_format: LogFormat,
+    color: ColorChoice,
+    /// How many seconds the whole run may take.
+    max_time: Option<u64>,
+}
+
+/// Parses the command line into the subcommand to run and the global
+/// options.
+fn parse_argv() -> Result<(Cmd, Globals)> {
+    let matches = cli().get_matches();
+
+    let config = Config::load(matches.value_of("config").map(Path::new))?;
+    let globals = Globals {
+        update_check: config.update_check && !matches.is_present("no-update-check"),
+        verbosity: matches.occurrences_of("verbose") as i64
+            - matches.occurrences_of("quiet") as i64,
+        log_format: matches
+            .value_of_t("log-format")
+            .unwrap_or_else(|e| e.exit()),
+        color: matches.value_of_t("color").unwrap_or_else(|e| e.exit()),
+        max_time: match matches.value_of("max-time") {
+            Some(secs) => match secs.parse() {
+                Ok(secs) if secs > 0 => Some(secs),
+                _ => {
+                    return Error::err(ErrorKind::MalformedOption {
+                        option: "--max-time".to_string(),
+                        value: secs.to_string(),
+                        expected: "a number of seconds above 0".to_string(),
+                    })
+                }
+            },
+            None => None,
+        },
+    };
+
+    let cmd = match matches.subcommand() {
+        Some((name @ ("repo" | "template"), sub_matches)) => {
+            let texts: Vec<&str> = sub_matches
+                .values_of("REPO")
+                .into_iter()
+                .flatten()
+                .collect();
+            if texts.is_empty() {
                 // TODO: Add clipboard support
                 // let mut ctx: ClipboardContext = ClipboardProvider::new()?;
                 // ctx.get_contents()?
                 return Error::err(ErrorKind::EmptyText);
-            };
+            }
 
-            if text.matches("/").count() != 1 {
-                return Error::err(ErrorKind::MalformedRepo { repo: text });
+            let mut specs = texts
+                .into_iter()
+                .map(parse_repo_spec)
+                .collect::<Result<Vec<_>>>()?;
+            if let Some(tree) = sub_matches.value_of("ref") {
+                // A ref given in the spec itself takes precedence.
+                for spec in specs.iter_mut().filter(|s| s.tree.is_none()) {
+                    spec.tree = Some(tree.to_string());
+                }
+            }
+            let refs: Vec<String> = sub_matches
+                .values_of("ref")
+                .into_iter()
+                .flatten()
+                .map(String::from)
+                .collect();
+            let conflicting = match specs.as_slice() {
+                _ if refs.len() < 2 => None,
+                [spec] if spec.tree.as_ref() != Some(&refs[0]) => {
+                    Some("a repo given with its own ref")
+                }
+                [_] => None,
+                _ => Some("more than one repo"),
+            };
+            if let Some(with) = conflicting {
+                return Error::err(ErrorKind::ConflictingOptions {
+                    option: "--ref given more than once".to_string(),
+                    with: with.to_string(),
+                });
             }
 
-            let (user, repo) = text.split_once("/").unwrap();
-            Ok((user.to_string(), repo.to_string()))
+            select_repo_config(&config, &specs)?;
+            let mut args = parse_download_args(sub_matches, config)?;
+            if refs.len() > 1 {
+                args.refs = refs;
+            }
+            if sub_matches.is_present("as-project") && args.layout.strip_prefix.is_none() {
+                args.layout.strip_prefix = match specs.as_slice() {
+                    [spec] if spec.dir.is_some() => spec.dir.clone(),
+                    _ => {
+                        return Error::err(ErrorKind::MissingOption {
+                            option: "--as-project".to_string(),
+                            needs: "--path or a link to a directory of a single repo".to_string(),
+                        })
+                    }
+                };
+            }
+            parse_picker_args(sub_matches, &mut args)?;
+            args.init_git = sub_matches.is_present("init-git");
+            if let Some(worktree) = sub_matches.value_of("apply-to") {
+                // GITDOWN_OUT_DIR is only a default, which the worktree
+                // replaces.
+                if sub_matches.occurrences_of("out") > 0 {
+                    return Error::err(ErrorKind::ConflictingOptions {
+                        option: "--apply-to".to_string(),
+                        with: "--out".to_string(),
+                    });
+                }
+                args.layout.out_dir = worktree.into();
+                args.apply = Some(Apply {
+                    force: sub_matches.is_present("force"),
+                });
+                // Git keeps whatever is overwritten, and the uncommitted
+                // changes are checked for separately.
+                if args.policy == OverwritePolicy::Prompt {
+                    args.policy = OverwritePolicy::Overwrite;
+                }
+            }
+            if name == "template" {
+                let vars = sub_matches
+                    .values_of("define")
+                    .into_iter()
+                    .flatten()
+                    .map(Template::parse_define)
+                    .collect::<Result<HashMap<_, _>>>()?;
+                args.template = Some(Template::new(vars));
+            } else {
+                args.lines_only = sub_matches.is_present("lines-only");
+                args.print_lines = args.lines_only && !sub_matches.is_present("out");
+            }
+            Ok(Cmd::Download(specs, Box::new(args)))
         }
-        _ => {
-            unimplemented!()
+        Some(("find", sub_matches)) => {
+            let mut args = parse_download_args(sub_matches, config)?;
+            parse_picker_args(sub_matches, &mut args)?;
+            Ok(Cmd::Find(
+                FindArgs {
+                    query: sub_matches.value_of("QUERY").unwrap().to_string(),
+                    language: sub_matches.value_of("language").map(String::from),
+                    by_stars: false,
+                    tree: sub_matches.value_of("ref").map(String::from),
+                },
+                Box::new(args),
+            ))
         }
-    }
-}
-
-#[tokio::main]
-async fn main() -> Result<()> {
-    let (user, repo) = parse_argv()?;
-    let client = Client::from_url("https://api.github.com/repos");
-
-    let res = client
-        .expect("Could not establish a connection with the GitHub API.")
-        .get_dentries(user.as_str(), repo.as_str(), None)
-        .await?;
-
-    let paths = res
-        .into_iter()
-        .map(|gh| gh.path.unwrap())
-        .collect::<Vec<String>>();
-
-    if let Some(paths) = get_from_fzf(paths).unwrap() {
-        let mut urls: Vec<GitHubDirEntry> = paths
-            .into_iter()
-            .map(|path| {
-                let raw_path = format!(
-                    "https://raw.githubusercontent.com/{}/{}/main/{}",
-                    user, repo, path
-                );
+        Some(("discover", sub_matches)) => {
+            let mut args = parse_download_args(sub_matches, config)?;
+            parse_picker_args(sub_matches, &mut args)?;
+            let days = sub_matches.value_of("days").unwrap();
+            let days: u64 = match days.parse() {
+                Ok(days) => days,
+                Err(_) => {
+                    return Error::err(ErrorKind::MalformedOption {
+                        option: "--days".to_string(),
+                        value: days.to_string(),
+                        expected: "a number of days".to_string(),
+                    })
+                }
+            };
+            let mut qualifiers: Vec<String> = sub_matches
+                .values_of("topic")
+                .into_iter()
+                .flatten()
+                .map(|topic| format!("topic:{}", topic))
+                .collect();
+            if days > 0 {
+                let since = picker::now().saturating_sub(days * 24 * 60 * 60);
+                qualifiers.push(format!("created:>={}", picker::format_date(since)));
+            }
+            let language = sub_matches.value_of("language").map(String::from);
+            if qualifiers.is_empty() && language.is_none() {
+                return Error::err(ErrorKind::MissingOption {
+                    option: "discover".to_string(),
+                    needs: "--topic, --language or --days above 0".to_string(),
+                });
+            }
+            Ok(Cmd::Find(
+                FindArgs {
+                    query: qualifiers.join(" "),
+                    language,
+                    by_stars: true,
+                    tree: sub_matches.value_of("ref").map(String::from),
+                },
+                Box::new(args),
+            ))
+        }
+        Some(("batch", sub_matches)) => {
+            let manifest = sub_matches.value_of("file").map(PathBuf::from);
+            let mut args = parse_download_args(sub_matches, config)?;
+            if args.emit_script.is_some() {
+                return Error::err(ErrorKind::UnsupportedOption {
+                    option: "--emit-script".to_string(),
+                    command: "batch".to_string(),
+                });
+            }
+            if manifest.is_none() && sub_matches.value_of("paths-from") == Some("-") {
+                return Error::err(ErrorKind::MissingOption {
+                    option: "--paths-from -".to_string(),
+                    needs: "the manifest in a file, given with --file".to_string(),
+                });
+            }
+            parse_picker_args(sub_matches, &mut args)?;
+            // A manifest read from stdin leaves nothing to pick with or to
+            // answer conflict prompts with.
+            if !args.last
+                && args.selection.is_none()
+                && (manifest.is_none() || !io::stdin().is_terminal())
+            {
+                args.all = true;
+            }
+            if manifest.is_none() && args.policy == OverwritePolicy::Prompt {
+                args.policy = OverwritePolicy::Overwrite;
+            }
+            Ok(Cmd::Batch(manifest, Box::new(args)))
+        }
+        Some(("urls", sub_matches)) => {
+            let urls: Vec<String> = sub_matches
+                .values_of("URL")
+                .into_iter()
+                .flatten()
+                .map(String::from)
+                .collect();
+            let file = sub_matches.value_of("file").map(PathBuf::from);
+            let mut args = parse_download_args(sub_matches, config)?;
+            // The urls say what to download and from which ref, and gists
+            // cannot be listed or pinned.
+            for flag in [
+                "ref",
+                "at",
+                "since",
+                "verify-tag",
+                "path",
+                "print-urls",
+                "emit-script",
+                "lock",
+                "provider",
+            ] {
+                if sub_matches.is_present(flag) {
+                    return Error::err(ErrorKind::UnsupportedOption {
+                        option: format!("--{}", flag),
+                        command: "urls".to_string(),
+                    });
+                }
+            }
+            args.all = true;
+            let stdin = urls.is_empty() && file.is_none();
+            if stdin && args.policy == OverwritePolicy::Prompt {
+                args.policy = OverwritePolicy::Overwrite;
+            }
+            Ok(Cmd::Urls(urls, file, Box::new(args)))
+        }
+        Some(("watch", sub_matches)) => {
+            let mut specs = sub_matches
+                .values_of("REPO")
+                .into_iter()
+                .flatten()
+                .map(parse_repo_spec)
+                .collect::<Result<Vec<_>>>()?;
+            if let Some(tree) = sub_matches.value_of("ref") {
+                for spec in specs.iter_mut().filter(|s| s.tree.is_none()) {
+                    spec.tree = Some(tree.to_string());
+                }
+            }
 
-                GitHubDirEntry {
-                    path: Some(path),
-                    ty: "blob".to_string(), // At this point, we can assume only blobs are given.
-                    size: None,
-                    raw_path: Some(raw_path),
+            let interval = sub_matches.value_of("interval").unwrap();
+            let interval = match interval.parse() {
+                Ok(secs) => std::time::Duration::from_secs(secs),
+                Err(_) => {
+                    return Error::err(ErrorKind::MalformedOption {
+                        option: "--interval".to_string(),
+                        value: interval.to_string(),
+                        expected: "a number of seconds".to_string(),
+                    })
                 }
-            })
-            .collect();
+            };
 
-        let client = ReqwestClient::builder().build()?;
+            select_repo_config(&config, &specs)?;
+            let mut args = parse_download_args(sub_matches, config)?;
+            // A tree pinned in the past never changes, and without a commit
+            // to compare, neither does one a provider lists.
+            if args.at.is_some() {
+                return Error::err(ErrorKind::UnsupportedOption {
+                    option: "--at".to_string(),
+                    command: "watch".to_string(),
+                });
+            }
+            if args.provider.is_some() {
+                return Error::err(ErrorKind::UnsupportedOption {
+                    option: "--provider".to_string(),
+                    command: "watch".to_string(),
+                });
+            }
+            args.all = true;
+            if specs.is_empty() && args.lockfile.is_none() {
+                args.lockfile = Some(PathBuf::from(LOCKFILE));
+            }
+            Ok(Cmd::Watch(
+                WatchArgs {
+                    specs,
+                    interval,
+                    once: sub_matches.is_present("once"),
+                },
+                Box::new(args),
+            ))
+        }
+        Some(("sync", sub_matches)) => {
+            let mut spec = parse_repo_spec(sub_matches.value_of("REPO").unwrap())?;
+            if spec.tree.is_none() {
+                spec.tree = sub_matches.value_of("ref").map(String::from);
+            }
 
-        let fetches = futures::stream::iter(urls.drain(..).map(|dentry| {
-            use std::fs;
+            select_repo_config(&config, std::slice::from_ref(&spec))?;
+            let mut args = parse_download_args(sub_matches, config)?;
+            // A partial listing would make everything else look deleted.
+            if args.prefix_repo {
+                return Error::err(ErrorKind::UnsupportedOption {
+                    option: "--prefix-repo".to_string(),
+                    command: "sync".to_string(),
+                });
+            }
+            if args.since.is_some() {
+                return Error::err(ErrorKind::UnsupportedOption {
+                    option: "--since".to_string(),
+                    command: "sync".to_string(),
+                });
+            }
+            args.all = true;
+            // A tree link names the directory to mirror, like --path does.
+            args.layout.strip_prefix = sub_matches
+                .value_of("path")
+                .map(String::from)
+                .or_else(|| spec.dir.clone());
+            // Syncing means mirroring, so local changes are not asked about.
+            if args.policy == OverwritePolicy::Prompt {
+                args.policy = OverwritePolicy::Overwrite;
+            }
+            Ok(Cmd::Sync(
+                spec,
+                sub_matches.is_present("delete"),
+                Box::new(args),
+            ))
+        }
+        Some(("mirror", sub_matches)) => {
+            let manifest = PathBuf::from(sub_matches.value_of("manifest").unwrap());
+            let mut args = parse_download_args(sub_matches, config)?;
+            // The manifest says what to take from where, and the mirror owns
+            // its directory and lockfile.
+            for flag in [
+                "ref",
+                "at",
+                "since",
+                "path",
+                "include",
+                "exclude",
+                "filter-from",
+                "out",
+                "prefix-repo",
+                "as-project",
+                "lock",
+                "pipe",
+                "dest",
+                "print-urls",
+                "emit-script",
+            ] {
+                if sub_matches.occurrences_of(flag) > 0 {
+                    return Error::err(ErrorKind::UnsupportedOption {
+                        option: format!("--{}", flag),
+                        command: "mirror".to_string(),
+                    });
+                }
+            }
+            args.all = true;
+            // A mirror is not edited locally, so it is always brought back in
+            // line.
+            args.policy = OverwritePolicy::Overwrite;
+            Ok(Cmd::Mirror(manifest, Box::new(args)))
+        }
+        Some(("wiki", sub_matches)) => {
+            let spec = parse_repo_spec(sub_matches.value_of("REPO").unwrap())?;
+            let mut args = parse_download_args(sub_matches, config)?;
+            // Everything that goes through the API or pins a ref needs a
+            // tree the wiki does not have.
+            for flag in ["ref", "at", "since", "print-urls", "lock"] {
+                if sub_matches.is_present(flag) || (flag == "ref" && spec.tree.is_some()) {
+                    return Error::err(ErrorKind::UnsupportedOption {
+                        option: format!("--{}", flag),
+                        command: "wiki".to_string(),
+                    });
+                }
+            }
+            args.all = sub_matches.is_present("all");
+            Ok(Cmd::Wiki(spec, Box::new(args)))
+        }
+        Some(("save", sub_matches)) => {
+            let mut spec = sub_matches
+                .value_of("REPO")
+                .map(parse_repo_spec)
+                .transpose()?;
+            if let Some(spec) = spec.as_mut().filter(|s| s.tree.is_none()) {
+                spec.tree = sub_matches.value_of("ref").map(String::from);
+            }
+            select_repo_config(&config, spec.as_slice())?;
+            Ok(Cmd::Save(SaveArgs {
+                name: sub_matches.value_of("NAME").unwrap().to_string(),
+                subtree: sub_matches
+                    .value_of("path")
+                    .map(String::from)
+                    .or_else(|| spec.as_ref().and_then(|s| s.dir.clone())),
+                spec,
+                rules: filter_lines(sub_matches)?,
+                no_ignore: sub_matches.is_present("no-ignore"),
+                all: sub_matches.is_present("all"),
+                last: sub_matches.is_present("last"),
+            }))
+        }
+        Some(("load", sub_matches)) => {
+            let name = match sub_matches.value_of("NAME") {
+                Some(name) => name,
+                None => return Ok((Cmd::ListSelections, globals)),
+            };
+            let selections = match Selections::path() {
+                Some(path) => Selections::load(&path)?,
+                None => Selections::default(),
+            };
+            let selection = selections.get(name)?;
+            let mut spec = parse_repo_spec(&selection.repo)?;
+            if let Some(tree) = sub_matches.value_of("ref") {
+                spec.tree = Some(tree.to_string());
+            }
 
-            let raw_path = dentry.raw_path.unwrap();
-            let path = dentry.path.unwrap();
+            select_repo_config(&config, std::slice::from_ref(&spec))?;
+            let mut args = parse_download_args(sub_matches, config)?;
+            // Rules given now come first, so they win over the saved ones.
+            let mut rules = filter_rules(sub_matches)?;
+            rules.extend(selection.filter_rules()?);
+            args.filter = build_filter(
+                selection.no_ignore || sub_matches.is_present("no-ignore"),
+                rules,
+                selection.subtree.as_deref().unwrap_or(""),
+            )?;
+            match &selection.paths {
+                Some(paths) => args.selection = Some(paths.clone()),
+                None => args.all = true,
+            }
+            Ok(Cmd::Download(vec![spec], Box::new(args)))
+        }
+        Some(("self-update", sub_matches)) => Ok(Cmd::SelfUpdate {
+            check: sub_matches.is_present("check"),
+        }),
+        Some(("manpage", sub_matches)) => {
+            Ok(Cmd::Manpage(sub_matches.value_of("DIR").map(PathBuf::from)))
+        }
+        Some(("completions", sub_matches)) => Ok(Cmd::Completions(
+            sub_matches.value_of_t("SHELL").unwrap_or_else(|e| e.exit()),
+        )),
+        Some(("new", sub_matches)) => {
+            let text = sub_matches.value_of("REPO").unwrap();
+            let snapshot = if gitdown::archive::is_local_archive(text) {
+                Snapshot::File(text.into())
+            } else {
+                let Spec {
+                    user, repo, tree, ..
+                } = parse_repo_spec(text)?;
+                Snapshot::Repo { user, repo, tree }
+            };
+            let pack = sub_matches.is_present("pack");
+            // Only tarballs are recompressed.
+            if pack
+                && (matches!(snapshot, Snapshot::File(_))
+                    || sub_matches.value_of("archive-format") != Some("tar"))
+            {
+                return Error::err(ErrorKind::MissingOption {
+                    option: "--pack".to_string(),
+                    needs: "a repository and --archive-format tar".to_string(),
+                });
+            }
+            Ok(Cmd::New(NewArgs {
+                snapshot,
+                pack,
+                target: sub_matches.value_of("DIR").unwrap().into(),
+                force: sub_matches.is_present("force"),
+                git_init: sub_matches.is_present("git-init"),
+                format: sub_matches
+                    .value_of_t("archive-format")
+                    .unwrap_or_else(|e| e.exit()),
+            }))
+        }
+        Some(("bundle", sub_matches)) => match sub_matches.subcommand() {
+            Some(("create", sub_matches)) => Ok(Cmd::BundleCreate {
+                bundle: sub_matches.value_of("BUNDLE").unwrap().into(),
+                lock: sub_matches.value_of("lock").unwrap().into(),
+                force: sub_matches.is_present("force"),
+            }),
+            Some(("extract", sub_matches)) => Ok(Cmd::BundleExtract {
+                bundle: sub_matches.value_of("BUNDLE").unwrap().into(),
+                out_dir: sub_matches.value_of("out").unwrap().into(),
+                force: sub_matches.is_present("force"),
+            }),
+            other => Error::err(ErrorKind::UnknownSubcommand {
+                command: "gitdown bundle".to_string(),
+                name: other.map(|(name, _)| name.to_string()),
+            }),
+        },
+        Some(("open", sub_matches)) => {
+            let mut spec = parse_repo_spec(sub_matches.value_of("REPO").unwrap())?;
+            if spec.tree.is_none() {
+                spec.tree = sub_matches.value_of("ref").map(String::from);
+            }
+            select_repo_config(&config, std::slice::from_ref(&spec))?;
+            Ok(Cmd::Open(OpenArgs {
+                path: sub_matches
+                    .value_of("PATH")
+                    .map(String::from)
+                    .or_else(|| spec.path.clone()),
+                spec,
+                read_only: sub_matches.is_present("read-only"),
+            }))
+        }
+        // Names clap does not know arrive as external subcommands.
+        other => Error::err(ErrorKind::UnknownSubcommand {
+            command: "gitdown".to_string(),
+            name: other.map(|(name, _)| name.to_string()),
+        }),
+    };
+    cmd.map(|cmd| (cmd, globals))
+}
 
-            let send_fut = client.get(&raw_path).send();
+/// Picks the `[repos]` settings of the repositories named on the command
+/// line into [`REPO_CONFIG`]. One run cannot use two sections, as it has a
+/// single client.
+fn select_repo_config(config: &Config, specs: &[Spec]) -> Result<()> {
+    let mut matched: Option<(&Spec, Option<(&str, &RepoConfig)>)> = None;
+    for spec in specs {
+        let section = config.repo(&spec.user, &spec.repo);
+        match matched {
+            Some((first, other)) if other.map(|o| o.0) != section.map(|s| s.0) => {
+                return Error::err(ErrorKind::ConflictingRepoSections {
+                    first: format!("{}/{}", first.user, first.repo),
+                    second: format!("{}/{}", spec.user, spec.repo),
+                })
+            }
+            Some(_) => {}
+            None => matched = Some((spec, section)),
+        }
+    }
+    if let Some((_, Some((pattern, repo)))) = matched {
+        debug!(pattern, "using the settings of a [repos] section");
+        let _ = REPO_CONFIG.set(RepoSection {
+            pattern: pattern.to_string(),
+            config: config.path.clone().unwrap_or_default(),
+            repo: repo.clone(),
+        });
+    }
+    Ok(())
+}
 
-            async move {
-                match send_fut.await {
-                    Ok(res) => match res.text().await {
-                        Ok(text) => {
-                            // println!("Received {} bytes from {}", text.len(), raw_path);
-                            fs::write(path, text).expect("Unable to write file");
-                        }
-                        Err(_) => error!("when reading {}", raw_path),
-                    },
-                    Err(_) => error!("when downloading {}", raw_path),
+/// Sets the options of [`picker_args`].
+fn parse_picker_args(sub_matches: &clap::ArgMatches, args: &mut Args) -> Result<()> {
+    args.all = sub_matches.is_present("all");
+    if args.all && !sub_matches.is_present("yes") {
+        args.confirm_all_above = Some(args.config.confirm_all_above.unwrap_or(CONFIRM_ALL_ABOVE));
+    }
+    if !sub_matches.is_present("yes") {
+        let (size, name) = match sub_matches.value_of("confirm-above") {
+            Some(size) => (Some(size), "--confirm-above"),
+            None => (
+                args.config.confirm_above.as_deref(),
+                "confirm_above in the config",
+            ),
+        };
+        args.confirm_above = match size {
+            Some(size) => match output::parse_size(size) {
+                Some(bytes) => Some(bytes),
+                None => {
+                    return Error::err(ErrorKind::MalformedOption {
+                        option: name.to_string(),
+                        value: size.to_string(),
+                        expected: "a size such as 2G".to_string(),
+                    })
+                }
+            },
+            None => Some(CONFIRM_ABOVE),
+        };
+    }
+    args.show_age = sub_matches.is_present("show-age");
+    if args.show_age && args.provider.is_some() {
+        return Error::err(ErrorKind::ConflictingOptions {
+            option: "--show-age".to_string(),
+            with: "--provider".to_string(),
+        });
+    }
+    args.last = sub_matches.is_present("last");
+    args.navigate = sub_matches.is_present("navigate");
+    args.stale_while_revalidate = sub_matches.value_of("cache") == Some("stale-while-revalidate");
+    if args.stale_while_revalidate && args.provider.is_some() {
+        return Error::err(ErrorKind::ConflictingOptions {
+            option: "--cache stale-while-revalidate".to_string(),
+            with: "--provider".to_string(),
+        });
+    }
+    if let Some(file) = sub_matches.value_of("paths-from") {
+        let text = if file == "-" {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            text
+        } else {
+            match std::fs::read_to_string(file) {
+                Ok(text) => text,
+                Err(_) => {
+                    return Error::err(ErrorKind::ReadFailure {
+                        path: file.to_string(),
+                    })
                 }
             }
-        }))
-        .buffer_unordered(4)
-        .collect::<Vec<()>>();
-        fetches.await;
+        };
+        args.selection = Some(git::path_list(&text));
     }
-
+    args.icons = sub_matches
+        .is_present("icons")
+        .then(|| sub_matches.value_of_t("icons").unwrap_or_else(|e| e.exit()));
+    args.sort = sub_matches
+        .is_present("sort")
+        .then(|| sub_matches.value_of_t("sort").unwrap_or_else(|e| e.exit()));
     Ok(())
 }
+
+/// Collects the `--include`, `--exclude` and `--filter-from` rules in the
+/// order they were given, since the first matching rule wins.
+fn filter_rules(sub_matches: &clap::ArgMatches) -> Result<Vec<Rule>> {
+    let mut rules = Vec::new();
+    for line in filter_lines(sub_matches)? {
+        rules.extend(Rule::parse_line(&line)?);
+    }
+    Ok(rules)
+}
+
+/// The rules of [`filter_rules`] as lines of a filter file, as `save` keeps
+/// them.
+fn filter_lines(sub_matches: &clap::ArgMatches) -> Result<Vec<String>> {
+    let mut lines: Vec<(usize, Vec<String>)> = Vec::new();
+    for name in ["include", "exclude", "filter-from"] {
+        let values = sub_matches.values_of(name).into_iter().flatten();
+        let indices = sub_matches.indices_of(name).into_iter().flatten();
+        for (value, index) in values.zip(indices) {
+            let line = match name {
+                "include" => vec![format!("+ {}", value)],
+                "exclude" => vec![format!("- {}", value)],
+                _ => match std::fs::read_to_string(value) {
+                    Ok(text) => text.lines().map(String::from).collect(),
+                    Err(_) => {
+                        return Error::err(ErrorKind::ReadFailure {
+                            path: value.to_string(),
+                        })
+                    }
+                },
+            };
+            lines.push((index, line));
+        }
+    }
+
+    lines.sort_by_key(|(index, _)| *index);
+    Ok(lines.into_iter().flat_map(|(_, line)| line).collect())
+}
+
+/// The filter of `rules` within `subtree`, after the ignore files unless
+/// `no_ignore`.
+fn build_filter(no_ignore: bool, rules: Vec<Rule>, subtree: &str) -> Result<Filter> {
+    let filter = if no_ignore {
+        Filter::default()
+    } else {
+        Filter::from_ignore_files(&Filter::ignore_files())?
+    };
+    Ok(filter.with_rules(rules).with_subtree(subtree))
+}
+
+fn parse_download_args(sub_matches: &clap::ArgMatches, config: Config) -> Result<Args> {
+    // The settings of the repositories replace the environment, not flags.
+    let from_repo = |flag: &str| {
+        REPO_CONFIG
+            .get()
+            .map(|section| &section.repo)
+            .filter(|_| sub_matches.occurrences_of(flag) == 0)
+    };
+    let provider = from_repo("provider").and_then(|r| r.provider.as_deref());
+    if provider.is_some() {
+        for flag in [
+            "verify-tag",
+            "since",
+            "at",
+            "preflight",
+            "print-urls",
+            "emit-script",
+        ] {
+            if sub_matches.is_present(flag) {
+                return Error::err(ErrorKind::ConflictingOptions {
+                    option: format!("--{}", flag),
+                    with: "the provider the config names for the repository".to_string(),
+                });
+            }
+        }
+    }
+    let policy = if sub_matches.is_present("backup") {
+        OverwritePolicy::Backup
+    } else {
+        match sub_matches.value_of("skip-existing") {
+            Some("same") => OverwritePolicy::SkipSame,
+            Some(_) => OverwritePolicy::Skip,
+            None if sub_matches.is_present("force") => OverwritePolicy::Overwrite,
+            None if io::stdin().is_terminal() && io::stderr().is_terminal() => {
+                OverwritePolicy::Prompt
+            }
+            None => OverwritePolicy::Overwrite,
+        }
+    };
+
+    let args = Args {
+        policy,
+        refetch: sub_matches.is_present("refetch"),
+        layout: Layout {
+            out_dir: from_repo("out")
+                .and_then(RepoConfig::out_dir)
+                .unwrap_or_else(|| sub_matches.value_of("out").unwrap_or(".").into()),
+            flatten: sub_matches.is_present("flatten"),
+            rename: sub_matches
+                .value_of("rename")
+                .map(Rename::parse)
+                .transpose()?,
+            strip_prefix: sub_matches
+                .value_of("path")
+                .filter(|_| sub_matches.is_present("as-project"))
+                .map(String::from),
+            names: sub_matches.value_of_t("names").unwrap_or_else(|e| e.exit()),
+        },
+        filter: build_filter(
+            sub_matches.is_present("no-ignore"),
+            filter_rules(sub_matches)?,
+            sub_matches.value_of("path").unwrap_or(""),
+        )?,
+        condition: sub_matches.value_of("where").map(Expr::parse).transpose()?,
+        since: sub_matches.value_of("since").map(String::from),
+        at: match sub_matches.value_of("at") {
+            Some(date) if !client::is_date(date) => {
+                return Error::err(ErrorKind::MalformedOption {
+                    option: "--at".to_string(),
+                    value: date.to_string(),
+                    expected: "a date such as 2023-06-01".to_string(),
+                })
+            }
+            date => date.map(String::from),
+        },
+        verify_tag: sub_matches.is_present("verify-tag"),
+        init_git: false,
+        prefix_repo: sub_matches.is_present("prefix-repo"),
+        apply: None,
+        lines_only: false,
+        print_lines: false,
+        all: false,
+        confirm_all_above: None,
+        confirm_above: None,
+        show_age: false,
+        sort: None,
+        icons: None,
+        navigate: false,
+        stale_while_revalidate: false,
+        last: false,
+        selection: None,
+        refs: Vec::new(),
+        template: None,
+        // The emitted script fetches the files as they are upstream.
+        render: match sub_matches.value_of("render") {
+            Some(_) if sub_matches.is_present("emit-script") => {
+                if sub_matches.occurrences_of("render") > 0 {
+                    return Error::err(ErrorKind::ConflictingOptions {
+                        option: "--render".to_string(),
+                        with: "--emit-script".to_string(),
+                    });
+                }
+                None
+            }
+            Some(render) if !sub_matches.is_present("no-render") => Some(Render::parse(render)?),
+            _ => None,
+        },
+        explain: sub_matches.is_present("explain"),
+        dry_run: sub_matches.is_present("dry-run"),
+        print_urls: sub_matches.is_present("print-urls"),
+        emit_script: sub_matches.value_of("emit-script").map(PathBuf::from),
+        checksum: sub_matches
+            .value_of("print-checksum")
+            .map(|c| c.parse().unwrap()),
+        sink: match (sub_matches.value_of("pipe"), sub_matches.value_of("dest")) {
+            (Some(command), _) => Some(Sink::Command(command.to_string())),
+            (None, Some(dest)) => Some(Sink::parse_dest(dest)?),
+            (None, None) => None,
+        },
+        jobs: match from_repo("jobs").and_then(|r| r.jobs) {
+            Some(jobs) => jobs,
+            None => match sub_matches.value_of("jobs").unwrap().parse() {
+                Ok(jobs) if jobs > 0 => jobs,
+                _ => {
+                    return Error::err(ErrorKind::MalformedOption {
+                        option: "--jobs".to_string(),
+                        value: sub_matches.value_of("jobs").unwrap().to_string(),
+                        expected: "a number above 0".to_string(),
+                    })
+                }
+            },
+        },
+        preflight: sub_matches.is_present("preflight"),
+        provider: provider
+            .or_else(|| sub_matches.value_of("provider"))
+            .map(Provider::find)
+            .transpose()?,
+        file_timeout: match sub_matches.value_of("file-timeout") {
+            Some(secs) => match secs.parse() {
+                Ok(secs) if secs > 0 => Some(Duration::from_secs(secs)),
+                _ => {
+                    return Error::err(ErrorKind::MalformedOption {
+                        option: "--file-timeout".to_string(),
+                        value: secs.to_string(),
+                        expected: "a number of seconds above 0".to_string(),
+                    })
+                }
+            },
+            None => None,
+        },
+        exec: sub_matches.value_of("exec").map(ExecHook::new),
+        lockfile: sub_matches.value_of("lock").map(PathBuf::from),
+        error_log: sub_matches.value_of("error-log").map(PathBuf::from),
+        config,
+        fetched: Arc::default(),
+        emitted: Arc::default(),
+    };
+    // Ages come from GitHub's commit history.
+    if args.provider.is_some() && args.condition.as_ref().is_some_and(Expr::uses_age) {
+        return Error::err(ErrorKind::ConflictingOptions {
+            option: "--where looking at the age of files".to_string(),
+            with: "--provider".to_string(),
+        });
+    }
+    Ok(args)
+}
+
+/// Installs the latest release over the running binary, for `self-update`.
+async fn self_update(check: bool) -> Result<()> {
+    let client = ReqwestClient::builder().user_agent("gitdown").build()?;
+    let release = update::latest_release(&client).await?;
+    if !release.is_newer() {
+        println!("gitdown v{} is up to date", update::CURRENT_VERSION);
+        return Ok(());
+    }
+    if check {
+        println!(
+            "gitdown {} is available (this is v{})",
+            release.tag_name,
+            update::CURRENT_VERSION
+        );
+        return Ok(());
+    }
+
+    let asset = match release.platform_asset() {
+        Some(asset) => asset,
+        None => {
+            return Error::err(ErrorKind::UpdateFailure {
+                reason: format!(
+                    "{} has no binary for {}-{}",
+                    release.tag_name,
+                    std::env::consts::ARCH,
+                    std::env::consts::OS
+                ),
+            })
+        }
+    };
+    // Never install something that cannot be verified.
+    let sums = match release.checksum_asset(asset) {
+        Some(sums) => sums,
+        None => {
+            return Error::err(ErrorKind::UpdateFailure {
+                reason: format!("{} has no checksum for {}", release.tag_name, asset.name),
+            })
+        }
+    };
+
+    let sums = client
+        .get(&sums.browser_download_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let digest = match update::find_checksum(&sums, &asset.name) {
+        Some(digest) => digest,
+        None => {
+            return Error::err(ErrorKind::UpdateFailure {
+                reason: format!("no checksum listed for {}", asset.name),
+            })
+        }
+    };
+
+    let bytes = client
+        .get(&asset.browser_download_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?
+        .to_vec();
+    update::verify_checksum(&asset.name, &bytes, &digest)?;
+    let binary = update::extract_binary(&asset.name, bytes)?;
+    let exe = update::replace_current_exe(&binary)?;
+
+    println!(
+        "Updated {} from v{} to {}",
+        exe.display(),
+        update::CURRENT_VERSION,
+        release.tag_name
+    );
+    Ok(())
+}
+
+/// Renders the man pages for `manpage`.
+fn manpage(dir: Option<PathBuf>) -> Result<()> {
+    use std::fs;
+
+    let cmd = cli();
+    let dir = match dir {
+        Some(dir) => dir,
+        None => {
+            clap_mangen::Man::new(cmd).render(&mut io::stdout())?;
+            return Ok(());
+        }
+    };
+
+    fs::create_dir_all(&dir)?;
+    for sub in cmd.get_subcommands().filter(|s| s.get_name() != "help") {
+        let name = format!("gitdown-{}", sub.get_name());
+        let mut page = Vec::new();
+        clap_mangen::Man::new(
+            sub.clone()
+                .name(&name)
+                .version(cmd.get_version().unwrap_or_default()),
+        )
+        .render(&mut page)?;
+        fs::write(dir.join(format!("{}.1", name)), page)?;
+    }
+    let mut page = Vec::new();
+    clap_mangen::Man::new(cmd).render(&mut page)?;
+    fs::write(dir.join("gitdown.1"), page)?;
+    Ok(())
+}
+
+/// Extracts a snapshot for `new`.
+async fn new_project(args: NewArgs) -> Result<()> {
+    if !args.force && !args.pack {
+        gitdown::archive::ensure_empty_dir(&args.target)?;
+    }
+
+    let (count, source) = match &args.snapshot {
+        Snapshot::Repo { user, repo, tree } => {
+            let client = api_client()?;
+            let archive = client
+                .get_archive(user, repo, tree.as_deref(), args.format)
+                .await?;
+            if args.pack {
+                gitdown::archive::pack_tarball(&archive, &args.target, args.force)?;
+                println!("Packed {}/{} into {}", user, repo, args.target.display());
+                return Ok(());
+            }
+            std::fs::create_dir_all(&args.target)?;
+            let count = gitdown::archive::extract(args.format, &archive, &args.target)?;
+            (count, format!("{}/{}", user, repo))
+        }
+        Snapshot::File(path) => {
+            std::fs::create_dir_all(&args.target)?;
+            let count = gitdown::archive::extract_file(path, &args.target)?;
+            (count, path.display().to_string())
+        }
+    };
+
+    if args.git_init {
+        git::init(&args.target)?;
+    }
+
+    println!(
+        "Copied {} files from {} into {}",
+        count,
+        source,
+        args.target.display()
+    );
+    Ok(())
+}
+
+/// Downloads files into a temporary directory and opens them with
+/// `$EDITOR`, or `$PAGER` with `--read-only`, for `open`.
+async fn open_files(args: OpenArgs) -> Result<()> {
+    let OpenArgs {
+        spec,
+        path,
+        read_only,
+    } = args;
+    let client = api_client()?;
+    let spec = pin_commit(&client, &spec).await?;
+
+    let paths = match path {
+        // A path that could not be in a tree could make the url or the
+        // file name point elsewhere.
+        Some(path)
+            if path
+                .split('/')
+                .any(|c| c.is_empty() || c == "." || c == "..") =>
+        {
+            return Error::err(ErrorKind::PathDoesNotExist {
+                path,
+                repo: spec.to_string(),
+                suggestions: Vec::new(),
+            });
+        }
+        Some(path) => vec![path],
+        None => {
+            let listing = client
+                .get_dentries(&spec.user, &spec.repo, Some(spec.rev()))
+                .await?;
+            let paths = listing.into_iter().filter_map(|e| e.path).filter(|p| {
+                spec.dir
+                    .as_ref()
+                    .is_none_or(|dir| Path::new(p).starts_with(dir))
+            });
+            match get_from_fzf(paths, &Labels::new())? {
+                Some(paths) => paths,
+                None => return Ok(()),
+            }
+        }
+    };
+
+    // A fresh directory only this user can enter, so nobody can swap the
+    // files before the editor opens them; it is removed when dropped.
+    let dir = tempfile::Builder::new().prefix("gitdown-").tempdir()?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o700))?;
+    }
+    // Keep the file names, so the editor can tell the file types from them.
+    let layout = Layout {
+        out_dir: dir.path().to_path_buf(),
+        ..Layout::default()
+    };
+    let vars = RenameVars {
+        owner: &spec.user,
+        repo: &spec.repo,
+        tree: spec.rev(),
+    };
+    let selected: Vec<&str> = paths.iter().map(String::as_str).collect();
+    let files = layout.destinations(&selected, &vars);
+    let raw_client = ReqwestClient::builder().build()?;
+    for (path, file) in paths.iter().zip(&files) {
+        usage::record_raw();
+        let res = raw_client
+            .get(client.raw_url(&spec.user, &spec.repo, spec.rev(), path))
+            .send()
+            .await?;
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+            // Only worth listing the tree for once the path is known to be
+            // wrong.
+            let suggestions = match client
+                .get_dentries(&spec.user, &spec.repo, Some(spec.rev()))
+                .await
+            {
+                Ok(listing) => {
+                    suggest::closest(path, listing.iter().filter_map(|e| e.path.as_deref()))
+                }
+                Err(_) => Vec::new(),
+            };
+            return Error::err(ErrorKind::PathDoesNotExist {
+                path: path.clone(),
+                repo: spec.to_string(),
+                suggestions,
+            });
+        }
+        let bytes = res.error_for_status()?.bytes().await?;
+        let file = longpath::for_io(file);
+        std::fs::create_dir_all(file.parent().unwrap())?;
+        std::fs::write(&file, bytes)?;
+    }
+
+    let (var, fallback) = if read_only {
+        ("PAGER", "less")
+    } else {
+        ("EDITOR", "vi")
+    };
+    let program = std::env::var(var)
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| fallback.to_string());
+    let mut words = program.split_whitespace();
+    let status = Command::new(words.next().unwrap())
+        .args(words)
+        .args(&files)
+        .status()?;
+    if !status.success() {
+        return Error::err(ErrorKind::EditorFailure {
+            command: program.clone(),
+            status: status.to_string(),
+        });
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let code = match run().await {
+        Ok(summary) => exit::for_summary(&summary),
+        Err(e) => {
+            e.eprintln();
+            exit::for_error(&e)
+        }
+    };
+    std::process::exit(code);
+}
+
+/// Runs the subcommand, returning what happened to the selected files.
+async fn run() -> Result<Summary> {
+    let (cmd, globals) = parse_argv()?;
+    if let Some(limit) = globals.max_time {
+        deadline::start(Duration::from_secs(limit));
+    }
+    logging::init(
+        globals.verbosity,
+        globals.log_format,
+        globals.color.enabled(),
+    );
+    // JSON logs own stderr, so the status lines would only get in the way.
+    output::init(
+        globals.color,
+        globals.log_format == LogFormat::Text,
+        globals.verbosity < 0,
+    );
+    // Only runs that did something useful end with the notice.
+    let update_check = globals.update_check
+        && !matches!(
+            cmd,
+            Cmd::Completions(_) | Cmd::Manpage(_) | Cmd::SelfUpdate { .. }
+        );
+
+    // Polling once installs the handler right away, before anything (such as
+    // fzf) gets to block; fzf still sees the interrupt, restores the
+    // terminal and exits, and the run ends as interrupted.
+    let mut interrupted = Box::pin(tokio::signal::ctrl_c());
+    let _ = (&mut interrupted).now_or_never();
+
+    let work = async {
+        match cmd {
+            Cmd::Download(specs, args) => download(specs, *args).await,
+            Cmd::Find(find_args, args) => find(find_args, *args).await,
+            Cmd::Batch(manifest, args) => batch(manifest, *args).await,
+            Cmd::Urls(urls, file, args) => download_urls(urls, file, *args).await,
+            Cmd::Watch(watch_args, args) => watch(watch_args, *args).await,
+            Cmd::Sync(spec, delete, args) => sync(spec, delete, *args).await,
+            Cmd::Mirror(manifest, args) => mirror(&manifest, *args).await,
+            Cmd::Wiki(spec, args) => download_wiki(spec, *args).await,
+            Cmd::Completions(shell) => {
+                clap_complete::generate(shell, &mut cli(), "gitdown", &mut io::stdout());
+                Ok(Summary::default())
+            }
+            Cmd::Manpage(dir) => manpage(dir).map(|_| Summary::default()),
+            Cmd::SelfUpdate { check } => self_update(check).await.map(|_| Summary::default()),
+            Cmd::New(args) => new_project(args).await.map(|_| Summary::default()),
+            Cmd::Open(args) => open_files(args).await.map(|_| Summary::default()),
+            Cmd::Save(args) => save_selection(args).await.map(|_| Summary::default()),
+            Cmd::ListSelections => list_selections().map(|_| Summary::default()),
+            Cmd::BundleCreate {
+                bundle,
+                lock,
+                force,
+            } => bundle_create(&bundle, &lock, force)
+                .await
+                .map(|_| Summary::default()),
+            Cmd::BundleExtract {
+                bundle,
+                out_dir,
+                force,
+            } => bundle_extract(&bundle, &out_dir, force),
+        }
+    };
+    // Dropping the work on Ctrl-C cancels in-flight downloads, which clean up
+    // after themselves.
+    let res = tokio::select! {
+        res = work => res,
+        _ = interrupted => Error::err(ErrorKind::Interrupted),
+        _ = deadline::expired() => Error::err(ErrorKind::OutOfTime {
+            limit: globals.max_time.unwrap(),
+        }),
+    };
+    report_usage();
+
+    if res.is_ok() && update_check {
+        let client = ReqwestClient::builder().user_agent("gitdown").build()?;
+        if let Some(tag) = update::newer_release_notice(&client).await {
+            eprintln!(
+                "gitdown {} is available (this is v{}); run `gitdown self-update` to install it",
+                tag,
+                update::CURRENT_VERSION
+            );
+        }
+    }
+    res
+}
+
+/// Reports the requests the run issued, as a status line and, for JSON logs,
+/// as an event.
+fn report_usage() {
+    let usage = usage::snapshot();
+    if usage.is_empty() {
+        return;
+    }
+    let quota = |resource: &str| {
+        usage
+            .quotas
+            .iter()
+            .find(|q| q.resource == resource)
+            .map(|q| (q.consumed, q.remaining))
+    };
+    info!(
+        api_requests = usage.api_requests,
+        raw_requests = usage.raw_requests,
+        core_quota_used = quota("core").map(|(used, _)| used),
+        core_quota_remaining = quota("core").map(|(_, left)| left),
+        quotas = ?usage.quotas,
+        "requests issued"
+    );
+    output::status(Status::Requests, &usage);
+}
+
+/// Lists, selects and downloads files, for `repo` and `template`.
+async fn download(specs: Vec<Spec>, args: Args) -> Result<Summary> {
+    let client = api_client()?;
+    let raw_client = ReqwestClient::builder().build()?;
+
+    let out_dir = &args.layout.out_dir;
+    if args.init_git && git::is_repo_root(out_dir) {
+        return Error::err(ErrorKind::GitRepositoryExists {
+            path: out_dir.display().to_string(),
+        });
+    }
+
+    let summary = if let [spec] = specs.as_slice() {
+        download_repo(&client, &raw_client, spec, &args).await?
+    } else {
+        download_repos(&client, &raw_client, &specs, &args).await?
+    };
+    if summary.total() > 0 && !args.print_lines {
+        output::status(Status::Finished, &summary);
+    }
+    if args.init_git && summary.downloaded > 0 && !args.dry_run {
+        commit_snapshot(&client, &specs, &args).await?;
+    }
+    Ok(summary)
+}
+
+/// Commits the output directory to a new repository, for `--init-git`. The
+/// message names the upstream commits, whose messages go in the body.
+async fn commit_snapshot(client: &Client, specs: &[Spec], args: &Args) -> Result<()> {
+    let mut subject = Vec::new();
+    let mut body = Vec::new();
+    for spec in specs {
+        let pinned = pin_at(client, spec, args).await?;
+        let commit = client
+            .get_commit(&spec.user, &spec.repo, pinned.rev())
+            .await?;
+        subject.push(format!("{}/{}", spec.user, spec.repo));
+        let message = commit.commit.map(|c| c.message).unwrap_or_default();
+        body.push(format!(
+            "From {}/{} at {}:\n\n{}",
+            spec.user,
+            spec.repo,
+            commit.sha,
+            message.trim()
+        ));
+    }
+    let message = format!(
+        "Import files from {}\n\n{}\n",
+        subject.join(", "),
+        body.join("\n\n")
+    );
+
+    let out_dir = &args.layout.out_dir;
+    git::init_and_commit(out_dir, &message)?;
+    output::status(
+        Status::Finished,
+        format_args!(
+            "committed the files to a new repository in {}",
+            out_dir.display()
+        ),
+    );
+    Ok(())
+}
+
+/// Searches for repositories and downloads from the ones picked, for `find`.
+async fn find(find_args: FindArgs, args: Args) -> Result<Summary> {
+    let client = api_client()?;
+    let sort = find_args.by_stars.then_some("stars");
+    let found = client
+        .search_repositories_by(&find_args.query, find_args.language.as_deref(), sort)
+        .await?;
+    if found.is_empty() {
+        return Error::err(ErrorKind::NoRepositoriesFound {
+            query: find_args.query.clone(),
+        });
+    }
+
+    // The description is shown, and searched, after the name.
+    let items: Vec<String> = found
+        .iter()
+        .map(|r| {
+            format!(
+                "{}  {}",
+                r.full_name,
+                r.description.as_deref().unwrap_or("")
+            )
+        })
+        .collect();
+    let labels: Labels = found
+        .iter()
+        .zip(&items)
+        .map(|(r, item)| (item.clone(), format!("★ {}", r.stargazers_count)))
+        .collect();
+    let picked = match get_from_fzf(items, &labels)? {
+        Some(picked) => picked,
+        None => return Ok(Summary::default()),
+    };
+
+    let specs = picked
+        .iter()
+        .filter_map(|line| {
+            let name = line.split_whitespace().next()?;
+            found.iter().find(|r| r.full_name == name)
+        })
+        .map(|r| {
+            let mut spec = parse_repo_spec(&r.full_name)?;
+            spec.tree = Some(
+                find_args
+                    .tree
+                    .clone()
+                    .unwrap_or_else(|| r.default_branch.clone()),
+            );
+            Ok(spec)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    download(specs, args).await
+}
+
+/// Picks files from a repository and saves them as a named selection, or
+/// forgets one, for `save`.
+async fn save_selection(save: SaveArgs) -> Result<()> {
+    let path = match Selections::path() {
+        Some(path) => path,
+        None => return Error::err(ErrorKind::NoConfigDir),
+    };
+    let mut selections = Selections::load(&path)?;
+    let spec = match save.spec {
+        Some(spec) => spec,
+        None => {
+            if !selections.remove(&save.name) {
+                return Error::err(ErrorKind::NoSuchSelection { name: save.name });
+            }
+            selections.save(&path)?;
+            output::status(
+                Status::Finished,
+                format_args!("forgot selection {}", save.name),
+            );
+            return Ok(());
+        }
+    };
+
+    // Parsing the rules now reports a typo before it is saved.
+    let selection = Selection {
+        repo: match &spec.tree {
+            Some(tree) => format!("{}/{}@{}", spec.user, spec.repo, tree),
+            None => format!("{}/{}", spec.user, spec.repo),
+        },
+        subtree: save.subtree,
+        rules: save.rules,
+        no_ignore: save.no_ignore,
+        paths: None,
+    };
+    let filter = build_filter(
+        selection.no_ignore,
+        selection.filter_rules()?,
+        selection.subtree.as_deref().unwrap_or(""),
+    )?;
+
+    let paths = if let Some(path) = &spec.path {
+        Some(vec![path.clone()])
+    } else if save.last {
+        Some(last_selection(&spec)?)
+    } else {
+        let client = api_client()?;
+        let listing = client
+            .get_dentries(&spec.user, &spec.repo, spec.tree.as_deref())
+            .await?;
+        let paths: Vec<String> = listing
+            .into_iter()
+            .filter_map(|gh| gh.path)
+            .filter(|path| filter.is_match(path))
+            .collect();
+        if save.all {
+            debug!(kept = paths.len(), "saving the filters alone");
+            None
+        } else {
+            match get_from_fzf(paths, &Labels::new())? {
+                Some(paths) => Some(paths),
+                None => return Ok(()),
+            }
+        }
+    };
+    let described = match &paths {
+        Some(paths) => format!("{} files", paths.len()),
+        None => "its filters".to_string(),
+    };
+    let repo = selection.repo.clone();
+    selections.insert(&save.name, Selection { paths, ..selection });
+    selections.save(&path)?;
+    output::status(
+        Status::Finished,
+        format_args!("saved {} of {} as {}", described, repo, save.name),
+    );
+    Ok(())
+}
+
+/// Prints the saved selections, for `load` without a name.
+fn list_selections() -> Result<()> {
+    let selections = match Selections::path() {
+        Some(path) => Selections::load(&path)?,
+        None => Selections::default(),
+    };
+    for (name, selection) in &selections.selections {
+        let what = match &selection.paths {
+            Some(paths) => format!("{} files", paths.len()),
+            None => "every file its filters keep".to_string(),
+        };
+        println!("{}\t{}\t{}", name, selection.repo, what);
+    }
+    Ok(())
+}
+
+/// Writes the files `lock` records into a bundle, for `bundle create`. A
+/// file still holding the content the lockfile records is read from disk;
+/// any other is fetched again by its blob.
+async fn bundle_create(path: &Path, lock: &Path, force: bool) -> Result<()> {
+    let mut bundle = Bundle {
+        lock: Lockfile::load(lock)?,
+        ..Bundle::default()
+    };
+    if bundle.lock.files.is_empty() {
+        return Error::err(ErrorKind::CannotBundle {
+            lockfile: lock.display().to_string(),
+            reason: "it records no files".to_string(),
+        });
+    }
+
+    let mut client = None;
+    let mut fetched = 0;
+    for file in &mut bundle.lock.files {
+        if bundle::relative_dest(&file.dest).is_none() {
+            return Error::err(ErrorKind::CannotBundle {
+                lockfile: lock.display().to_string(),
+                reason: format!(
+                    "{} is not a relative path, so it cannot be extracted elsewhere",
+                    file.dest.display()
+                ),
+            });
+        }
+        let local = std::fs::read(&file.dest).ok().filter(|bytes| {
+            file.sha
+                .as_deref()
+                .is_none_or(|sha| gitdown::hash::git_blob_sha(bytes) == sha)
+        });
+        let bytes = match local {
+            Some(bytes) => bytes,
+            None => {
+                let client = match &client {
+                    Some(client) => client,
+                    None => client.insert(api_client()?),
+                };
+                let spec = parse_repo_spec(&file.repo)?;
+                let tree = file.commit.as_deref().unwrap_or(&file.tree);
+                debug!(path = %file.path, "fetching a file that changed on disk");
+                fetched += 1;
+                client
+                    .get_content(
+                        &spec.user,
+                        &spec.repo,
+                        tree,
+                        &file.path,
+                        file.sha.as_deref(),
+                    )
+                    .await?
+            }
+        };
+        let sha = gitdown::hash::git_blob_sha(&bytes);
+        if let Some(expected) = file.sha.as_ref().filter(|expected| **expected != sha) {
+            return Error::err(ErrorKind::ChecksumMismatch {
+                name: file.path.clone(),
+                expected: expected.clone(),
+                actual: sha,
+            });
+        }
+        file.sha = Some(sha.clone());
+        bundle.blobs.insert(sha, bytes);
+    }
+
+    bundle.write(path, force)?;
+    let size: u64 = bundle.blobs.values().map(|b| b.len() as u64).sum();
+    output::status(
+        Status::Finished,
+        format_args!(
+            "bundled {} files ({}, {} fetched again) into {}",
+            bundle.lock.files.len(),
+            output::human_size(size),
+            fetched,
+            path.display()
+        ),
+    );
+    Ok(())
+}
+
+/// Writes the files of a bundle under `out_dir` and records them in its
+/// lockfile, for `bundle extract`. A file that already has the bundled
+/// content is left alone, and one with other content only replaced with
+/// `force`.
+fn bundle_extract(path: &Path, out_dir: &Path, force: bool) -> Result<Summary> {
+    let bundle = Bundle::read(path)?;
+    let lock_path = out_dir.join(LOCKFILE);
+    let mut lock = Lockfile::load(&lock_path)?;
+
+    let mut summary = Summary::default();
+    for file in bundle.lock.files {
+        // `Bundle::read` checked both. The lockfile in `out_dir` records the
+        // files relative to it, as the bundled one did.
+        let relative = bundle::relative_dest(&file.dest).unwrap();
+        let dest = out_dir.join(&relative);
+        let sha = file.sha.clone().unwrap();
+        let bytes = &bundle.blobs[&sha];
+        match gitdown::hash::file_blob_sha(&dest) {
+            Ok(local) if local == sha => {
+                summary.skipped += 1;
+                output::status(
+                    Status::Skipped,
+                    format_args!("{} (unchanged)", dest.display()),
+                );
+            }
+            Ok(_) if !force => {
+                summary.skipped += 1;
+                output::status(
+                    Status::Skipped,
+                    format_args!("{} (differs; pass --force to overwrite)", dest.display()),
+                );
+                continue;
+            }
+            _ => {
+                let part = overwrite::part_path(&dest);
+                let written = dest
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| std::fs::write(&part, bytes))
+                    .and_then(|_| std::fs::rename(&part, &dest));
+                if let Err(e) = written {
+                    let _ = std::fs::remove_file(&part);
+                    return Err(e.into());
+                }
+                summary.downloaded += 1;
+                summary.bytes += bytes.len() as u64;
+                summary.wire_bytes += bytes.len() as u64;
+                output::status(Status::Downloaded, dest.display());
+            }
+        }
+        lock.upsert(LockedFile {
+            dest: relative,
+            ..file
+        });
+    }
+
+    std::fs::create_dir_all(out_dir)?;
+    lock.save(&lock_path)?;
+    output::status(Status::Finished, &summary);
+    Ok(summary)
+}
+
+/// Parses a batch manifest: one `user/repo[@ref] [GLOB...]` per line, where
+/// the globs select what to download (everything if there are none). Blank
+/// lines and `#` comments are ignored.
+fn parse_manifest(text: &str) -> Result<Vec<(Spec, Vec<Rule>)>> {
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let malformed = |e: Box<Error>| {
+            Error::new(ErrorKind::MalformedManifest {
+                line: i + 1,
+                reason: e.to_string(),
+            })
+        };
+
+        let mut words = line.split_whitespace();
+        let spec = parse_repo_spec(words.next().unwrap()).map_err(malformed)?;
+        let rules = words
+            .map(Rule::include)
+            .collect::<Result<Vec<_>>>()
+            .map_err(malformed)?;
+        entries.push((spec, rules));
+    }
+    Ok(entries)
+}
+
+/// Downloads from every repository of a manifest, for `batch`.
+async fn batch(manifest: Option<PathBuf>, args: Args) -> Result<Summary> {
+    let text = match &manifest {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => {
+                return Error::err(ErrorKind::ReadFailure {
+                    path: path.display().to_string(),
+                })
+            }
+        },
+        None => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            text
+        }
+    };
+    let entries = parse_manifest(&text)?;
+
+    // Every repository shares the same clients, and so the same connections.
+    let client = api_client()?;
+    let raw_client = ReqwestClient::builder().build()?;
+
+    let repos = entries
+        .iter()
+        .map(|(spec, rules)| {
+            let mut args = args.clone();
+            args.filter = args.filter.with_rules(rules.iter().cloned());
+            (spec.clone(), args)
+        })
+        .collect();
+
+    // A repository that cannot be listed is left out of the picker, and the
+    // others carry on.
+    let mut total = Summary::default();
+    let mut failed_repos = 0;
+    let mut listed = Vec::new();
+    for (spec, result) in list_repos(&client, repos, &args).await {
+        match result {
+            Ok(repo) => listed.push(repo),
+            Err(e) => {
+                output::status(Status::Failed, format_args!("{}: {}", spec, e));
+                failed_repos += 1;
+            }
+        }
+    }
+
+    for (spec, fetched) in pick_from_repos(&client, &raw_client, listed, &args).await? {
+        match fetched {
+            Ok(summary) => {
+                if !output::is_quiet() {
+                    output::status(Status::Finished, format_args!("{}: {}", spec, summary));
+                }
+                total += summary;
+            }
+            Err(e) => {
+                output::status(Status::Failed, format_args!("{}: {}", spec, e));
+                failed_repos += 1;
+            }
+        }
+    }
+
+    output::status(
+        Status::Finished,
+        format_args!(
+            "{} across {} repositories ({} failed)",
+            total,
+            entries.len(),
+            failed_repos
+        ),
+    );
+    // A repository that could not be listed at all failed too.
+    total.failed += failed_repos;
+    Ok(total)
+}
+
+/// Downloads raw file urls, for `urls`. Each is written to its path in the
+/// repository, or to the file name of a gist, laid out like any other
+/// download; the urls of one repository and ref are downloaded together.
+async fn download_urls(
+    mut urls: Vec<String>,
+    file: Option<PathBuf>,
+    args: Args,
+) -> Result<Summary> {
+    if urls.is_empty() {
+        let text = match &file {
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(_) => {
+                    return Error::err(ErrorKind::ReadFailure {
+                        path: path.display().to_string(),
+                    })
+                }
+            },
+            None => {
+                let mut text = String::new();
+                io::stdin().read_to_string(&mut text)?;
+                text
+            }
+        };
+        urls = text.lines().map(String::from).collect();
+    }
+
+    let client = api_client()?;
+    let raw_client = ReqwestClient::builder().build()?;
+
+    let mut repos: Vec<(Spec, Vec<GitHubDirEntry>)> = Vec::new();
+    for (i, url) in urls.iter().enumerate() {
+        let url = url.trim();
+        if url.is_empty() || url.starts_with('#') {
+            continue;
+        }
+        let (link, raw) = match permalink::parse_raw(url, client.raw_root()) {
+            Some(link) => (link, url.to_string()),
+            None => match permalink::parse(url) {
+                Some(Permalink {
+                    user,
+                    repo,
+                    tree,
+                    path: Some(path),
+                    ..
+                }) => {
+                    let raw =
+                        client.raw_url(&user, &repo, tree.as_deref().unwrap_or("main"), &path);
+                    let link = RawLink {
+                        user,
+                        repo,
+                        tree,
+                        path,
+                    };
+                    (link, raw)
+                }
+                _ => {
+                    return Error::err(ErrorKind::MalformedManifest {
+                        line: i + 1,
+                        reason: format!("{} is not a raw file url or a github.com blob link", url),
+                    })
+                }
+            },
+        };
+
+        let RawLink {
+            user,
+            repo,
+            tree,
+            path,
+        } = link;
+        let entry = GitHubDirEntry {
+            path: Some(path),
+            sha: None,
+            size: None,
+            mode: None,
+            raw_path: Some(raw),
+            dest: None,
+        };
+        let same = |spec: &Spec| spec.user == user && spec.repo == repo && spec.tree == tree;
+        match repos.iter_mut().find(|(spec, _)| same(spec)) {
+            Some((_, entries)) => entries.push(entry),
+            None => {
+                let spec = Spec {
+                    user,
+                    repo,
+                    tree,
+                    path: None,
+                    dir: None,
+                    lines: None,
+                    commit: None,
+                };
+                repos.push((spec, vec![entry]));
+            }
+        }
+    }
+
+    let mut summary = Summary::default();
+    for (spec, mut urls) in repos {
+        let raw: Vec<Option<String>> = urls.iter_mut().map(|d| d.raw_path.take()).collect();
+        plan_destinations(&client, &spec, &args, &mut urls);
+        // The url given is the one downloaded, even where it differs from the
+        // one that would be built for its path.
+        for (dentry, raw) in urls.iter_mut().zip(raw) {
+            dentry.raw_path = raw;
+        }
+        summary += fetch_selection(&client, &raw_client, &spec, &args, urls).await?;
+    }
+    if summary.total() > 0 {
+        output::status(Status::Finished, &summary);
+    }
+    Ok(summary)
+}
+
+/// Re-downloads whatever changed upstream, every `interval`, for `watch`.
+async fn watch(watch_args: WatchArgs, args: Args) -> Result<Summary> {
+    let client = api_client()?;
+    let raw_client = ReqwestClient::builder().build()?;
+
+    loop {
+        let polled = if watch_args.specs.is_empty() {
+            poll_lockfile(&client, &raw_client, &args).await
+        } else {
+            poll_repos(&client, &raw_client, &watch_args.specs, &args).await
+        };
+
+        let summary = match polled {
+            Ok(summary) => summary,
+            Err(e) if !watch_args.once => {
+                output::status(Status::Failed, e);
+                Summary::default()
+            }
+            Err(e) => return Err(e),
+        };
+        if summary.total() > 0 {
+            output::status(Status::Finished, &summary);
+        }
+
+        if watch_args.once {
+            return Ok(summary);
+        }
+        tokio::time::sleep(watch_args.interval).await;
+        // Each poll downloads what changed since the last one.
+        args.fetched.lock().unwrap().clear();
+    }
+}
+
+/// Downloads the files of `specs` whose local copy differs from (or is
+/// missing compared to) the tree.
+async fn poll_repos(
+    client: &Client,
+    raw_client: &ReqwestClient,
+    specs: &[Spec],
+    args: &Args,
+) -> Result<Summary> {
+    let mut summary = Summary::default();
+    for spec in specs {
+        let spec = &pin_commit(client, spec).await?;
+        let mut urls = list_repo(client, spec, args).await?;
+        plan_destinations(client, spec, args, &mut urls);
+        let dests = urls.iter().map(|d| d.dest.clone().unwrap()).collect();
+        let mut locals = gitdown::hash::file_blob_shas(dests).await.into_iter();
+        urls.retain(|d| {
+            let remote = d.sha.as_deref();
+            let local = locals.next().flatten();
+            remote.is_none() || local.as_deref() != remote
+        });
+
+        if !urls.is_empty() {
+            summary += fetch_selection(client, raw_client, spec, args, urls).await?;
+        }
+    }
+    Ok(summary)
+}
+
+/// Downloads the files of the lockfile whose blob changed upstream, or whose
+/// local copy went missing.
+async fn poll_lockfile(
+    client: &Client,
+    raw_client: &ReqwestClient,
+    args: &Args,
+) -> Result<Summary> {
+    let lock = Lockfile::load(args.lockfile.as_ref().unwrap())?;
+
+    let mut groups: Vec<(Spec, Vec<LockedFile>)> = Vec::new();
+    for file in lock.files {
+        let mut spec = parse_repo_spec(&file.repo)?;
+        spec.tree = Some(file.tree.clone());
+        match groups.iter_mut().find(|(s, _)| *s == spec) {
+            Some((_, files)) => files.push(file),
+            None => groups.push((spec, vec![file])),
+        }
+    }
+
+    let mut summary = Summary::default();
+    for (spec, files) in groups {
+        let spec = pin_commit(client, &spec).await?;
+        let listing = list_repo(client, &spec, args).await?;
+        let mut listing: HashMap<String, GitHubDirEntry> = listing
+            .into_iter()
+            .map(|gh| (gh.path.clone().unwrap(), gh))
+            .collect();
+
+        let urls: Vec<GitHubDirEntry> = files
+            .into_iter()
+            .filter_map(|file| {
+                let mut dentry = listing.remove(&file.path)?;
+                if dentry.sha == file.sha && file.dest.is_file() {
+                    return None;
+                }
+                dentry.raw_path =
+                    Some(client.raw_url(&spec.user, &spec.repo, spec.rev(), &file.path));
+                dentry.dest = Some(file.dest);
+                Some(dentry)
+            })
+            .collect();
+
+        if !urls.is_empty() {
+            summary += fetch_selection(client, raw_client, &spec, args, urls).await?;
+        }
+    }
+    Ok(summary)
+}
+
+/// Makes the output directory match the (filtered) tree, for `sync`.
+async fn sync(spec: Spec, delete: bool, args: Args) -> Result<Summary> {
+    let client = api_client()?;
+    let raw_client = ReqwestClient::builder().build()?;
+
+    let spec = pin_at(&client, &spec, &args).await?;
+    let listing = list_tree(&client, &spec, &args).await?;
+    let upstream: HashSet<String> = listing.iter().filter_map(|d| d.path.clone()).collect();
+    let mut urls = narrow_listing(&client, &spec, &args, listing).await?;
+    plan_destinations(&client, &spec, &args, &mut urls);
+    let planned: HashSet<PathBuf> = urls.iter().map(|d| d.dest.clone().unwrap()).collect();
+
+    let mut changes = Vec::new();
+    let dests = urls.iter().map(|d| d.dest.clone().unwrap()).collect();
+    let mut locals = gitdown::hash::file_blob_shas(dests).await.into_iter();
+    urls.retain(|d| {
+        let dest = d.dest.as_ref().unwrap();
+        let local = locals.next().flatten();
+        match local {
+            None => changes.push(format!("A {}", dest.display())),
+            Some(sha) if d.sha.as_deref() != Some(sha.as_str()) => {
+                changes.push(format!("M {}", dest.display()))
+            }
+            Some(_) => return false,
+        }
+        true
+    });
+
+    // Only files that would be synced if they were upstream are extra: the
+    // lockfile, files the filters leave out and files upstream but not
+    // selected are kept.
+    let out_dir = &args.layout.out_dir;
+    let subtree = args.layout.strip_prefix.as_deref().unwrap_or("");
+    let subtree = subtree.trim_matches('/');
+    let mut extra = Vec::new();
+    collect_files(out_dir, &mut extra)?;
+    extra.retain(|f| {
+        let rel = match f.strip_prefix(out_dir) {
+            Ok(rel) if !planned.contains(f) => rel,
+            _ => return false,
+        };
+        if f.file_name() == Some(LOCKFILE.as_ref()) || args.lockfile.as_deref() == Some(f) {
+            return false;
+        }
+        let rel = rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let path = if subtree.is_empty() {
+            rel
+        } else {
+            format!("{}/{}", subtree, rel)
+        };
+        args.filter.is_match(&path) && !upstream.contains(&path)
+    });
+    extra.sort();
+
+    let summary = if urls.is_empty() || args.dry_run {
+        Summary::default()
+    } else {
+        fetch_selection(&client, &raw_client, &spec, &args, urls).await?
+    };
+
+    for file in &extra {
+        if delete && !args.dry_run {
+            std::fs::remove_file(file)?;
+            changes.push(format!("D {}", file.display()));
+        } else {
+            changes.push(format!(
+                "? {} (not upstream; pass --delete to remove)",
+                file.display()
+            ));
+        }
+    }
+
+    for change in &changes {
+        println!("{}", change);
+    }
+    if changes.is_empty() {
+        println!("{} is up to date", args.layout.out_dir.display());
+    } else if !args.dry_run {
+        output::status(Status::Finished, &summary);
+    }
+    Ok(summary)
+}
+
+/// Brings the mirror of a manifest in line with its repositories, for
+/// `mirror`: files are added and updated, files the lockfile has from an
+/// earlier run but no repository lists any more are deleted, and the
+/// lockfile is rewritten with the blob of every mirrored file.
+///
+/// A repository that cannot be listed keeps its files and their entries,
+/// so a failing run never empties the mirror.
+async fn mirror(manifest: &Path, args: Args) -> Result<Summary> {
+    let mirror = Mirror::load(manifest)?;
+    let root = mirror.root(manifest);
+    let lock_path = root.join(LOCKFILE);
+    let previous = Lockfile::load(&lock_path)?;
+
+    let mut repos = Vec::new();
+    for (i, repo) in mirror.repos.iter().enumerate() {
+        let spec = parse_repo_spec(&repo.name).map_err(|e| {
+            Error::new(ErrorKind::MalformedMirror {
+                path: manifest.display().to_string(),
+                reason: format!("repo {}: {}", i + 1, e),
+            })
+        })?;
+        let spec = Spec {
+            tree: repo.tree.clone().or(spec.tree),
+            ..spec
+        };
+        // The first rule a path matches decides, so exclusions go first.
+        let mut rules = Vec::new();
+        for glob in &repo.exclude {
+            rules.push(Rule::exclude(glob)?);
+        }
+        for glob in &repo.paths {
+            rules.push(Rule::include(glob)?);
+        }
+        let mut repo_args = args.clone();
+        repo_args.filter = args.filter.clone().with_rules(rules);
+        repo_args.layout.out_dir = match &repo.into {
+            Some(into) => root.join(into),
+            None => root.join(format!("{}-{}", spec.user, spec.repo)),
+        };
+        repos.push((spec, repo_args));
+    }
+
+    let client = api_client()?;
+    let raw_client = ReqwestClient::builder().build()?;
+
+    let mut summary = Summary::default();
+    let mut lock = Lockfile::default();
+    let mut mirrored = HashSet::new();
+    let mut failed_repos = HashSet::new();
+    for (given, listed) in list_repos(&client, repos, &args).await {
+        let Listed {
+            spec,
+            args: repo_args,
+            listing: mut urls,
+            ..
+        } = match listed {
+            Ok(listed) => listed,
+            Err(e) => {
+                output::status(Status::Failed, format_args!("{}: {}", given, e));
+                failed_repos.insert(format!("{}/{}", given.user, given.repo));
+                summary.failed += 1;
+                continue;
+            }
+        };
+        plan_destinations(&client, &spec, &repo_args, &mut urls);
+        let entries: Vec<LockedFile> = urls
+            .iter()
+            .map(|d| LockedFile {
+                repo: format!("{}/{}", spec.user, spec.repo),
+                tree: spec.tree.clone().unwrap_or_else(|| "main".to_string()),
+                commit: spec.commit.clone(),
+                path: d.path.clone().unwrap(),
+                dest: d.dest.clone().unwrap(),
+                sha: d.sha.clone(),
+            })
+            .collect();
+        mirrored.extend(entries.iter().map(|f| f.dest.clone()));
+
+        match fetch_selection(&client, &raw_client, &spec, &repo_args, urls).await {
+            Ok(fetched) => summary += fetched,
+            Err(e) => {
+                output::status(Status::Failed, format_args!("{}: {}", spec, e));
+                summary.failed += 1;
+            }
+        }
+        // Only what is on disk is recorded, so a file that failed keeps
+        // being retried.
+        let dests = entries.iter().map(|f| f.dest.clone()).collect();
+        let locals = gitdown::hash::file_blob_shas(dests).await;
+        lock.files.extend(
+            entries
+                .into_iter()
+                .zip(locals)
+                .filter(|(f, local)| f.sha.is_some() && *local == f.sha)
+                .map(|(f, _)| f),
+        );
+    }
+
+    for file in previous.files {
+        if failed_repos.contains(&file.repo) {
+            lock.upsert(file);
+        } else if !mirrored.contains(&file.dest) {
+            if !args.dry_run {
+                match std::fs::remove_file(longpath::for_io(&file.dest)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+            println!("D {}", file.dest.display());
+        }
+    }
+
+    if !args.dry_run {
+        lock.save(&lock_path)?;
+        output::status(Status::Finished, &summary);
+    }
+    Ok(summary)
+}
+
+/// Lists, selects and downloads pages of the wiki of `spec`.
+async fn download_wiki(spec: Spec, args: Args) -> Result<Summary> {
+    let client = api_client()?;
+    let raw_client = ReqwestClient::builder().build()?;
+
+    let listing: Vec<GitHubDirEntry> = wiki::list_pages(&spec.user, &spec.repo)?
+        .into_iter()
+        .filter(|page| args.filter.is_match(&page.path))
+        .map(|page| GitHubDirEntry {
+            path: Some(page.path),
+            sha: Some(page.sha),
+            size: Some(page.size),
+            mode: None,
+            raw_path: None,
+            dest: None,
+        })
+        .collect();
+    let paths = listing
+        .iter()
+        .map(|gh| gh.path.clone().unwrap())
+        .collect::<Vec<String>>();
+
+    let selection = if args.all {
+        Some(paths)
+    } else {
+        get_from_fzf(paths, &Labels::new())?
+    };
+
+    if let Some(paths) = selection {
+        let mut urls = select_entries(listing, paths);
+        plan_destinations(&client, &spec, &args, &mut urls);
+        for dentry in urls.iter_mut() {
+            dentry.raw_path = Some(wiki::raw_url(
+                &spec.user,
+                &spec.repo,
+                dentry.path.as_ref().unwrap(),
+            ));
+        }
+        let summary = fetch_selection(&client, &raw_client, &spec, &args, urls).await?;
+        if summary.total() > 0 {
+            output::status(Status::Finished, &summary);
+        }
+        return Ok(summary);
+    }
+    Ok(Summary::default())
+}
+
+/// Collects every file below `dir`, recursively, except in `.git`; a missing
+/// `dir` is empty.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.file_name() == Some(".git".as_ref()) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// What happened to a single selected file.
+enum Outcome {
+    /// The file was written to the given path; the index is its position in
+    /// the selection.
+    Written(usize, PathBuf, Transfer),
+    Piped(Transfer),
+    Skipped,
+    Failed(Box<Failure>),
+    /// The file did not arrive within `--file-timeout`.
+    TimedOut(FileJob),
+}
+
+/// How many times a file that timed out is downloaded again before it is
+/// reported as failed.
+const FILE_REQUEUES: u32 = 2;
+
+/// A selected file waiting to be downloaded.
+struct FileJob {
+    /// The file's position in the selection.
+    index: usize,
+    path: String,
+    url: String,
+    dest: PathBuf,
+    sha: Option<String>,
+    size: Option<usize>,
+    mode: Option<String>,
+}
+
+/// Logs, prints and returns the outcome of a file that could not be
+/// downloaded.
+fn failed(mut failure: Failure, e: FileError) -> Outcome {
+    info!(url = %failure.url, status = failure.status, error = %e.error, "failed");
+    output::status(
+        Status::Failed,
+        format_args!(
+            "{}: {}: {}",
+            pathname::display(&failure.path),
+            e.what,
+            e.error
+        ),
+    );
+    failure.errors = report::error_chain(&*e.error);
+    Outcome::Failed(Box::new(failure))
+}
+
+/// What every file of a selection is downloaded with.
+struct FetchContext<'a> {
+    client: &'a ReqwestClient,
+    /// The API, which files the raw endpoint refuses are fetched from.
+    api: &'a Client,
+    /// The repository and ref the files are from.
+    spec: &'a Spec,
+    policy: OverwritePolicy,
+    refetch: bool,
+    template: Option<&'a Template>,
+    render: Option<&'a Render>,
+    lines: Option<(usize, usize)>,
+    prompt: &'a ConflictPrompt,
+    hooks: &'a Hooks,
+    base_env: &'a HookEnv,
+    progress: &'a Mutex<Progress>,
+    checksum: Option<Checksum>,
+    sink: Option<&'a Sink>,
+    /// The provider that fetches the files it gave no url for.
+    provider: Option<&'a Provider>,
+    /// The output directory, which destinations are keyed relative to when
+    /// they are sent to a sink.
+    out_dir: &'a Path,
+    /// How long one file may take to arrive.
+    file_timeout: Option<Duration>,
+    throttle: Throttle,
+    /// The validators of earlier downloads, for conditional requests.
+    validators: Mutex<Validators>,
+    /// Held while a file is piped to a command, so the output of the
+    /// commands does not interleave.
+    piping: tokio::sync::Mutex<()>,
+}
+
+/// One file of a selection.
+struct FileToFetch<'a> {
+    path: &'a str,
+    url: &'a str,
+    dest: PathBuf,
+    /// The blob SHA the listing gave, which the download is checked against.
+    sha: Option<String>,
+    size: Option<usize>,
+    /// The file mode the listing gave.
+    mode: Option<String>,
+}
+
+enum Fetched {
+    /// The file was written to the given path.
+    Written(PathBuf, Transfer),
+    /// The file was sent to the `--pipe` command or the `--dest` target.
+    Piped(Transfer),
+    Skipped,
+    /// The file did not arrive within `--file-timeout`; nothing was written.
+    TimedOut,
+}
+
+/// The size of a download as received and once decoded.
+#[derive(Debug, Clone, Copy)]
+struct Transfer {
+    /// The bytes received, which may be compressed.
+    wire: u64,
+    /// The bytes after decoding.
+    decoded: u64,
+}
+
+/// Why a file was not downloaded.
+struct FileError {
+    /// What was being done, e.g. "could not download".
+    what: &'static str,
+    /// The HTTP status of the response, if there was one.
+    status: Option<u16>,
+    error: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl FileError {
+    /// Wraps errors of the step described by `what`.
+    fn of<E>(what: &'static str, status: Option<u16>) -> impl FnOnce(E) -> FileError
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        move |e| FileError {
+            what,
+            status,
+            error: e.into(),
+        }
+    }
+}
+
+/// What a file is known by in `Args::fetched`: its url, and its blob at its
+/// destination, which another ref name of the same commit shares.
+fn fetched_keys(url: &str, sha: Option<&str>, dest: &Path) -> Vec<String> {
+    let blob = sha.map(|sha| format!("{}:{}", sha, dest.display()));
+    std::iter::once(url.to_string()).chain(blob).collect()
+}
+
+/// Downloads one file according to the overwrite policy, reporting failures
+/// rather than giving up on the rest of the selection.
+async fn fetch_file(
+    ctx: &FetchContext<'_>,
+    file: FileToFetch<'_>,
+) -> std::result::Result<Fetched, FileError> {
+    use std::fs;
+
+    let FileToFetch {
+        path,
+        url,
+        dest,
+        sha,
+        size,
+        mode,
+    } = file;
+    let shown = pathname::display(path);
+
+    let action = match ctx.sink {
+        // Nothing is written, so there is nothing to compare or overwrite.
+        Some(_) => Some(Action::Write),
+        None => {
+            let (local, remote) = (dest.clone(), sha.clone());
+            let (policy, refetch) = (ctx.policy, ctx.refetch);
+            // Comparing reads and hashes the whole local file.
+            tokio::task::spawn_blocking(move || {
+                // Re-running over the same selection should not refetch
+                // anything.
+                if !refetch && overwrite::is_unchanged(&local, remote.as_deref()) {
+                    return Ok(None);
+                }
+                policy.apply(&local, remote.as_deref()).map(Some)
+            })
+            .await
+            .expect("checking the local file panicked")
+            .map_err(FileError::of("could not check the local file", None))?
+        }
+    };
+    let action = match action {
+        Some(action) => action,
+        None => {
+            debug!(dest = %dest.display(), "unchanged, skipping");
+            output::status(Status::Skipped, format_args!("{} (unchanged)", shown));
+            return Ok(Fetched::Skipped);
+        }
+    };
+    if action == Action::Skip {
+        debug!(dest = %dest.display(), policy = ?ctx.policy, "exists, skipping");
+        output::status(Status::Skipped, format_args!("{} (exists)", shown));
+        return Ok(Fetched::Skipped);
+    }
+
+    // What this file answered with last time, at whichever commit, if the
+    // local file is still what it answered then.
+    let repo = format!("{}/{}", ctx.spec.user, ctx.spec.repo);
+    let validator = if ctx.refetch || ctx.sink.is_some() {
+        None
+    } else {
+        let key = Validators::key(&repo, path, &dest);
+        ctx.validators.lock().unwrap().get(&key, &dest).cloned()
+    };
+    let validator = match validator {
+        Some(validator) => {
+            let local = gitdown::hash::file_blob_shas(vec![dest.clone()])
+                .await
+                .pop()
+                .flatten();
+            local
+                .is_some_and(|sha| sha == validator.sha)
+                .then_some(validator)
+        }
+        None => None,
+    };
+
+    let received = async {
+        match receive(ctx, url, size, &dest, &shown, validator.as_ref()).await {
+            Err(e) if matches!(e.status, Some(403 | 404)) && ctx.provider.is_none() => {
+                receive_from_api(ctx, path, sha.as_deref(), e).await
+            }
+            received => received,
+        }
+    };
+    let received = match ctx.file_timeout {
+        Some(deadline) => match tokio::time::timeout(deadline, received).await {
+            Ok(received) => received?,
+            Err(_) => {
+                warn!(url, "no response after {:?}, giving up for now", deadline);
+                return Ok(Fetched::TimedOut);
+            }
+        },
+        None => received.await?,
+    };
+    let Received {
+        bytes,
+        status,
+        etag,
+        last_modified,
+        content_encoding,
+        elapsed,
+        backend,
+        hasher,
+    } = match received {
+        Some(received) => received,
+        None => return Ok(Fetched::Skipped),
+    };
+    let status = Some(status);
+    let received = bytes.len();
+    // Decoded before any rewriting, so hashes are of the file's content.
+    let bytes = encoding::decode(content_encoding.as_deref(), bytes)
+        .map_err(FileError::of("could not decode the response", status))?;
+    let transfer = Transfer {
+        wire: received as u64,
+        decoded: bytes.len() as u64,
+    };
+    let streamed = match hasher {
+        Some(hasher) => hasher.finish(bytes.len()).await,
+        None => None,
+    };
+    let (bytes, hashes) = match streamed {
+        Some(hashes) => (bytes, hashes),
+        None => Hashes::off_runtime(bytes, ctx.checksum).await,
+    };
+    // The listing named the blob, so a truncated or altered body is caught
+    // before anything is written.
+    if let Some(expected) = &sha {
+        if hashes.blob != *expected {
+            let error = Error::new(ErrorKind::ChecksumMismatch {
+                name: path.to_string(),
+                expected: expected.clone(),
+                actual: hashes.blob,
+            });
+            return Err(FileError::of("could not verify the file", status)(error));
+        }
+    }
+
+    let bytes = match ctx.template {
+        Some(template) => template.render_bytes(bytes),
+        None => bytes,
+    };
+    let bytes = match ctx.lines {
+        Some(range) => permalink::extract_lines(&bytes, range),
+        None => bytes,
+    };
+    let bytes = match ctx.render.map(|render| render.render(path, &bytes)) {
+        Some(Ok(Some(rendered))) => rendered,
+        Some(Err(reason)) => {
+            warn!(path, %reason, "could not render the file, writing it as it is");
+            bytes
+        }
+        _ => bytes,
+    };
+
+    if let Some(sink) = ctx.sink {
+        // Keys use forward slashes whatever the platform.
+        let key = dest
+            .strip_prefix(ctx.out_dir)
+            .unwrap_or(&dest)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let mut env = ctx.base_env.clone();
+        env.extend(vec![
+            ("GITDOWN_FILE_PATH", path.to_string()),
+            ("GITDOWN_FILE_URL", url.to_string()),
+            ("GITDOWN_FILE_SHA", sha.unwrap_or_default()),
+            ("GITDOWN_FILE_SIZE", bytes.len().to_string()),
+        ]);
+        let _piping = if sink.is_exclusive() {
+            Some(ctx.piping.lock().await)
+        } else {
+            None
+        };
+        sink.send(&key, &env, &bytes)
+            .await
+            .map_err(FileError::of("could not send the file", status))?;
+        let target = sink.describe(&key);
+        info!(
+            target = target.as_str(),
+            bytes = bytes.len(),
+            wire_bytes = received,
+            status,
+            backend,
+            "sent"
+        );
+        output::status(Status::Downloaded, format_args!("{} {}", shown, target));
+        return Ok(Fetched::Piped(transfer));
+    }
+
+    // The digest and the validator are of what is written, so rewritten
+    // content is hashed again.
+    let rewritten = ctx.template.is_some()
+        || ctx.lines.is_some()
+        || ctx
+            .render
+            .is_some_and(|render| render.rendering(path).is_some());
+    // A rewritten file never has the blob SHA of the listing, so it is
+    // compared with what would be written before the user is asked.
+    if action == Action::Conflict && rewritten {
+        let local = tokio::fs::read(longpath::for_io(&dest)).await;
+        if local.is_ok_and(|local| local == bytes) {
+            debug!(dest = %dest.display(), "unchanged, skipping");
+            output::status(Status::Skipped, format_args!("{} (unchanged)", shown));
+            return Ok(Fetched::Skipped);
+        }
+    }
+    let dest = if action == Action::Conflict {
+        match ctx
+            .prompt
+            .resolve(&dest, &bytes, ctx.out_dir)
+            .map_err(FileError::of("could not resolve the conflict", status))?
+        {
+            Resolution::Overwrite => dest,
+            Resolution::Rename(dest) => dest,
+            Resolution::Skip => {
+                output::status(
+                    Status::Skipped,
+                    format_args!("{} (kept the local copy)", shown),
+                );
+                return Ok(Fetched::Skipped);
+            }
+        }
+    } else {
+        dest
+    };
+
+    let (bytes, hashes) = if rewritten {
+        Hashes::off_runtime(bytes, ctx.checksum).await
+    } else {
+        (bytes, hashes)
+    };
+    let len = bytes.len();
+    let Hashes {
+        blob,
+        checksum: digest,
+    } = hashes;
+    let validator = (etag.is_some() || last_modified.is_some()).then(|| Validator {
+        dest: dest.clone(),
+        etag,
+        last_modified,
+        sha: blob,
+    });
+    let io_dest = longpath::for_io(&dest);
+    let part = overwrite::part_path(&io_dest);
+    let written = io_dest
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&part, bytes))
+        .and_then(|_| match mode.as_deref() {
+            Some("100755") => overwrite::make_executable(&part),
+            _ => Ok(()),
+        })
+        // Only once the download is complete is the old file moved aside.
+        .and_then(|_| match action {
+            Action::Backup if io_dest.is_file() => {
+                fs::rename(&io_dest, overwrite::backup_path(&io_dest))
+            }
+            _ => Ok(()),
+        })
+        .and_then(|_| fs::rename(&part, &io_dest));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&part);
+        return Err(FileError::of("could not write the file", status)(e));
+    }
+    let bytes_per_sec = received as f64 / elapsed.as_secs_f64().max(0.001);
+    info!(
+        dest = %dest.display(),
+        bytes = len,
+        wire_bytes = received,
+        encoding = content_encoding.as_deref(),
+        checksum = digest.as_deref(),
+        status,
+        backend,
+        elapsed_ms = elapsed.as_millis() as u64,
+        bytes_per_sec,
+        "finished"
+    );
+    output::status(
+        Status::Downloaded,
+        format_args!(
+            "{} -> {} ({} at {}/s)",
+            shown,
+            dest.display(),
+            output::human_size(transfer.decoded),
+            output::human_size(bytes_per_sec as u64)
+        ),
+    );
+
+    if let Some(validator) = validator {
+        let key = Validators::key(&repo, path, &dest);
+        ctx.validators.lock().unwrap().record(key, validator);
+    }
+    if let Some(digest) = &digest {
+        println!("{}  {}", digest, dest.display());
+    }
+
+    if let Some(hook) = &ctx.hooks.post_download {
+        let mut env = ctx.base_env.clone();
+        env.extend(vec![
+            ("GITDOWN_FILE_PATH", path.to_string()),
+            ("GITDOWN_FILE_DEST", dest.display().to_string()),
+            ("GITDOWN_FILE_URL", url.to_string()),
+            ("GITDOWN_FILE_SHA", sha.unwrap_or_default()),
+            (
+                "GITDOWN_FILE_SIZE",
+                size.map(|s| s.to_string()).unwrap_or_default(),
+            ),
+        ]);
+        if let Err(e) = hooks::run(hook, &env).await {
+            error!(path, error = %e, "post_download hook failed");
+        }
+    }
+
+    Ok(Fetched::Written(dest, transfer))
+}
+
+/// A response body, read in full.
+struct Received {
+    bytes: Vec<u8>,
+    status: u16,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    content_encoding: Option<String>,
+    /// How long reading the body took.
+    elapsed: Duration,
+    /// What served the file: `raw`, `api` or `provider`.
+    backend: &'static str,
+    /// What hashed the body as it arrived, if it could be.
+    hasher: Option<StreamHasher>,
+}
+
+/// Requests `url` and reads its body, or returns `None` if `validator`
+/// shows the local copy is still current.
+async fn receive(
+    ctx: &FetchContext<'_>,
+    url: &str,
+    size: Option<usize>,
+    dest: &Path,
+    shown: &str,
+    validator: Option<&Validator>,
+) -> std::result::Result<Option<Received>, FileError> {
+    info!(url, size, "started");
+    if let Some((provider, blob)) = ctx.provider.and_then(|p| Some((p, p.blob(url)?))) {
+        let started = Instant::now();
+        let bytes = provider
+            .fetch_blob(&blob)
+            .await
+            .map_err(FileError::of("could not fetch", None))?;
+        ctx.progress.lock().unwrap().record(bytes.len() as u64);
+        return Ok(Some(Received {
+            bytes,
+            status: 200,
+            etag: None,
+            last_modified: None,
+            content_encoding: None,
+            elapsed: started.elapsed(),
+            backend: "provider",
+            hasher: None,
+        }));
+    }
+    let mut limited = 0;
+    let (res, admitted) = loop {
+        let admitted = ctx.throttle.admit().await;
+        let mut req = ctx
+            .client
+            .get(url)
+            .header(header::ACCEPT_ENCODING, encoding::ACCEPT_ENCODING);
+        if let Some(v) = validator {
+            if let Some(etag) = &v.etag {
+                req = req.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &v.last_modified {
+                req = req.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        usage::record_raw();
+        let res = req.send().await;
+        if let Ok(res) = &res {
+            if let Some(wait) = throttle::secondary_limit_wait(res.status(), res.headers()) {
+                if limited < throttle::RETRIES && wait <= throttle::MAX_WAIT {
+                    limited += 1;
+                    ctx.throttle.back_off(wait);
+                    continue;
+                }
+                let error = Error::new(ErrorKind::SecondaryRateLimited {
+                    status: res.status(),
+                    retry_after: Some(wait.as_secs()),
+                    documentation_url: None,
+                });
+                let status = Some(res.status().as_u16());
+                return Err(FileError::of("could not download", status)(error));
+            }
+        }
+        match res.and_then(|res| res.error_for_status()) {
+            Ok(res) if res.status() == reqwest::StatusCode::NOT_MODIFIED => {
+                debug!(dest = %dest.display(), "not modified, skipping");
+                output::status(Status::Skipped, format_args!("{} (not modified)", shown));
+                return Ok(None);
+            }
+            Ok(res) => break (res, admitted),
+            Err(e) => {
+                let status = e.status().map(|s| s.as_u16());
+                return Err(FileError::of("could not download", status)(e));
+            }
+        }
+    };
+    let status = res.status().as_u16();
+    let header_value = |name| {
+        res.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+    };
+    let (etag, last_modified) = (
+        header_value(header::ETAG),
+        header_value(header::LAST_MODIFIED),
+    );
+    let content_encoding = res
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    // A compressed body is shorter than the listed size.
+    let expected = res.content_length().map(|l| l as usize).or(size);
+    // A body sent as it is is the content, which can then be hashed as it
+    // arrives.
+    let hasher = match (
+        content_encoding.as_deref().map(str::trim),
+        res.content_length(),
+    ) {
+        (None | Some("") | Some("identity"), Some(len)) => {
+            Some(StreamHasher::start(len as usize, ctx.checksum))
+        }
+        _ => None,
+    };
+    let mut res = res;
+    let mut bytes = Vec::with_capacity(expected.unwrap_or(0));
+    let mut throughput = Throughput::new();
+    let mut reported = Instant::now();
+    while let Some(chunk) = res
+        .chunk()
+        .await
+        .map_err(FileError::of("could not read the response", Some(status)))?
+    {
+        bytes.extend_from_slice(&chunk);
+        if let Some(hasher) = &hasher {
+            hasher.feed(&chunk);
+        }
+        throughput.record(chunk.len() as u64);
+        if reported.elapsed() >= progress::REPORT_EVERY {
+            reported = Instant::now();
+            let remaining = expected.map(|s| s.saturating_sub(bytes.len()) as u64);
+            debug!(
+                bytes = bytes.len(),
+                size = expected,
+                bytes_per_sec = throughput.bytes_per_sec(),
+                eta_secs = remaining
+                    .and_then(|r| throughput.eta(r))
+                    .map(|eta| eta.as_secs_f64()),
+                "progress"
+            );
+        }
+
+        let mut overall = ctx.progress.lock().unwrap();
+        if overall.record(chunk.len() as u64) {
+            info!(
+                bytes = overall.received,
+                total = overall.total,
+                bytes_per_sec = overall.throughput.bytes_per_sec(),
+                eta_secs = overall.eta().map(|eta| eta.as_secs_f64()),
+                "overall progress"
+            );
+        }
+    }
+    drop(admitted);
+    Ok(Some(Received {
+        bytes,
+        status,
+        etag,
+        last_modified,
+        content_encoding,
+        elapsed: throughput.elapsed(),
+        backend: "raw",
+        hasher,
+    }))
+}
+
+/// Fetches a file the raw endpoint answered `refused` for through the API,
+/// which serves private repositories with the token and is not behind a
+/// replication lag. If that fails too, the raw endpoint's failure is the
+/// one reported.
+async fn receive_from_api(
+    ctx: &FetchContext<'_>,
+    path: &str,
+    sha: Option<&str>,
+    refused: FileError,
+) -> std::result::Result<Option<Received>, FileError> {
+    let spec = ctx.spec;
+    debug!(
+        path,
+        status = refused.status,
+        "the raw endpoint refused the file, asking the API"
+    );
+    let started = Instant::now();
+    let _admitted = ctx.throttle.admit().await;
+    match ctx
+        .api
+        .get_content(&spec.user, &spec.repo, spec.rev(), path, sha)
+        .await
+    {
+        Ok(bytes) => {
+            ctx.progress.lock().unwrap().record(bytes.len() as u64);
+            Ok(Some(Received {
+                bytes,
+                status: 200,
+                etag: None,
+                last_modified: None,
+                content_encoding: None,
+                elapsed: started.elapsed(),
+                backend: "api",
+                hasher: None,
+            }))
+        }
+        Err(e) => {
+            debug!(path, error = %e, "the API could not serve the file either");
+            Err(refused)
+        }
+    }
+}
+
+/// With `--at`, replaces the ref of `spec` by the last commit made on it
+/// before that date. With `--verify-tag`, first checks the tag it names.
+/// With `--explain`, every step is printed, up to one that fails.
+async fn pin_at(client: &Client, spec: &Spec, args: &Args) -> Result<Spec> {
+    if !args.explain {
+        return resolve_at(client, spec, args).await;
+    }
+    explain_spec(client, spec, args).await;
+    let pinned = resolve_at(client, spec, args).await;
+    explain_pinned(client, &pinned, args);
+    pinned
+}
+
+async fn resolve_at(client: &Client, spec: &Spec, args: &Args) -> Result<Spec> {
+    // A provider is given the ref as is.
+    if args.provider.is_some() {
+        return Ok(spec.clone());
+    }
+    if args.verify_tag {
+        verify_tag(client, spec).await?;
+    }
+    let date = match &args.at {
+        Some(date) => date,
+        None => return pin_commit(client, spec).await,
+    };
+    let tree = spec.tree.as_deref().unwrap_or("main");
+    match client
+        .commit_before(&spec.user, &spec.repo, tree, date)
+        .await?
+    {
+        Some(sha) => Ok(Spec {
+            tree: Some(sha.clone()),
+            commit: Some(sha),
+            ..spec.clone()
+        }),
+        None => Error::err(ErrorKind::NoCommitBefore {
+            date: date.clone(),
+            tree: tree.to_string(),
+            repo: format!("{}/{}", spec.user, spec.repo),
+        }),
+    }
+}
+
+fn explain(step: &str, how: impl Display) {
+    output::status(Status::Explain, format_args!("{:<10} {}", step, how));
+}
+
+/// Prints where the requests about `spec` go and which ref they ask for.
+async fn explain_spec(client: &Client, spec: &Spec, args: &Args) {
+    match spec.path.as_ref().or(spec.dir.as_ref()) {
+        Some(path) => explain(
+            "repository",
+            format_args!("{}/{}, path {}", spec.user, spec.repo, path),
+        ),
+        None => explain("repository", format_args!("{}/{}", spec.user, spec.repo)),
+    }
+    match REPO_CONFIG.get() {
+        Some(section) => explain(
+            "config",
+            format_args!(
+                "[repos.\"{}\"] of {}",
+                section.pattern,
+                section.config.display()
+            ),
+        ),
+        None => explain("config", "no [repos] section matches"),
+    }
+    match &args.provider {
+        Some(provider) => explain(
+            "provider",
+            format_args!("{} ({})", provider.name, provider.program().display()),
+        ),
+        None => explain("provider", "GitHub"),
+    }
+    // The client was built from them, so they are there.
+    if let Ok(settings) = ClientSettings::get() {
+        match settings.token {
+            Some(token) => explain("token", format_args!("from {}", token.source)),
+            None => explain("token", "none, so only public repositories are visible"),
+        }
+        let source = settings
+            .api_url
+            .map_or("the default".to_string(), |url| url.source);
+        explain(
+            "api",
+            format_args!("{} (from {})", client.api_url(), source),
+        );
+    }
+
+    match &spec.tree {
+        Some(tree) => explain("ref", format_args!("{} (given)", tree)),
+        None if args.provider.is_some() => explain("ref", "main (none given)"),
+        None => match client.default_branch(&spec.user, &spec.repo).await {
+            Ok(branch) if branch == "main" => {
+                explain("ref", "main (none given), the repository's default branch")
+            }
+            Ok(branch) => explain(
+                "ref",
+                format_args!(
+                    "main (none given), though the repository's default branch is {}; pass --ref {} for it",
+                    branch, branch
+                ),
+            ),
+            Err(e) => explain(
+                "ref",
+                format_args!("main (none given); the repository could not be looked up: {}", e),
+            ),
+        },
+    }
+    if let Some(date) = &args.at {
+        explain("at", format_args!("the last commit before {}", date));
+    }
+}
+
+/// Prints what the ref of a spec was resolved to, and the urls its files
+/// are fetched from.
+fn explain_pinned(client: &Client, pinned: &Result<Spec>, args: &Args) {
+    let spec = match pinned {
+        Ok(spec) => spec,
+        Err(e) => return explain("commit", format_args!("could not be resolved: {}", e)),
+    };
+    match (&spec.commit, &args.provider) {
+        (Some(commit), _) => explain("commit", commit),
+        (None, Some(provider)) => explain(
+            "commit",
+            format_args!("not resolved; {} is given the ref as is", provider.name),
+        ),
+        (None, None) => explain("commit", "not resolved"),
+    }
+    match &args.provider {
+        Some(provider) => explain(
+            "raw urls",
+            format_args!("from {}, file by file", provider.name),
+        ),
+        None => {
+            let source = ClientSettings::get()
+                .ok()
+                .and_then(|settings| settings.raw_url)
+                .map_or("the default".to_string(), |url| url.source);
+            let template = client.raw_url(&spec.user, &spec.repo, spec.rev(), "");
+            explain(
+                "raw urls",
+                format_args!("{}{{path}} (from {})", template, source),
+            );
+        }
+    }
+}
+
+/// Resolves the ref of `spec` to the commit it points at now, so that the
+/// listing and every file come from the same tree.
+async fn pin_commit(client: &Client, spec: &Spec) -> Result<Spec> {
+    let tree = spec.tree.as_deref().unwrap_or("main");
+    let commit = if client::is_commit_sha(tree) {
+        tree.to_string()
+    } else {
+        match client.resolve_commit(&spec.user, &spec.repo, tree).await {
+            Ok(commit) => commit,
+            Err(e) => return Err(missing_repo(client, spec, e).await),
+        }
+    };
+    debug!(repo = %spec, commit = %commit, "pinned the ref");
+    Ok(Spec {
+        commit: Some(commit),
+        ..spec.clone()
+    })
+}
+
+/// Turns `e`, the failure of the first request about `spec`, into
+/// [`ErrorKind::RepoDoesNotExist`] with the owner's repositories closest to
+/// the name, if the repository turns out not to exist. Otherwise `e` is
+/// passed on.
+async fn missing_repo(client: &Client, spec: &Spec, e: Box<Error>) -> Box<Error> {
+    if !matches!(
+        e.kind(),
+        ErrorKind::TreeDoesNotExist { .. } | ErrorKind::NotFound { .. }
+    ) {
+        return e;
+    }
+    // If the repository exists, it is the ref that is wrong.
+    if !matches!(
+        client.repository_exists(&spec.user, &spec.repo).await,
+        Ok(false)
+    ) {
+        return e;
+    }
+    // An owner that does not exist has nothing to suggest.
+    let repos = client
+        .owner_repositories(&spec.user)
+        .await
+        .unwrap_or_default();
+    Error::new(ErrorKind::RepoDoesNotExist {
+        repo: format!("{}/{}", spec.user, spec.repo),
+        suggestions: suggest::closest(&spec.repo, repos.iter().map(String::as_str))
+            .into_iter()
+            .map(|repo| format!("{}/{}", spec.user, repo))
+            .collect(),
+    })
+}
+
+/// Fails unless the ref of `spec` is an annotated tag whose signature
+/// GitHub verified.
+async fn verify_tag(client: &Client, spec: &Spec) -> Result<()> {
+    let repo = format!("{}/{}", spec.user, spec.repo);
+    let tag = match &spec.tree {
+        Some(tag) => tag,
+        None => {
+            return Error::err(ErrorKind::MissingOption {
+                option: "--verify-tag".to_string(),
+                needs: "--ref to name a tag".to_string(),
+            })
+        }
+    };
+    let unverified = |reason: &str| {
+        Error::err(ErrorKind::UnverifiedTag {
+            tag: tag.clone(),
+            repo: repo.clone(),
+            reason: reason.to_string(),
+        })
+    };
+    match client.tag_verification(&spec.user, &spec.repo, tag).await {
+        Ok(Some(v)) if v.verified => {
+            info!(tag = %tag, reason = %v.reason, "verified tag");
+            Ok(())
+        }
+        Ok(Some(v)) => unverified(&v.reason),
+        Ok(None) => unverified("a lightweight tag cannot be signed"),
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound { .. }) => unverified("no such tag"),
+        Err(e) => Err(e),
+    }
+}
+
+/// Lists the blobs of a repository that pass the filter, are under the
+/// directory its link named and, with `--since`, changed since then.
+#[tracing::instrument(name = "list", skip_all, fields(repo = %spec))]
+async fn list_repo(client: &Client, spec: &Spec, args: &Args) -> Result<Vec<GitHubDirEntry>> {
+    let res = list_tree(client, spec, args).await?;
+    narrow_listing(client, spec, args, res).await
+}
+
+/// Lists every blob of a repository, keeping the listing for the next
+/// `--cache stale-while-revalidate` run.
+async fn list_tree(client: &Client, spec: &Spec, args: &Args) -> Result<Vec<GitHubDirEntry>> {
+    let tree = spec.rev();
+    let res = match &args.provider {
+        Some(provider) => provider.list_tree(&spec.user, &spec.repo, tree).await?,
+        None => {
+            client
+                .get_dentries(spec.user.as_str(), spec.repo.as_str(), Some(tree))
+                .await?
+        }
+    };
+    if args.stale_while_revalidate {
+        cache_tree(spec, &CachedTree::new(spec.commit.clone(), &res));
+    }
+    Ok(res)
+}
+
+/// Keeps the listing of `spec`; failing to is only logged.
+fn cache_tree(spec: &Spec, cached: &CachedTree) {
+    let tree = spec.tree.as_deref().unwrap_or("main");
+    if let Some(path) = CachedTree::path(&spec.user, &spec.repo, tree) {
+        if let Err(e) = cached.save(&path) {
+            warn!("could not cache the listing of {}: {}", spec, e);
+        }
+    }
+}
+
+/// The entries of a listing that pass the filters and the `--where`
+/// expression, in the `--sort` order.
+async fn narrow_listing(
+    client: &Client,
+    spec: &Spec,
+    args: &Args,
+    res: Vec<GitHubDirEntry>,
+) -> Result<Vec<GitHubDirEntry>> {
+    let tree = spec.rev();
+    let changed = match &args.since {
+        Some(since) => {
+            client
+                .changed_since(&spec.user, &spec.repo, tree, since)
+                .await?
+        }
+        None => None,
+    };
+
+    let listed = res.len();
+    let mut res: Vec<GitHubDirEntry> = res
+        .into_iter()
+        .filter(|gh| args.filter.is_match(gh.path.as_ref().unwrap()))
+        .filter(|gh| match &spec.dir {
+            Some(dir) => Path::new(gh.path.as_ref().unwrap()).starts_with(dir),
+            None => true,
+        })
+        .filter(|gh| {
+            changed
+                .as_ref()
+                .is_none_or(|changed| changed.contains(gh.path.as_ref().unwrap()))
+        })
+        .collect();
+    if let Some(condition) = &args.condition {
+        let ages = if condition.uses_age() {
+            let paths: Vec<String> = res.iter().filter_map(|gh| gh.path.clone()).collect();
+            let now = picker::now();
+            client
+                .last_commit_dates(&spec.user, &spec.repo, tree, &paths)
+                .await?
+                .into_iter()
+                .filter_map(|(path, date)| {
+                    let timestamp = picker::parse_timestamp(&date)?;
+                    Some((path, now.saturating_sub(timestamp)))
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        res.retain(|gh| {
+            let path = gh.path.as_deref().unwrap();
+            condition.is_match(&Facts {
+                path,
+                size: gh.size.map(|s| s as u64),
+                age: ages.get(path).copied(),
+            })
+        });
+    }
+    if let Some(key) = args.sort {
+        picker::sort(&mut res, key);
+    }
+    debug!(listed, kept = res.len(), "listed the tree");
+    Ok(res)
+}
+
+/// Labels the entries of `listing` in the picker, by path: whether they
+/// exist locally and, if asked for, their age and an icon.
+///
+/// The labels are a nicety, so failing to look them up is only logged.
+async fn picker_labels(
+    client: &Client,
+    spec: &Spec,
+    args: &Args,
+    listing: &[GitHubDirEntry],
+) -> Labels {
+    let paths: Vec<String> = listing.iter().filter_map(|gh| gh.path.clone()).collect();
+    let mut columns = Vec::new();
+
+    // Files already downloaded are marked, so the missing ones stand out.
+    let mut planned = listing.to_vec();
+    plan_destinations(client, spec, args, &mut planned);
+    planned.retain(|gh| gh.dest.as_ref().is_some_and(|dest| dest.exists()));
+    let dests = planned.iter().map(|gh| gh.dest.clone().unwrap()).collect();
+    let locals = gitdown::hash::file_blob_shas(dests).await;
+    let markers: Labels = planned
+        .into_iter()
+        .zip(locals)
+        .filter_map(|(gh, local)| {
+            let marker = match (local, &gh.sha) {
+                (Some(local), Some(sha)) if local == *sha => "[local]",
+                _ => "[modified]",
+            };
+            Some((gh.path?, marker.to_string()))
+        })
+        .collect();
+    if !markers.is_empty() {
+        columns.push(markers);
+    }
+
+    // fzf cannot preselect entries, so those picked last time are starred.
+    let history = History::path().and_then(|path| History::load(&path).ok());
+    let last = history
+        .as_ref()
+        .and_then(|h| h.last(&format!("{}/{}", spec.user, spec.repo)));
+    if let Some(last) = last {
+        let stars = last
+            .iter()
+            .filter(|path| paths.contains(path))
+            .map(|path| (path.clone(), "*".to_string()))
+            .collect::<Labels>();
+        if !stars.is_empty() {
+            columns.push(stars);
+        }
+    }
+
+    if args.show_age {
+        match client
+            .last_commit_dates(&spec.user, &spec.repo, spec.rev(), &paths)
+            .await
+        {
+            Ok(dates) => {
+                let now = picker::now();
+                let ages = dates
+                    .into_iter()
+                    .filter_map(|(path, date)| {
+                        let timestamp = picker::parse_timestamp(&date)?;
+                        Some((path, picker::age(timestamp, now)))
+                    })
+                    .collect();
+                columns.push(ages);
+            }
+            Err(e) => warn!("could not look up when files last changed: {}", e),
+        }
+    }
+    if let Some(set) = args.icons {
+        let icons = paths
+            .iter()
+            .map(|path| (path.clone(), picker::icon(path, set).to_string()))
+            .collect();
+        columns.push(icons);
+    }
+
+    picker::join_columns(&paths, &columns)
+}
+
+/// How many files `--all` takes before asking, unless the config says
+/// otherwise.
+const CONFIRM_ALL_ABOVE: usize = 500;
+
+/// Asks whether `--all` should go ahead with a selection this large. There
+/// is nobody to ask when stdin is not a terminal, so it always does then.
+fn confirm_all(args: &Args, listing: &[GitHubDirEntry]) -> Result<bool> {
+    match args.confirm_all_above {
+        Some(limit) if listing.len() > limit && io::stdin().is_terminal() => {
+            let bytes: usize = listing.iter().filter_map(|gh| gh.size).sum();
+            conflict::confirm(&format!(
+                "Download all {} files ({})?",
+                listing.len(),
+                output::human_size(bytes as u64)
+            ))
+        }
+        _ => Ok(true),
+    }
+}
+
+/// How large a selection can be before asking, unless `--confirm-above` or
+/// the config says otherwise: 1 GiB.
+const CONFIRM_ABOVE: u64 = 1 << 30;
+
+/// Asks whether a selection of `copies` copies of `selected` should be
+/// downloaded when it adds up to more than `--confirm-above`. With nobody to
+/// ask, it is refused until `--yes` is given. Nothing is downloaded by a dry
+/// run or the options that only print, so they never ask.
+fn confirm_size(args: &Args, selected: &[GitHubDirEntry], copies: usize) -> Result<bool> {
+    let limit = match args.confirm_above {
+        Some(limit) if !args.dry_run && !args.print_urls && args.emit_script.is_none() => limit,
+        _ => return Ok(true),
+    };
+    let bytes = selected.iter().filter_map(|gh| gh.size).sum::<usize>() as u64 * copies as u64;
+    if bytes <= limit {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        return Error::err(ErrorKind::SelectionTooLarge {
+            size: output::human_size(bytes),
+            limit: output::human_size(limit),
+        });
+    }
+    conflict::confirm(&format!(
+        "Download {} files ({})?",
+        selected.len(),
+        output::human_size(bytes)
+    ))
+}
+
+/// The paths last picked from the repository of `spec`.
+fn last_selection(spec: &Spec) -> Result<Vec<String>> {
+    let repo = format!("{}/{}", spec.user, spec.repo);
+    let history = match History::path() {
+        Some(path) => History::load(&path)?,
+        None => History::default(),
+    };
+    match history.last(&repo) {
+        Some(paths) => Ok(paths.to_vec()),
+        None => Error::err(ErrorKind::NoLastSelection { repo }),
+    }
+}
+
+/// Remembers `paths` as picked from the repository of `spec`, for `--last`.
+/// This is a convenience, so failing to is only logged.
+fn remember_selection(spec: &Spec, paths: &[String]) {
+    let path = match History::path() {
+        Some(path) => path,
+        None => return,
+    };
+    let remembered = History::load(&path).and_then(|mut history| {
+        history.record(&format!("{}/{}", spec.user, spec.repo), paths);
+        history.save(&path)
+    });
+    if let Err(e) = remembered {
+        warn!("could not remember the selection: {}", e);
+    }
+}
+
+/// Loads the validators of earlier downloads. They only save requests, so
+/// failing to is only logged.
+fn load_validators() -> Validators {
+    let path = match Validators::path() {
+        Some(path) => path,
+        None => return Validators::default(),
+    };
+    Validators::load(&path).unwrap_or_else(|e| {
+        warn!("could not load the validators of earlier downloads: {}", e);
+        Validators::default()
+    })
+}
+
+/// Saves `validators` for later runs, logging a failure to.
+fn save_validators(validators: &Validators) {
+    if let Some(path) = Validators::path() {
+        if let Err(e) = validators.save(&path) {
+            warn!("could not save the validators of this run: {}", e);
+        }
+    }
+}
+
+/// Picks the entries of `listing` named by `paths`, in the order of `paths`.
+fn select_entries(listing: Vec<GitHubDirEntry>, paths: Vec<String>) -> Vec<GitHubDirEntry> {
+    let _span = info_span!("select", count = paths.len()).entered();
+    debug!("selected {} paths", paths.len());
+    // Keep the listing around so the selected entries still carry their SHAs.
+    let mut listing: HashMap<String, GitHubDirEntry> = listing
+        .into_iter()
+        .map(|gh| (gh.path.clone().unwrap(), gh))
+        .collect();
+
+    let mut seen = HashSet::new();
+    paths
+        .into_iter()
+        .filter(|path| seen.insert(path.clone()))
+        .filter_map(|path| {
+            let entry = listing.remove(&path);
+            if entry.is_none() {
+                // Only a remembered or given list of paths can name a path
+                // that is not there.
+                warn!("{} is no longer in the repository, skipping", path);
+            }
+            entry
+        })
+        .collect()
+}
+
+/// Lists, selects and downloads the files of one repository.
+async fn download_repo(
+    client: &Client,
+    raw_client: &ReqwestClient,
+    spec: &Spec,
+    args: &Args,
+) -> Result<Summary> {
+    let picking = spec.path.is_none() && !args.all && args.selection.is_none() && !args.last;
+    if picking && args.stale_while_revalidate {
+        let tree = spec.tree.as_deref().unwrap_or("main");
+        let cached = CachedTree::path(&spec.user, &spec.repo, tree)
+            .map(|path| CachedTree::load(&path))
+            .transpose();
+        match cached {
+            Ok(Some(Some(cached))) => {
+                return download_revalidated(client, raw_client, spec, args, cached).await
+            }
+            Ok(_) => debug!("no cached listing of {}", spec),
+            Err(e) => warn!("could not read the cached listing of {}: {}", spec, e),
+        }
+    }
+
+    let spec = &pin_at(client, spec, args).await?;
+    let listing = list_repo(client, spec, args).await?;
+    let paths = listing
+        .iter()
+        .map(|gh| gh.path.clone().unwrap())
+        .collect::<Vec<String>>();
+
+    let selection = if let Some(path) = &spec.path {
+        if !paths.contains(path) {
+            return Error::err(ErrorKind::PathDoesNotExist {
+                path: path.clone(),
+                repo: format!("{}/{}", spec.user, spec.repo),
+                suggestions: suggest::closest(path, paths.iter().map(String::as_str)),
+            });
+        }
+        Some(vec![path.clone()])
+    } else if args.all {
+        if !confirm_all(args, &listing)? {
+            return Ok(Summary::default());
+        }
+        Some(paths)
+    } else if let Some(paths) = &args.selection {
+        Some(paths.clone())
+    } else if args.last {
+        Some(last_selection(spec)?)
+    } else {
+        let labels = picker_labels(client, spec, args, &listing).await;
+        let selection = pick(args, paths, &labels)?;
+        if let Some(paths) = &selection {
+            remember_selection(spec, paths);
+        }
+        selection
+    };
+
+    match selection {
+        Some(paths) => download_selected(client, raw_client, spec, args, listing, paths).await,
+        None => Ok(Summary::default()),
+    }
+}
+
+/// Opens the picker on `cached`, the listing of an earlier run, while the
+/// repository is pinned and listed again, then downloads the picked paths
+/// as the fresh listing has them. Paths that are gone are skipped, and
+/// changes are pointed out once the picker closes, as it cannot be updated
+/// while open.
+async fn download_revalidated(
+    client: &Client,
+    raw_client: &ReqwestClient,
+    spec: &Spec,
+    args: &Args,
+    cached: CachedTree,
+) -> Result<Summary> {
+    let stale_spec = Spec {
+        commit: cached.commit.clone(),
+        ..spec.clone()
+    };
+    let stale = narrow_listing(client, &stale_spec, args, cached.entries()).await?;
+    let labels = picker_labels(client, &stale_spec, args, &stale).await;
+    let paths: Vec<String> = stale.into_iter().filter_map(|gh| gh.path).collect();
+    let picker = {
+        let args = args.clone();
+        tokio::task::spawn_blocking(move || pick(&args, paths, &labels))
+    };
+    let refresh = async {
+        let spec = pin_at(client, spec, args).await?;
+        let entries = list_tree(client, &spec, args).await?;
+        Ok::<_, Box<Error>>((spec, entries))
+    };
+    let (refreshed, picked) = tokio::join!(refresh, picker);
+    let selection = picked.expect("the picker panicked")?;
+    let (spec, entries) = refreshed?;
+
+    let changes = cached.changes(&CachedTree::new(spec.commit.clone(), &entries));
+    let paths = match selection {
+        Some(paths) => paths,
+        None => return Ok(Summary::default()),
+    };
+    remember_selection(&spec, &paths);
+    if !changes.is_empty() {
+        output::status(
+            Status::Refreshed,
+            format_args!("{} changed since it was cached: {}", spec, changes),
+        );
+    }
+
+    let listing = narrow_listing(client, &spec, args, entries).await?;
+    let listed: HashSet<&str> = listing.iter().filter_map(|gh| gh.path.as_deref()).collect();
+    let (paths, gone): (Vec<String>, Vec<String>) = paths
+        .into_iter()
+        .partition(|path| listed.contains(path.as_str()));
+    for path in &gone {
+        output::status(
+            Status::Skipped,
+            format_args!("{} (no longer in {})", pathname::display(path), spec),
+        );
+    }
+    download_selected(client, raw_client, &spec, args, listing, paths).await
+}
+
+/// Downloads `paths` of `listing`, at every ref of `--ref` if there are
+/// several.
+async fn download_selected(
+    client: &Client,
+    raw_client: &ReqwestClient,
+    spec: &Spec,
+    args: &Args,
+    listing: Vec<GitHubDirEntry>,
+    paths: Vec<String>,
+) -> Result<Summary> {
+    let mut urls = select_entries(listing, paths);
+    // Every ref downloads a copy of its own.
+    if !confirm_size(args, &urls, args.refs.len().max(1))? {
+        return Ok(Summary::default());
+    }
+    if !args.refs.is_empty() {
+        let paths = urls.into_iter().filter_map(|gh| gh.path).collect();
+        return download_refs(client, raw_client, spec, args, paths).await;
+    }
+    plan_destinations(client, spec, args, &mut urls);
+    fetch_selection(client, raw_client, spec, args, urls).await
+}
+
+/// Downloads `paths` at every ref of `--ref`, each into a directory of the
+/// output directory named after the ref, so the copies can be diffed.
+async fn download_refs(
+    client: &Client,
+    raw_client: &ReqwestClient,
+    spec: &Spec,
+    args: &Args,
+    paths: Vec<String>,
+) -> Result<Summary> {
+    let mut summary = Summary::default();
+    for tree in &args.refs {
+        let spec = Spec {
+            tree: Some(tree.clone()),
+            commit: None,
+            ..spec.clone()
+        };
+        let spec = pin_at(client, &spec, args).await?;
+        let listing = list_repo(client, &spec, args).await?;
+
+        let mut args = args.clone();
+        let dir = tree.replace('/', "-");
+        let dir = NameStrategy::default().component(&dir, cfg!(windows));
+        args.layout.out_dir = args.layout.out_dir.join(&*dir);
+        let mut urls = select_entries(listing, paths.clone());
+        plan_destinations(client, &spec, &args, &mut urls);
+        summary += fetch_selection(client, raw_client, &spec, &args, urls).await?;
+    }
+    Ok(summary)
+}
+
+/// A repository listed for the merged picker, with the arguments its files
+/// are listed and downloaded with.
+struct Listed {
+    spec: Spec,
+    args: Args,
+    listing: Vec<GitHubDirEntry>,
+    labels: Labels,
+}
+
+/// Pins and lists every repository, `args.jobs` at a time, keeping their
+/// order. Each result is paired with the spec as given, to report failures
+/// by.
+async fn list_repos(
+    client: &Client,
+    repos: Vec<(Spec, Args)>,
+    args: &Args,
+) -> Vec<(Spec, Result<Listed>)> {
+    // Labels are only shown by the picker.
+    let labelled = !args.all && !args.last && args.selection.is_none();
+    futures::stream::iter(repos)
+        .map(|(spec, repo_args)| async move {
+            let listed = async {
+                let pinned = pin_at(client, &spec, &repo_args).await?;
+                let listing = list_repo(client, &pinned, &repo_args).await?;
+                let labels = if labelled {
+                    picker_labels(client, &pinned, &repo_args, &listing).await
+                } else {
+                    Labels::new()
+                };
+                Ok(Listed {
+                    spec: pinned,
+                    args: repo_args,
+                    listing,
+                    labels,
+                })
+            }
+            .await;
+            (spec, listed)
+        })
+        .buffered(args.jobs)
+        .collect()
+        .await
+}
+
+/// Lists several repositories at once, offers their files in a single
+/// picker (as `user/repo:path`) and downloads each selected file from its
+/// repository.
+async fn download_repos(
+    client: &Client,
+    raw_client: &ReqwestClient,
+    specs: &[Spec],
+    args: &Args,
+) -> Result<Summary> {
+    let repos = specs
+        .iter()
+        .map(|spec| (spec.clone(), args.clone()))
+        .collect();
+    let listed = list_repos(client, repos, args)
+        .await
+        .into_iter()
+        .map(|(_, listed)| listed)
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut summary = Summary::default();
+    for (_, fetched) in pick_from_repos(client, raw_client, listed, args).await? {
+        summary += fetched?;
+    }
+    Ok(summary)
+}
+
+/// Offers the files of every listed repository in a single picker and
+/// downloads the selection, one repository after the other. Each
+/// repository's result is returned with its spec; only a failure to pick is
+/// an error of its own.
+async fn pick_from_repos(
+    client: &Client,
+    raw_client: &ReqwestClient,
+    listed: Vec<Listed>,
+    args: &Args,
+) -> Result<Vec<(Spec, Result<Summary>)>> {
+    let prefix = |spec: &Spec| format!("{}/{}", spec.user, spec.repo);
+    let mut items = Vec::new();
+    let mut labels = Labels::new();
+    for repo in &listed {
+        let prefix = prefix(&repo.spec);
+        labels.extend(
+            repo.labels
+                .iter()
+                .map(|(path, label)| (format!("{}:{}", prefix, path), label.clone())),
+        );
+        items.extend(
+            repo.listing
+                .iter()
+                .map(|gh| format!("{}:{}", prefix, gh.path.as_ref().unwrap())),
+        );
+    }
+
+    let selection = if args.all {
+        let all: Vec<GitHubDirEntry> = listed
+            .iter()
+            .flat_map(|repo| repo.listing.iter().cloned())
+            .collect();
+        if !confirm_all(args, &all)? {
+            return Ok(Vec::new());
+        }
+        Some(items)
+    } else if let Some(paths) = &args.selection {
+        let mut selection = Vec::new();
+        for repo in &listed {
+            // The list names the paths of every repository at once, so each
+            // only takes those it has.
+            let prefix = prefix(&repo.spec);
+            let listed: HashSet<&str> = repo
+                .listing
+                .iter()
+                .filter_map(|gh| gh.path.as_deref())
+                .collect();
+            selection.extend(
+                paths
+                    .iter()
+                    .filter(|path| listed.contains(path.as_str()))
+                    .map(|path| format!("{}:{}", prefix, path)),
+            );
+        }
+        Some(selection)
+    } else if args.last {
+        let mut selection = Vec::new();
+        for repo in &listed {
+            let prefix = prefix(&repo.spec);
+            selection.extend(
+                last_selection(&repo.spec)?
+                    .into_iter()
+                    .map(|path| format!("{}:{}", prefix, path)),
+            );
+        }
+        Some(selection)
+    } else {
+        pick(args, items, &labels)?
+    };
+    let selection = match selection {
+        Some(selection) => selection,
+        None => return Ok(Vec::new()),
+    };
+    let remember = !args.all && !args.last && args.selection.is_none();
+
+    let mut picked = Vec::new();
+    for repo in listed {
+        let Listed {
+            spec,
+            args: repo_args,
+            listing,
+            ..
+        } = repo;
+        let prefix = prefix(&spec);
+        // Repository names cannot contain a colon, so the first one ends
+        // the prefix.
+        let paths: Vec<String> = selection
+            .iter()
+            .filter_map(|item| item.split_once(':'))
+            .filter(|(repo, _)| *repo == prefix)
+            .map(|(_, path)| path.to_string())
+            .collect();
+        if remember {
+            remember_selection(&spec, &paths);
+        }
+        if !paths.is_empty() {
+            picked.push((spec, repo_args, select_entries(listing, paths)));
+        }
+    }
+
+    let selected: Vec<GitHubDirEntry> = picked
+        .iter()
+        .flat_map(|(_, _, urls)| urls.iter().cloned())
+        .collect();
+    if !confirm_size(args, &selected, 1)? {
+        return Ok(Vec::new());
+    }
+
+    let mut fetched = Vec::new();
+    for (spec, repo_args, mut urls) in picked {
+        plan_destinations(client, &spec, &repo_args, &mut urls);
+        let summary = fetch_selection(client, raw_client, &spec, &repo_args, urls).await;
+        fetched.push((spec, summary));
+    }
+    Ok(fetched)
+}
+
+/// Fills in the raw url and local destination of the selected entries.
+fn plan_destinations(client: &Client, spec: &Spec, args: &Args, urls: &mut [GitHubDirEntry]) {
+    let (user, repo) = (&spec.user, &spec.repo);
+    let tree = spec.tree.as_deref().unwrap_or("main");
+    let Args {
+        layout, template, ..
+    } = args;
+    let prefixed;
+    let layout = if args.prefix_repo {
+        prefixed = Layout {
+            out_dir: layout.out_dir.join(repo_dir_name(spec)),
+            ..layout.clone()
+        };
+        &prefixed
+    } else {
+        layout
+    };
+
+    let selected: Vec<&str> = urls.iter().map(|d| d.path.as_deref().unwrap()).collect();
+    let vars = RenameVars {
+        owner: user,
+        repo,
+        tree,
+    };
+    let mut dests = layout.destinations(&selected, &vars);
+    if let Some(template) = template {
+        for dest in dests.iter_mut() {
+            *dest = PathBuf::from(template.render(&dest.to_string_lossy()).into_owned());
+        }
+    }
+    if let Some(render) = &args.render {
+        for (dest, path) in dests.iter_mut().zip(&selected) {
+            *dest = render.dest(path, dest);
+        }
+    }
+    for (dentry, dest) in urls.iter_mut().zip(dests) {
+        let path = dentry.path.as_ref().unwrap();
+        dentry.raw_path = Some(match &args.provider {
+            Some(provider) => provider.blob_url(user, repo, spec.rev(), path),
+            None => client.raw_url(user, repo, spec.rev(), path),
+        });
+        dentry.dest = Some(dest);
+    }
+}
+
+/// Fails if `out_dir` is not in a git worktree or, unless forced, if a file
+/// about to be written has uncommitted changes, for `--apply-to`.
+fn check_worktree(apply: Apply, out_dir: &Path, urls: &[GitHubDirEntry]) -> Result<()> {
+    let dirty = git::dirty_paths(out_dir)?;
+    if apply.force {
+        return Ok(());
+    }
+    let dirty: HashSet<PathBuf> = dirty.iter().map(|p| out_dir.join(p)).collect();
+    let clobbered: Vec<String> = urls
+        .iter()
+        .filter_map(|d| d.dest.as_ref())
+        .filter(|dest| dirty.contains(*dest))
+        .map(|dest| dest.display().to_string())
+        .collect();
+    if clobbered.is_empty() {
+        Ok(())
+    } else {
+        Error::err(ErrorKind::UncommittedChanges { paths: clobbered })
+    }
+}
+
+/// The directory `--prefix-repo` writes the files of `spec` under:
+/// `<owner>-<repo>`, followed by `-<ref>` if one was given.
+fn repo_dir_name(spec: &Spec) -> String {
+    let mut name = format!("{}-{}", spec.user, spec.repo);
+    if let Some(tree) = &spec.tree {
+        name.push('-');
+        name.push_str(&tree.replace('/', "-"));
+    }
+    NameStrategy::default()
+        .component(&name, cfg!(windows))
+        .into_owned()
+}
+
+/// Removes the `.part` files of these destinations when dropped, which is
+/// also what happens to an interrupted download.
+struct PartFiles(Vec<PathBuf>);
+
+impl Drop for PartFiles {
+    fn drop(&mut self) {
+        for dest in &self.0 {
+            let _ = std::fs::remove_file(overwrite::part_path(&longpath::for_io(dest)));
+        }
+    }
+}
+
+/// Checks the selected entries with a HEAD request each, falling back to a
+/// request for their first byte where HEAD is refused, and fails if any is
+/// missing, differs in size from the listing or looks like an error page.
+/// Every problem is reported before failing, so a large download can be
+/// fixed in one go.
+async fn preflight(raw_client: &ReqwestClient, urls: &[GitHubDirEntry], jobs: usize) -> Result<()> {
+    let problems: Vec<(String, String)> = futures::stream::iter(urls)
+        .map(|d| async move {
+            let path = d.path.clone().unwrap();
+            preflight_file(raw_client, d)
+                .await
+                .err()
+                .map(|problem| (path, problem))
+        })
+        .buffered(jobs)
+        .filter_map(|problem| async move { problem })
+        .collect()
+        .await;
+
+    for (path, problem) in &problems {
+        output::status(Status::Failed, format_args!("{}: {}", path, problem));
+    }
+    if !problems.is_empty() {
+        return Error::err(ErrorKind::PreflightFailure {
+            problems: problems.len(),
+            files: urls.len(),
+        });
+    }
+    let bytes: usize = urls.iter().filter_map(|d| d.size).sum();
+    info!(files = urls.len(), bytes, "preflight passed");
+    Ok(())
+}
+
+/// Checks a single entry for [`preflight`], describing what is wrong with
+/// it.
+async fn preflight_file(
+    raw_client: &ReqwestClient,
+    d: &GitHubDirEntry,
+) -> std::result::Result<(), String> {
+    let url = d.raw_path.as_deref().unwrap();
+    let failed = |e: reqwest::Error| format!("could not check it: {}", e);
+    usage::record_raw();
+    let mut res = raw_client.head(url).send().await.map_err(failed)?;
+    let mut ranged = false;
+    if res.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED {
+        usage::record_raw();
+        res = raw_client
+            .get(url)
+            .header(header::RANGE, "bytes=0-0")
+            .send()
+            .await
+            .map_err(failed)?;
+        ranged = res.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    }
+    if !res.status().is_success() {
+        return Err(format!("the server answered {}", res.status()));
+    }
+
+    let header_value = |name| res.headers().get(name).and_then(|v| v.to_str().ok());
+    // A ranged answer gives the full size after the slash of its range.
+    let size = if ranged {
+        header_value(header::CONTENT_RANGE)
+            .and_then(|range| range.rsplit('/').next())
+            .and_then(|size| size.parse::<usize>().ok())
+    } else {
+        header_value(header::CONTENT_LENGTH).and_then(|size| size.parse().ok())
+    };
+    if let (Some(listed), Some(size)) = (d.size, size) {
+        if listed != size {
+            return Err(format!(
+                "{} bytes are served where {} were listed",
+                size, listed
+            ));
+        }
+    }
+
+    // Raw files are never served as HTML, so a page is a login or error
+    // page in their place.
+    let path = d.path.as_deref().unwrap_or_default();
+    let page = header_value(header::CONTENT_TYPE).is_some_and(|t| t.starts_with("text/html"));
+    if page && !path.ends_with(".html") && !path.ends_with(".htm") {
+        return Err("an HTML page is served in its place".to_string());
+    }
+    Ok(())
+}
+
+/// Downloads the selected entries, whose destinations have been planned.
+async fn fetch_selection(
+    client: &Client,
+    raw_client: &ReqwestClient,
+    spec: &Spec,
+    args: &Args,
+    mut urls: Vec<GitHubDirEntry>,
+) -> Result<Summary> {
+    let Args {
+        policy,
+        refetch,
+        layout,
+        template,
+        dry_run,
+        print_urls,
+        emit_script,
+        exec,
+        lockfile,
+        error_log,
+        config,
+        lines_only,
+        print_lines,
+        checksum,
+        sink,
+        file_timeout,
+        apply,
+        ..
+    } = args;
+    let (user, repo) = (&spec.user, &spec.repo);
+    let (policy, refetch) = (*policy, *refetch);
+    let lines = if *lines_only { spec.lines } else { None };
+    let tree = spec.tree.as_deref().unwrap_or("main");
+
+    // Repositories given twice, or under another ref name of the same
+    // commit, would otherwise download the same url (or the same blob to the
+    // same place) again.
+    let selected = urls.len();
+    {
+        let fetched = args.fetched.lock().unwrap();
+        let mut seen = HashSet::new();
+        urls.retain(|d| {
+            let keys = fetched_keys(
+                d.raw_path.as_ref().unwrap(),
+                d.sha.as_deref(),
+                d.dest.as_ref().unwrap(),
+            );
+            let new = keys
+                .iter()
+                .all(|k| !fetched.contains(k) && !seen.contains(k));
+            if new {
+                seen.extend(keys);
+            } else {
+                debug!(
+                    path = d.path.as_deref(),
+                    "already downloaded in this run, skipping"
+                );
+                output::status(
+                    Status::Skipped,
+                    format_args!(
+                        "{} (duplicate)",
+                        pathname::display(d.path.as_deref().unwrap())
+                    ),
+                );
+            }
+            new
+        });
+    }
+    let duplicates = selected - urls.len();
+
+    // Only now is the provider asked for the urls, of the files that are
+    // actually downloaded.
+    if let Some(provider) = &args.provider {
+        let paths: Vec<String> = urls.iter().map(|d| d.path.clone().unwrap()).collect();
+        let resolved = provider.raw_urls(user, repo, spec.rev(), &paths).await?;
+        for (dentry, url) in urls.iter_mut().zip(resolved) {
+            dentry.raw_path = Some(url);
+        }
+    }
+
+    if *print_urls {
+        let sha = match &spec.commit {
+            Some(commit) => commit.clone(),
+            None => client.resolve_commit(user, repo, tree).await?,
+        };
+        for dentry in &urls {
+            println!(
+                "{}",
+                client.raw_url(user, repo, &sha, dentry.path.as_ref().unwrap())
+            );
+        }
+        return Ok(Summary::default());
+    }
+
+    if let Some(script_path) = emit_script {
+        let sha = match &spec.commit {
+            Some(commit) => commit.clone(),
+            None => client.resolve_commit(user, repo, tree).await?,
+        };
+        // Each selection of the run adds to the script, which is written
+        // again in full.
+        let mut emitted = args.emitted.lock().unwrap();
+        emitted.origins.push(format!("{}/{}@{}", user, repo, sha));
+        emitted.files.extend(urls.iter().map(|d| {
+            (
+                client.raw_url(user, repo, &sha, d.path.as_ref().unwrap()),
+                d.dest.clone().unwrap(),
+                d.sha.clone(),
+            )
+        }));
+        let files: Vec<ScriptFile> = emitted
+            .files
+            .iter()
+            .map(|(url, dest, sha)| ScriptFile {
+                url,
+                dest,
+                sha: sha.as_deref(),
+            })
+            .collect();
+
+        let origin = emitted.origins.join(", ");
+        std::fs::write(script_path, script::shell_script(&origin, &files))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(script_path, std::fs::Permissions::from_mode(0o755))?;
+        }
+        return Ok(Summary::default());
+    }
+
+    if *dry_run {
+        for dentry in &urls {
+            let size = dentry
+                .size
+                .map_or_else(|| "unknown size".to_string(), |s| format!("{} bytes", s));
+            println!(
+                "{} -> {} ({})",
+                dentry.raw_path.as_ref().unwrap(),
+                dentry.dest.as_ref().unwrap().display(),
+                size
+            );
+        }
+        return Ok(Summary::default());
+    }
+
+    // Only past a dry run are file contents requested.
+    if args.preflight {
+        preflight(raw_client, &urls, args.jobs).await?;
+    }
+
+    if *print_lines {
+        let mut stdout = io::stdout();
+        for dentry in &urls {
+            usage::record_raw();
+            let res = raw_client
+                .get(dentry.raw_path.as_ref().unwrap())
+                .send()
+                .await?
+                .error_for_status()?;
+            let bytes = res.bytes().await?;
+            match lines {
+                Some(range) => stdout.write_all(&permalink::extract_lines(&bytes, range))?,
+                None => stdout.write_all(&bytes)?,
+            }
+        }
+        return Ok(Summary {
+            downloaded: urls.len(),
+            ..Summary::default()
+        });
+    }
+
+    let hooks = &config.hooks;
+    let base_env: HookEnv = vec![
+        ("GITDOWN_OWNER", user.clone()),
+        ("GITDOWN_REPO", repo.clone()),
+        ("GITDOWN_REF", tree.to_string()),
+        ("GITDOWN_DEST_DIR", layout.out_dir.display().to_string()),
+    ];
+    if let Some(apply) = apply {
+        check_worktree(*apply, &layout.out_dir, &urls)?;
+    }
+    if let Some(hook) = &hooks.pre_download {
+        hooks::run(hook, &base_env).await?;
+    }
+
+    let prompt = ConflictPrompt::new();
+    let parts = PartFiles(urls.iter().map(|d| d.dest.clone().unwrap()).collect());
+
+    let locked: Vec<(String, Option<String>)> = urls
+        .iter()
+        .map(|d| (d.path.clone().unwrap(), d.sha.clone()))
+        .collect();
+
+    let total: usize = urls.iter().filter_map(|d| d.size).sum();
+    let progress = Mutex::new(Progress::new(total as u64));
+    let ctx = FetchContext {
+        client: raw_client,
+        api: client,
+        spec,
+        policy,
+        refetch,
+        template: template.as_ref(),
+        render: args.render.as_ref(),
+        lines,
+        prompt: &prompt,
+        hooks,
+        base_env: &base_env,
+        progress: &progress,
+        checksum: *checksum,
+        sink: sink.as_ref(),
+        provider: args.provider.as_ref(),
+        out_dir: &layout.out_dir,
+        file_timeout: *file_timeout,
+        piping: tokio::sync::Mutex::new(()),
+        throttle: Throttle::new(),
+        validators: Mutex::new(load_validators()),
+    };
+    let fail = |job: &FileJob, e: FileError| {
+        failed(
+            Failure {
+                repo: format!("{}/{}", user, repo),
+                tree: tree.to_string(),
+                path: job.path.clone(),
+                url: job.url.clone(),
+                dest: job.dest.clone(),
+                status: e.status,
+                errors: Vec::new(),
+            },
+            e,
+        )
+    };
+    let fetch = |job: FileJob| {
+        let span = info_span!("download", path = %job.path);
+        let ctx = &ctx;
+        async move {
+            let file = FileToFetch {
+                path: &job.path,
+                url: &job.url,
+                dest: job.dest.clone(),
+                sha: job.sha.clone(),
+                size: job.size,
+                mode: job.mode.clone(),
+            };
+            let keys = fetched_keys(&job.url, job.sha.as_deref(), &job.dest);
+            let outcome = match deadline::within(fetch_file(ctx, file)).await {
+                Some(Ok(Fetched::Written(dest, transfer))) => {
+                    Outcome::Written(job.index, dest, transfer)
+                }
+                Some(Ok(Fetched::Piped(transfer))) => Outcome::Piped(transfer),
+                Some(Ok(Fetched::Skipped)) => Outcome::Skipped,
+                Some(Ok(Fetched::TimedOut)) => Outcome::TimedOut(job),
+                Some(Err(e)) => fail(&job, e),
+                None => {
+                    let e = FileError {
+                        what: "could not download",
+                        status: None,
+                        error: "the run ran out of --max-time".into(),
+                    };
+                    fail(&job, e)
+                }
+            };
+            // A file that failed is tried again where it comes up next.
+            if matches!(
+                outcome,
+                Outcome::Written(..) | Outcome::Piped(_) | Outcome::Skipped
+            ) {
+                args.fetched.lock().unwrap().extend(keys);
+            }
+            outcome
+        }
+        .instrument(span)
+    };
+
+    // A file that times out goes to the back of the queue, so one straggler
+    // does not hold up the rest; it is retried once the others are done.
+    let mut pending: Vec<FileJob> = urls
+        .drain(..)
+        .enumerate()
+        .map(|(index, dentry)| FileJob {
+            index,
+            path: dentry.path.unwrap(),
+            url: dentry.raw_path.unwrap(),
+            dest: dentry.dest.unwrap(),
+            sha: dentry.sha,
+            size: dentry.size,
+            mode: dentry.mode,
+        })
+        .collect();
+    let mut outcomes = Vec::new();
+    for attempt in 0..=FILE_REQUEUES {
+        if pending.is_empty() {
+            break;
+        }
+        if attempt > 0 {
+            let backoff = Duration::from_secs(1 << attempt);
+            info!(
+                files = pending.len(),
+                "retrying timed out files in {:?}", backoff
+            );
+            tokio::time::sleep(backoff).await;
+        }
+        let round = futures::stream::iter(pending.drain(..).map(fetch))
+            .buffer_unordered(args.jobs)
+            .collect::<Vec<Outcome>>()
+            .await;
+        for outcome in round {
+            match outcome {
+                Outcome::TimedOut(job) => pending.push(job),
+                outcome => outcomes.push(outcome),
+            }
+        }
+    }
+    for job in pending {
+        let e = FileError {
+            what: "could not download",
+            status: None,
+            error: format!(
+                "timed out {} times after {:?}",
+                FILE_REQUEUES + 1,
+                file_timeout.unwrap()
+            )
+            .into(),
+        };
+        outcomes.push(fail(&job, e));
+    }
+
+    let mut summary = Summary {
+        duplicates,
+        ..Summary::default()
+    };
+    let mut written = Vec::new();
+    let mut failures = Vec::new();
+    for outcome in outcomes {
+        match outcome {
+            Outcome::Written(i, dest, transfer) => {
+                summary.downloaded += 1;
+                summary.wire_bytes += transfer.wire;
+                summary.bytes += transfer.decoded;
+                written.push((i, dest));
+            }
+            Outcome::Piped(transfer) => {
+                summary.downloaded += 1;
+                summary.wire_bytes += transfer.wire;
+                summary.bytes += transfer.decoded;
+            }
+            Outcome::Skipped => summary.skipped += 1,
+            Outcome::Failed(failure) => {
+                summary.failed += 1;
+                failures.push(*failure);
+            }
+            Outcome::TimedOut(_) => unreachable!("timed out files are requeued"),
+        }
+    }
+    if !written.is_empty() {
+        save_validators(&ctx.validators.lock().unwrap());
+    }
+    if summary.downloaded > 0 {
+        summary.commits.extend(
+            spec.commit
+                .as_ref()
+                .map(|c| format!("{}/{}@{}", user, repo, c)),
+        );
+    }
+
+    if let Some(report_path) = error_log {
+        let mut report = ErrorReport::load(report_path)?;
+        report.forget(&parts.0);
+        report.failures.extend(failures);
+        report.save(report_path)?;
+    }
+
+    // Run hooks in selection order rather than completion order.
+    written.sort();
+
+    if let Some(lock_path) = lockfile {
+        let mut lock = Lockfile::load(lock_path)?;
+        for (i, dest) in &written {
+            let (path, sha) = &locked[*i];
+            lock.upsert(LockedFile {
+                repo: format!("{}/{}", user, repo),
+                tree: tree.to_string(),
+                commit: spec.commit.clone(),
+                path: path.clone(),
+                dest: dest.clone(),
+                sha: sha.clone(),
+            });
+        }
+        lock.save(lock_path)?;
+    }
+
+    let files: Vec<PathBuf> = written.into_iter().map(|(_, dest)| dest).collect();
+
+    if apply.is_some() && !files.is_empty() {
+        let out_dir = &layout.out_dir;
+        let paths: Vec<String> = files
+            .iter()
+            .filter_map(|f| f.strip_prefix(out_dir).ok())
+            .map(|f| f.to_string_lossy().into_owned())
+            .collect();
+        git::add(out_dir, &paths)?;
+        if !output::is_quiet() {
+            output::status(
+                Status::Finished,
+                format_args!("staged {} files in {}", paths.len(), out_dir.display()),
+            );
+        }
+    }
+
+    if let Some(hook) = exec {
+        hook.run(&files)?;
+    }
+
+    if let Some(hook) = &hooks.post_run {
+        let mut env = base_env.clone();
+        env.push((
+            "GITDOWN_FILES",
+            files
+                .iter()
+                .map(|f| f.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ));
+        env.push(("GITDOWN_FILE_COUNT", files.len().to_string()));
+        hooks::run(hook, &env).await?;
+    }
+
+    Ok(summary)
+}
@@ -0,0 +1,96 @@
+//! The manifest of `mirror`, which keeps a directory in sync with paths of
+//! several repositories.
+//!
+//! ```toml
+//! # Where the mirror is kept, relative to the manifest (its own directory
+//! # by default).
+//! dir = "third_party"
+//!
+//! [[repo]]
+//! name = "user/repo"
+//! ref = "v1.2.0"
+//! paths = ["include/**", "LICENSE"]
+//! exclude = ["**/*_test.h"]
+//! # The directory of the mirror its files go in, <owner>-<repo> by default.
+//! into = "repo"
+//! ```
+
+use crate::error::{Error, ErrorKind, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The default name of the manifest.
+pub const MANIFEST: &str = "mirror.toml";
+
+/// What a mirror is made of.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mirror {
+    /// Where the mirror is kept, relative to the manifest.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    #[serde(rename = "repo", default)]
+    pub repos: Vec<MirroredRepo>,
+}
+
+/// A repository of the mirror and the paths taken from it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MirroredRepo {
+    /// The repository, as `user/repo`.
+    pub name: String,
+    /// The branch, tag or commit to mirror; the default branch when `None`.
+    #[serde(rename = "ref", default)]
+    pub tree: Option<String>,
+    /// Globs of the paths to mirror; every path when empty.
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Globs of the paths to leave out.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// The directory of the mirror the files go in.
+    #[serde(default)]
+    pub into: Option<PathBuf>,
+}
+
+impl Mirror {
+    /// Loads the manifest at `path`.
+    pub fn load(path: &Path) -> Result<Mirror> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => {
+                return Error::err(ErrorKind::ReadFailure {
+                    path: path.display().to_string(),
+                })
+            }
+        };
+
+        let malformed = |reason: String| {
+            Error::err(ErrorKind::MalformedMirror {
+                path: path.display().to_string(),
+                reason,
+            })
+        };
+        let mirror: Mirror = match toml::from_str(&text) {
+            Ok(mirror) => mirror,
+            Err(e) => return malformed(e.to_string()),
+        };
+        if mirror.repos.is_empty() {
+            return malformed("it lists no [[repo]]".to_string());
+        }
+        Ok(mirror)
+    }
+
+    /// The directory the mirror is kept in, for the manifest at `path`.
+    pub fn root(&self, path: &Path) -> PathBuf {
+        let base = match path.parent() {
+            Some(base) if !base.as_os_str().is_empty() => base,
+            _ => Path::new("."),
+        };
+        match &self.dir {
+            Some(dir) => base.join(dir),
+            None => base.to_path_buf(),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! The status lines printed while downloading, e.g.
+//!
+//! ```text
+//!   Downloaded src/lib.rs -> ./lib.rs
+//!      Skipped README.md (unchanged)
+//! ```
+//!
+//! They go to stderr, so stdout stays free for `--print-urls` and friends.
+
+use std::fmt::Display;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static COLOR: AtomicBool = AtomicBool::new(false);
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// When to color output, as given to `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color when stderr is a terminal and `NO_COLOR` is not set.
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("unknown color choice {}", s)),
+        }
+    }
+}
+
+impl ColorChoice {
+    /// Whether this choice means coloring stderr.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && io::stderr().is_terminal()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Sets up status lines; `enabled` is false when something else (such as
+/// JSON logs) owns stderr, and `quiet` leaves only failures and the summary
+/// a run ends with.
+pub fn init(color: ColorChoice, enabled: bool, quiet: bool) {
+    COLOR.store(color.enabled(), Ordering::Relaxed);
+    ENABLED.store(enabled, Ordering::Relaxed);
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Whether only failures and the final summary are printed, for `-q`; a
+/// summary that is not the last of its run should then be left out.
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// What a status line reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Downloaded,
+    Skipped,
+    Failed,
+    Finished,
+    /// The requests a run issued.
+    Requests,
+    /// A cached listing turned out to be out of date.
+    Refreshed,
+    /// A step of resolving a repository, for `--explain`.
+    Explain,
+}
+
+impl Status {
+    fn label(self) -> (&'static str, &'static str) {
+        match self {
+            Status::Downloaded => ("Downloaded", "32"),
+            Status::Skipped => ("Skipped", "33"),
+            Status::Failed => ("Failed", "31"),
+            Status::Finished => ("Finished", "32"),
+            Status::Requests => ("Requests", "36"),
+            Status::Refreshed => ("Refreshed", "36"),
+            Status::Explain => ("Explain", "36"),
+        }
+    }
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 MiB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Parses a size like `1536`, `500K`, `1.5G` or `2 GiB`. Units are binary
+/// whether or not they are written with an `i`, and a trailing `B` is
+/// optional.
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let unit = unit.trim().to_ascii_lowercase();
+    let unit = unit.strip_suffix('b').unwrap_or(&unit);
+    let unit = unit.strip_suffix('i').unwrap_or(unit);
+    let power = match unit {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        _ => return None,
+    };
+    Some((number * 1024f64.powi(power)) as u64)
+}
+
+/// Prints `message` under a right-aligned, colored label.
+pub fn status(status: Status, message: impl Display) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    if is_quiet() && !matches!(status, Status::Failed | Status::Finished) {
+        return;
+    }
+
+    let (label, color) = status.label();
+    if COLOR.load(Ordering::Relaxed) {
+        eprintln!("\x1b[1;{}m{:>12}\x1b[0m {}", color, label, message);
+    } else {
+        eprintln!("{:>12} {}", label, message);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::error::Result;
+use crate::hash;
+use crate::longpath;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What to do when a destination file already exists locally.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Replace the existing file (the default).
+    #[default]
+    Overwrite,
+    /// Leave any existing file untouched.
+    Skip,
+    /// Leave the existing file untouched only if its blob SHA matches the
+    /// remote one; otherwise replace it.
+    SkipSame,
+    /// Move the existing file to `<name>.bak` (or the first free
+    /// `<name>.bak.<n>`) once the new one is ready to take its place.
+    Backup,
+    /// Ask the user what to do when the existing file differs from the
+    /// remote one (the default in interactive sessions).
+    Prompt,
+}
+
+/// The outcome of applying an `OverwritePolicy` to a destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// The destination is free, or may be replaced, and may be written.
+    Write,
+    /// The destination holds a file that is moved aside, with
+    /// `backup_path`, just before the download is renamed into place.
+    Backup,
+    /// The destination must be left as is.
+    Skip,
+    /// The destination holds a differing file and the user has to decide.
+    Conflict,
+}
+
+impl OverwritePolicy {
+    /// Decides whether `dest` may be written. Nothing is moved or removed
+    /// here, so a download that fails leaves `dest` as it was. `remote_sha`
+    /// is the blob SHA from the tree listing, if known.
+    pub fn apply(self, dest: &Path, remote_sha: Option<&str>) -> Result<Action> {
+        let dest = &*longpath::for_io(dest);
+        if !dest.is_file() {
+            return Ok(Action::Write);
+        }
+
+        use OverwritePolicy::*;
+        match self {
+            Overwrite => Ok(Action::Write),
+            Skip => Ok(Action::Skip),
+            SkipSame => match remote_sha {
+                Some(sha) if hash::file_blob_sha(dest)? == sha => Ok(Action::Skip),
+                _ => Ok(Action::Write),
+            },
+            Backup => Ok(Action::Backup),
+            Prompt => match remote_sha {
+                Some(sha) if hash::file_blob_sha(dest)? == sha => Ok(Action::Write),
+                _ => Ok(Action::Conflict),
+            },
+        }
+    }
+}
+
+/// Whether `dest` already holds the blob `remote_sha`, in which case there is
+/// nothing to download.
+pub fn is_unchanged(dest: &Path, remote_sha: Option<&str>) -> bool {
+    let dest = &*longpath::for_io(dest);
+    match remote_sha {
+        Some(sha) if dest.is_file() => hash::file_blob_sha(dest).is_ok_and(|local| local == sha),
+        _ => false,
+    }
+}
+
+/// Returns `<dest>.part`, where a download is written before it is renamed
+/// into place, so an interrupted run never leaves a truncated `dest`.
+pub fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+/// Gives `dest` execute permission wherever it has read permission, as git
+/// checks out a file of mode `100755`. Other platforms have no such bit.
+pub fn make_executable(dest: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = fs::metadata(dest)?.permissions();
+        let mode = permissions.mode();
+        permissions.set_mode(mode | (mode & 0o444) >> 2);
+        fs::set_permissions(dest, permissions)?;
+    }
+    #[cfg(not(unix))]
+    let _ = dest;
+    Ok(())
+}
+
+/// Returns the first of `<dest>.bak`, `<dest>.bak.1`, `<dest>.bak.2`, ...
+/// that does not exist, e.g. `src/lib.rs` becomes `src/lib.rs.bak`, so an
+/// earlier backup is never replaced.
+pub fn backup_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    let mut backup = dest.with_file_name(&name);
+    let mut n = 0;
+    while backup.symlink_metadata().is_ok() {
+        n += 1;
+        let mut numbered = name.clone();
+        numbered.push(format!(".{}", n));
+        backup = dest.with_file_name(numbered);
+    }
+    backup
+}
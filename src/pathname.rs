@@ -0,0 +1,146 @@
+//! Turning repository paths into local file names.
+//!
+//! Git paths are arbitrary bytes, and the API hands them out as strings with
+//! whatever they contain: spaces, `#`, control characters, unusual unicode,
+//! or names Windows refuses. Each component is checked before it becomes
+//! part of a destination, and paths are escaped before they become part of a
+//! url.
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use std::borrow::Cow;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The characters a raw url path segment cannot hold as they are; anything
+/// outside ASCII is escaped too.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b']')
+    .add(b'\\')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// The characters Windows does not allow in file names.
+const WINDOWS_RESERVED: &[char] = &['<', '>', ':', '"', '\\', '|', '?', '*'];
+
+/// The device names Windows reserves, with or without an extension.
+const WINDOWS_DEVICES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// What to do with file names that cannot be written as they are.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NameStrategy {
+    /// Percent-escape the offending characters, e.g. `a%3Ab`, which keeps
+    /// distinct names distinct.
+    #[default]
+    Escape,
+    /// Replace the offending characters with `_`.
+    Replace,
+    /// Write names as they are and let the file system complain.
+    Keep,
+}
+
+impl FromStr for NameStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "escape" => Ok(NameStrategy::Escape),
+            "replace" => Ok(NameStrategy::Replace),
+            "keep" => Ok(NameStrategy::Keep),
+            _ => Err(format!("unknown name strategy {}", s)),
+        }
+    }
+}
+
+impl NameStrategy {
+    /// The local form of the repository path `path`, for this platform.
+    pub fn local_path(self, path: &str) -> PathBuf {
+        self.local_path_for(path, cfg!(windows))
+    }
+
+    /// The local form of `path` on Windows (`windows`) or elsewhere.
+    ///
+    /// Empty, `.` and `..` components are always dropped, so no path can
+    /// escape the output directory.
+    pub fn local_path_for(self, path: &str, windows: bool) -> PathBuf {
+        path.split('/')
+            .filter(|c| !c.is_empty() && *c != "." && *c != "..")
+            .map(|c| self.component(c, windows).into_owned())
+            .collect()
+    }
+
+    /// The local form of a single file or directory name.
+    pub fn component(self, name: &str, windows: bool) -> Cow<'_, str> {
+        if self == NameStrategy::Keep {
+            return Cow::Borrowed(name);
+        }
+
+        let last = name.chars().count().saturating_sub(1);
+        let unsafe_at = |i: usize, c: char| {
+            c.is_control()
+                || (windows
+                    && (WINDOWS_RESERVED.contains(&c) || (i == last && (c == '.' || c == ' '))))
+        };
+        let mut local = String::with_capacity(name.len());
+        for (i, c) in name.chars().enumerate() {
+            if !unsafe_at(i, c) {
+                local.push(c);
+            } else if self == NameStrategy::Replace {
+                local.push('_');
+            } else {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    local.push_str(&format!("%{:02X}", b));
+                }
+            }
+        }
+
+        let stem = local.split('.').next().unwrap_or_default();
+        if windows && WINDOWS_DEVICES.iter().any(|d| d.eq_ignore_ascii_case(stem)) {
+            local.insert(0, '_');
+        }
+        if local == name {
+            Cow::Borrowed(name)
+        } else {
+            Cow::Owned(local)
+        }
+    }
+}
+
+/// Escapes a repository path for a url, keeping its `/` separators.
+pub fn url_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `path` with control characters escaped, so printing it cannot move the
+/// cursor or recolor the terminal.
+pub fn display(path: &str) -> Cow<'_, str> {
+    if !path.chars().any(char::is_control) {
+        return Cow::Borrowed(path);
+    }
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c.is_control() {
+            escaped.extend(c.escape_default());
+        } else {
+            escaped.push(c);
+        }
+    }
+    Cow::Owned(escaped)
+}
@@ -0,0 +1,155 @@
+//! Links to files on github.com, as copied from the browser or an issue.
+
+use percent_encoding::percent_decode_str;
+
+/// What a `https://github.com/user/repo[/blob/<ref>/<path>][#L<n>[-L<m>]]`
+/// or `https://github.com/user/repo/tree/<ref>[/<dir>]` link points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permalink {
+    pub user: String,
+    pub repo: String,
+    pub tree: Option<String>,
+    /// The file the link points at, if any.
+    pub path: Option<String>,
+    /// The directory the link points at, if any.
+    pub dir: Option<String>,
+    /// The first and last line highlighted by the link, 1-based.
+    pub lines: Option<(usize, usize)>,
+}
+
+/// Parses a github.com link, returning `None` for anything else.
+///
+/// The ref is taken to be the segment after `blob` or `tree`, so branch
+/// names with a slash are not supported; permalinks use a commit SHA anyway.
+pub fn parse(url: &str) -> Option<Permalink> {
+    let rest = ["https://", "http://", ""]
+        .iter()
+        .find_map(|scheme| url.strip_prefix(scheme)?.strip_prefix("github.com/"))?;
+    let (rest, fragment) = match rest.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (rest, None),
+    };
+    let rest = rest.split('?').next().unwrap_or_default();
+
+    let mut segments = rest.trim_end_matches('/').splitn(5, '/');
+    let user = segments.next().filter(|s| !s.is_empty())?;
+    let repo = segments.next().filter(|s| !s.is_empty())?;
+    let repo = repo.strip_suffix(".git").unwrap_or(repo);
+    let (tree, path, dir) = match (segments.next(), segments.next(), segments.next()) {
+        (None, _, _) => (None, None, None),
+        (Some("blob"), Some(tree), Some(path)) if !tree.is_empty() && !path.is_empty() => {
+            (Some(tree.to_string()), Some(path.to_string()), None)
+        }
+        (Some("tree"), Some(tree), dir) if !tree.is_empty() => (
+            Some(tree.to_string()),
+            None,
+            dir.filter(|d| !d.is_empty()).map(String::from),
+        ),
+        _ => return None,
+    };
+    let lines = match fragment {
+        Some(fragment) if path.is_some() => parse_lines(fragment),
+        _ => None,
+    };
+
+    Some(Permalink {
+        user: user.to_string(),
+        repo: repo.to_string(),
+        tree,
+        path,
+        dir,
+        lines,
+    })
+}
+
+/// What a raw file url points at: `<root>/user/repo/<ref>/<path>`, where
+/// the root is `https://raw.githubusercontent.com` or another raw endpoint,
+/// or a gist's `https://gist.githubusercontent.com/user/<id>/raw[/<rev>]/<file>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawLink {
+    pub user: String,
+    /// The repository, or the id of a gist.
+    pub repo: String,
+    /// The ref of a repository, or the revision of a gist if the url names
+    /// one.
+    pub tree: Option<String>,
+    /// The path of the file, percent-decoded.
+    pub path: String,
+}
+
+/// The raw content host of gists.
+const GIST_ROOT: &str = "https://gist.githubusercontent.com/";
+
+/// Parses a raw file url served from `root`, or from a gist, returning
+/// `None` for anything else.
+///
+/// As with [`parse`], the ref is taken to be a single segment.
+pub fn parse_raw(url: &str, root: &str) -> Option<RawLink> {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let decode = |path: &str| percent_decode_str(path).decode_utf8_lossy().into_owned();
+
+    if let Some(rest) = url.strip_prefix(GIST_ROOT) {
+        let mut segments = rest.splitn(4, '/');
+        let user = segments.next().filter(|s| !s.is_empty())?;
+        let id = segments.next().filter(|s| !s.is_empty())?;
+        if segments.next() != Some("raw") {
+            return None;
+        }
+        let rest = segments.next()?;
+        let (tree, file) = match rest.split_once('/') {
+            Some((rev, file)) => (Some(rev.to_string()), file),
+            None => (None, rest),
+        };
+        if file.is_empty() || file.contains('/') {
+            return None;
+        }
+        return Some(RawLink {
+            user: user.to_string(),
+            repo: id.to_string(),
+            tree,
+            path: decode(file),
+        });
+    }
+
+    let rest = url
+        .strip_prefix(root.trim_end_matches('/'))?
+        .strip_prefix('/')?;
+    let mut segments = rest.splitn(4, '/');
+    let user = segments.next().filter(|s| !s.is_empty())?;
+    let repo = segments.next().filter(|s| !s.is_empty())?;
+    let tree = segments.next().filter(|s| !s.is_empty())?;
+    let path = segments
+        .next()
+        .filter(|s| !s.is_empty() && !s.ends_with('/'))?;
+    Some(RawLink {
+        user: user.to_string(),
+        repo: repo.to_string(),
+        tree: Some(tree.to_string()),
+        path: decode(path),
+    })
+}
+
+/// Parses `L10` or `L10-L42`.
+fn parse_lines(fragment: &str) -> Option<(usize, usize)> {
+    let (start, end) = match fragment.split_once('-') {
+        Some((start, end)) => (start, end),
+        None => (fragment, fragment),
+    };
+    let start: usize = start.strip_prefix('L')?.parse().ok()?;
+    let end: usize = end.strip_prefix('L')?.parse().ok()?;
+    if start == 0 || end < start {
+        return None;
+    }
+    Some((start, end))
+}
+
+/// Keeps lines `start..=end` (1-based) of `bytes`, with their line endings.
+pub fn extract_lines(bytes: &[u8], (start, end): (usize, usize)) -> Vec<u8> {
+    bytes
+        .split_inclusive(|&b| b == b'\n')
+        .skip(start - 1)
+        .take(end + 1 - start)
+        .flatten()
+        .copied()
+        .collect()
+}
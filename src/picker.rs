@@ -0,0 +1,208 @@
+//! Labels shown next to the entries of the picker.
+
+use crate::client::GitHubDirEntry;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The order entries are listed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Alphabetically by path.
+    Path,
+    /// Largest first.
+    Size,
+    /// By extension, then path.
+    Ext,
+    /// Shallowest first, then by path.
+    Depth,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(SortKey::Path),
+            "size" => Ok(SortKey::Size),
+            "ext" => Ok(SortKey::Ext),
+            "depth" => Ok(SortKey::Depth),
+            _ => Err(format!("unknown sort key {}", s)),
+        }
+    }
+}
+
+/// Sorts `entries` by `key`; ties are broken by path.
+pub fn sort(entries: &mut [GitHubDirEntry], key: SortKey) {
+    let path = |e: &GitHubDirEntry| e.path.clone().unwrap_or_default();
+    match key {
+        SortKey::Path => entries.sort_by_key(path),
+        SortKey::Size => entries.sort_by_key(|e| (std::cmp::Reverse(e.size.unwrap_or(0)), path(e))),
+        SortKey::Ext => entries.sort_by_key(|e| {
+            let path = path(e);
+            let ext = Path::new(&path)
+                .extension()
+                .map(|ext| ext.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            (ext, path)
+        }),
+        SortKey::Depth => entries.sort_by_key(|e| {
+            let path = path(e);
+            (path.matches('/').count(), path)
+        }),
+    }
+}
+
+/// Which icons mark file types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconSet {
+    /// Nerd Font devicons, which need a patched font.
+    Nerd,
+    /// Plain ASCII markers that any terminal can show.
+    Ascii,
+}
+
+impl FromStr for IconSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nerd" => Ok(IconSet::Nerd),
+            "ascii" => Ok(IconSet::Ascii),
+            _ => Err(format!("unknown icon set {}", s)),
+        }
+    }
+}
+
+/// The icon of the file at `path`, from its name or extension.
+pub fn icon(path: &str, set: IconSet) -> &'static str {
+    let path = Path::new(path);
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    // (nerd, ascii)
+    let (nerd, ascii) = match (name.as_str(), ext.as_str()) {
+        ("dockerfile", _) => ("\u{f308}", "$"),
+        ("makefile", _) | (_, "sh" | "bash" | "zsh" | "fish" | "ps1") => ("\u{f489}", "$"),
+        ("license" | "licence" | "copying", _) => ("\u{f718}", "#"),
+        (_, "rs") => ("\u{e7a8}", "<>"),
+        (_, "py") => ("\u{e73c}", "<>"),
+        (_, "js" | "mjs" | "cjs" | "jsx") => ("\u{e74e}", "<>"),
+        (_, "ts" | "tsx") => ("\u{e628}", "<>"),
+        (_, "go") => ("\u{e627}", "<>"),
+        (_, "c" | "h") => ("\u{e61e}", "<>"),
+        (_, "cc" | "cpp" | "cxx" | "hpp") => ("\u{e61d}", "<>"),
+        (_, "java" | "kt") => ("\u{e738}", "<>"),
+        (_, "rb") => ("\u{e739}", "<>"),
+        (_, "html" | "htm") => ("\u{e736}", "<>"),
+        (_, "css" | "scss" | "sass") => ("\u{e749}", "<>"),
+        (_, "vim" | "lua") => ("\u{e62b}", "<>"),
+        (_, "md" | "markdown" | "rst" | "txt" | "adoc") => ("\u{e73e}", "#"),
+        (_, "json") => ("\u{e60b}", "="),
+        (_, "toml" | "yaml" | "yml" | "ini" | "cfg" | "conf" | "lock") => ("\u{e615}", "="),
+        (_, "png" | "jpg" | "jpeg" | "gif" | "svg" | "ico" | "webp") => ("\u{f1c5}", "*"),
+        (_, "zip" | "gz" | "tgz" | "xz" | "zst" | "tar" | "7z") => ("\u{f410}", "%"),
+        _ => ("\u{f15b}", "-"),
+    };
+    match set {
+        IconSet::Nerd => nerd,
+        IconSet::Ascii => ascii,
+    }
+}
+
+/// Joins label columns, each keyed by path, into one label per path, with
+/// every column padded to its widest value.
+pub fn join_columns(
+    paths: &[String],
+    columns: &[HashMap<String, String>],
+) -> HashMap<String, String> {
+    if columns.is_empty() {
+        return HashMap::new();
+    }
+    let widths: Vec<usize> = columns
+        .iter()
+        .map(|c| c.values().map(|v| v.chars().count()).max().unwrap_or(0))
+        .collect();
+
+    paths
+        .iter()
+        .map(|path| {
+            let label: Vec<String> = columns
+                .iter()
+                .zip(&widths)
+                .map(|(column, width)| {
+                    let value = column.get(path).map_or("", String::as_str);
+                    format!("{:<width$}", value, width = width)
+                })
+                .collect();
+            (path.clone(), label.join(" "))
+        })
+        .collect()
+}
+
+/// Parses an ISO 8601 UTC timestamp such as `2022-05-01T12:30:00Z` into
+/// seconds since the epoch.
+pub fn parse_timestamp(text: &str) -> Option<u64> {
+    let (date, time) = text.trim_end_matches('Z').split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (y, m, d) = (date.next()??, date.next()??, date.next()??);
+    let mut time = time.splitn(3, ':').map(|p| p.parse::<i64>().ok());
+    let (hh, mm, ss) = (time.next()??, time.next()??, time.next()??);
+
+    // Days since the epoch of a proleptic Gregorian date, after Howard
+    // Hinnant's `days_from_civil`.
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    u64::try_from(days * 86400 + hh * 3600 + mm * 60 + ss).ok()
+}
+
+/// The UTC date of `timestamp` (in seconds since the epoch), as
+/// `YYYY-MM-DD`.
+pub fn format_date(timestamp: u64) -> String {
+    // Howard Hinnant's `civil_from_days`, the inverse of the above.
+    let days = (timestamp / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// How long ago `timestamp` (in seconds since the epoch) was, compactly:
+/// `5m`, `3h`, `12d`, `4mo` or `2y`.
+pub fn age(timestamp: u64, now: u64) -> String {
+    let secs = now.saturating_sub(timestamp);
+    let (minute, hour, day) = (60, 60 * 60, 24 * 60 * 60);
+    match secs {
+        s if s < hour => format!("{}m", s / minute),
+        s if s < day => format!("{}h", s / hour),
+        s if s < 30 * day => format!("{}d", s / day),
+        s if s < 365 * day => format!("{}mo", s / (30 * day)),
+        s => format!("{}y", s / (365 * day)),
+    }
+}
+
+/// The current time in seconds since the epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
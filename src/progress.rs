@@ -0,0 +1,118 @@
+//! Transfer rates and estimates of the time remaining.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How far back rates look, so they follow changes in speed without jumping
+/// around with every chunk.
+pub const WINDOW: Duration = Duration::from_secs(5);
+
+/// How often progress is reported while a transfer runs.
+pub const REPORT_EVERY: Duration = Duration::from_secs(1);
+
+/// A transfer rate, averaged over the last [`WINDOW`].
+#[derive(Debug, Clone)]
+pub struct Throughput {
+    started: Instant,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl Default for Throughput {
+    fn default() -> Self {
+        Throughput {
+            started: Instant::now(),
+            samples: VecDeque::new(),
+        }
+    }
+}
+
+impl Throughput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that `bytes` were just received.
+    pub fn record(&mut self, bytes: u64) {
+        let now = Instant::now();
+        self.samples.push_back((now, bytes));
+        while let Some((at, _)) = self.samples.front() {
+            if now.duration_since(*at) <= WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// The rate in bytes per second, once there is anything to go by.
+    pub fn bytes_per_sec(&self) -> Option<f64> {
+        let now = Instant::now();
+        let since = now
+            .checked_sub(WINDOW)
+            .map_or(self.started, |start| start.max(self.started));
+        let elapsed = now.duration_since(since).as_secs_f64();
+        if elapsed < 0.001 || self.samples.is_empty() {
+            return None;
+        }
+        let bytes: u64 = self
+            .samples
+            .iter()
+            .filter(|(at, _)| *at >= since)
+            .map(|(_, bytes)| bytes)
+            .sum();
+        Some(bytes as f64 / elapsed)
+    }
+
+    /// How long `remaining` more bytes take at the current rate.
+    pub fn eta(&self, remaining: u64) -> Option<Duration> {
+        match self.bytes_per_sec() {
+            Some(rate) if rate > 0.0 => Some(Duration::from_secs_f64(remaining as f64 / rate)),
+            _ => None,
+        }
+    }
+
+    /// How long ago the transfer started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+/// The progress of a whole selection, shared by its downloads.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    /// The bytes expected, from the sizes in the listing.
+    pub total: u64,
+    /// The bytes received so far.
+    pub received: u64,
+    pub throughput: Throughput,
+    reported: Instant,
+}
+
+impl Progress {
+    pub fn new(total: u64) -> Self {
+        Progress {
+            total,
+            received: 0,
+            throughput: Throughput::new(),
+            reported: Instant::now(),
+        }
+    }
+
+    /// Records that `bytes` were just received, returning whether progress
+    /// is due to be reported.
+    pub fn record(&mut self, bytes: u64) -> bool {
+        self.received += bytes;
+        self.throughput.record(bytes);
+        if self.reported.elapsed() >= REPORT_EVERY {
+            self.reported = Instant::now();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// How long the rest of the selection takes at the current rate.
+    pub fn eta(&self) -> Option<Duration> {
+        self.throughput
+            .eta(self.total.saturating_sub(self.received))
+    }
+}
@@ -0,0 +1,284 @@
+//! Providers: external executables that serve repositories of hosts gitdown
+//! does not know, given with `--provider NAME`.
+//!
+//! A provider is an executable named `gitdown-provider-NAME` on `PATH`. It is
+//! run once per call with the method as its only argument and a JSON request
+//! on stdin:
+//!
+//! - `list-tree`, with `{"owner", "repo", "ref"}`, answers
+//!   `{"entries": [{"path", "sha", "size", "mode"}, ...]}` on stdout, listing
+//!   every blob; `sha`, `size` and `mode` may be `null`.
+//! - `resolve-raw-url`, with `{"owner", "repo", "ref", "paths": [...]}`,
+//!   answers `{"urls": [...]}`, a url (or `null`) per path that gitdown then
+//!   downloads over HTTP.
+//! - `fetch-blob`, with `{"owner", "repo", "ref", "path"}`, writes the
+//!   content of the file to stdout as is, for paths without a url.
+//!
+//! A provider that does not implement `resolve-raw-url` exits with
+//! [`UNSUPPORTED`], and every file is fetched with `fetch-blob`. Any other
+//! failure is reported with what the provider wrote to stderr.
+
+use crate::client::GitHubDirEntry;
+use crate::error::{Error, ErrorKind, Result};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// The prefix of provider executables.
+pub const PREFIX: &str = "gitdown-provider-";
+
+/// The exit status of a provider asked for a method it does not implement.
+pub const UNSUPPORTED: i32 = 64;
+
+/// The scheme of the urls of files only `fetch-blob` can fetch.
+const BLOB_SCHEME: &str = "gitdown-provider://";
+
+/// A provider found on `PATH`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provider {
+    pub name: String,
+    program: PathBuf,
+}
+
+/// What `list-tree` answers.
+#[derive(Debug, Deserialize)]
+struct Tree {
+    entries: Vec<TreeEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TreeEntry {
+    path: String,
+    #[serde(default)]
+    sha: Option<String>,
+    #[serde(default)]
+    size: Option<usize>,
+    #[serde(default)]
+    mode: Option<String>,
+}
+
+/// What `resolve-raw-url` answers.
+#[derive(Debug, Deserialize)]
+struct RawUrls {
+    urls: Vec<Option<String>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    owner: &'a str,
+    repo: &'a str,
+    #[serde(rename = "ref")]
+    tree: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    paths: Option<&'a [String]>,
+}
+
+/// A file `fetch-blob` is asked for, as named by its url.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blob {
+    pub owner: String,
+    pub repo: String,
+    pub tree: String,
+    pub path: String,
+}
+
+impl Provider {
+    /// Finds the executable of the provider `name` on `PATH`.
+    pub fn find(name: &str) -> Result<Provider> {
+        let file = format!("{}{}{}", PREFIX, name, std::env::consts::EXE_SUFFIX);
+        let program = std::env::var_os("PATH")
+            .iter()
+            .flat_map(std::env::split_paths)
+            .map(|dir| dir.join(&file))
+            .find(|path| path.is_file());
+        match program {
+            Some(program) => Ok(Provider {
+                name: name.to_string(),
+                program,
+            }),
+            None => Error::err(ErrorKind::NoSuchProvider {
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    /// The executable of the provider.
+    pub fn program(&self) -> &Path {
+        &self.program
+    }
+
+    /// Lists the blobs of `owner/repo` at `tree`.
+    pub async fn list_tree(
+        &self,
+        owner: &str,
+        repo: &str,
+        tree: &str,
+    ) -> Result<Vec<GitHubDirEntry>> {
+        let method = "list-tree";
+        let request = Request {
+            owner,
+            repo,
+            tree,
+            path: None,
+            paths: None,
+        };
+        let output = self.check(method, self.call(method, &request).await?)?;
+        let tree: Tree = self.parse(method, &output.stdout)?;
+        Ok(tree
+            .entries
+            .into_iter()
+            .map(|entry| GitHubDirEntry {
+                path: Some(entry.path),
+                sha: entry.sha,
+                size: entry.size,
+                mode: entry.mode,
+                raw_path: None,
+                dest: None,
+            })
+            .collect())
+    }
+
+    /// The url of each of `paths`, downloaded over HTTP, or for those the
+    /// provider gives none, its [`Provider::blob_url`].
+    pub async fn raw_urls(
+        &self,
+        owner: &str,
+        repo: &str,
+        tree: &str,
+        paths: &[String],
+    ) -> Result<Vec<String>> {
+        let method = "resolve-raw-url";
+        let request = Request {
+            owner,
+            repo,
+            tree,
+            path: None,
+            paths: Some(paths),
+        };
+        let output = self.call(method, &request).await?;
+        let urls = if output.status.code() == Some(UNSUPPORTED) {
+            vec![None; paths.len()]
+        } else {
+            let urls: RawUrls = self.parse(method, &self.check(method, output)?.stdout)?;
+            if urls.urls.len() != paths.len() {
+                return self.failed(
+                    method,
+                    format!("{} urls for {} paths", urls.urls.len(), paths.len()),
+                );
+            }
+            urls.urls
+        };
+        Ok(urls
+            .into_iter()
+            .zip(paths)
+            .map(|(url, path)| url.unwrap_or_else(|| self.blob_url(owner, repo, tree, path)))
+            .collect())
+    }
+
+    /// Fetches the content of a file with `fetch-blob`.
+    pub async fn fetch_blob(&self, blob: &Blob) -> Result<Vec<u8>> {
+        let method = "fetch-blob";
+        let request = Request {
+            owner: &blob.owner,
+            repo: &blob.repo,
+            tree: &blob.tree,
+            path: Some(&blob.path),
+            paths: None,
+        };
+        let output = self.call(method, &request).await?;
+        self.check(method, output).map(|output| output.stdout)
+    }
+
+    /// The url of a file only `fetch-blob` can fetch, which stands in for
+    /// its raw url until [`Provider::raw_urls`] resolves it.
+    pub fn blob_url(&self, owner: &str, repo: &str, tree: &str, path: &str) -> String {
+        let encode = |s: &str| utf8_percent_encode(s, NON_ALPHANUMERIC).to_string();
+        format!(
+            "{}{}/{}/{}/{}/{}",
+            BLOB_SCHEME,
+            self.name,
+            encode(owner),
+            encode(repo),
+            encode(tree),
+            encode(path)
+        )
+    }
+
+    /// The file a [`Provider::blob_url`] names, or `None` for any other url.
+    pub fn blob(&self, url: &str) -> Option<Blob> {
+        let rest = url.strip_prefix(BLOB_SCHEME)?;
+        let mut segments = rest.split('/');
+        if segments.next()? != self.name {
+            return None;
+        }
+        let mut decoded = segments.map(|s| percent_decode_str(s).decode_utf8_lossy().into_owned());
+        Some(Blob {
+            owner: decoded.next()?,
+            repo: decoded.next()?,
+            tree: decoded.next()?,
+            path: decoded.next()?,
+        })
+    }
+
+    /// Runs `method` with `request` on stdin.
+    ///
+    /// The request is written while stdout and stderr are read, all at once,
+    /// so a provider is never stalled on a full pipe, whichever it writes to
+    /// first.
+    async fn call<T: Serialize>(&self, method: &str, request: &T) -> Result<Output> {
+        let mut child = Command::new(&self.program)
+            .arg(method)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let request = serde_json::to_vec(request).unwrap();
+        let write = async move {
+            let written = stdin.write_all(&request).await;
+            // Closing stdin ends the request.
+            drop(stdin);
+            written
+        };
+        let (written, output) = tokio::join!(write, child.wait_with_output());
+        let output = output?;
+        match written {
+            // A provider may answer without reading the whole request.
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.into()),
+            _ => Ok(output),
+        }
+    }
+
+    /// Fails unless the provider exited successfully.
+    fn check(&self, method: &str, output: Output) -> Result<Output> {
+        if output.status.success() {
+            return Ok(output);
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let reason = if stderr.is_empty() {
+            format!("it exited with {}", output.status)
+        } else {
+            stderr
+        };
+        self.failed(method, reason)
+    }
+
+    fn parse<T: serde::de::DeserializeOwned>(&self, method: &str, stdout: &[u8]) -> Result<T> {
+        serde_json::from_slice(stdout)
+            .or_else(|e| self.failed(method, format!("its answer is malformed: {}", e)))
+    }
+
+    fn failed<T>(&self, method: &str, reason: String) -> Result<T> {
+        Error::err(ErrorKind::ProviderFailure {
+            provider: self.name.clone(),
+            method: method.to_string(),
+            reason,
+        })
+    }
+}
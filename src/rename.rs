@@ -0,0 +1,160 @@
+use crate::error::{Error, ErrorKind, Result};
+use regex::Regex;
+use std::path::Path;
+
+/// The placeholders a template may use, besides those derived from the path.
+const VARS: &[&str] = &["path", "dir", "name", "stem", "ext", "owner", "repo", "ref"];
+
+/// Values for the repository-level placeholders of a rename template.
+#[derive(Debug, Clone, Copy)]
+pub struct RenameVars<'a> {
+    pub owner: &'a str,
+    pub repo: &'a str,
+    pub tree: &'a str,
+}
+
+/// A rule rewriting destination paths (relative to the output directory).
+#[derive(Debug, Clone)]
+pub enum Rename {
+    /// A template such as `{stem}-{ref}{ext}`; see `VARS` for placeholders.
+    Template(String),
+    /// A sed-style `s/regex/replacement/[g]` substitution.
+    Substitute {
+        regex: Regex,
+        replacement: String,
+        global: bool,
+    },
+}
+
+impl Rename {
+    /// Parses `--rename`: anything of the form `s<delim>...<delim>...<delim>`
+    /// is a substitution, everything else a template.
+    pub fn parse(rule: &str) -> Result<Rename> {
+        let malformed = |reason: &str| {
+            Error::err(ErrorKind::MalformedRename {
+                rule: rule.to_string(),
+                reason: reason.to_string(),
+            })
+        };
+
+        let mut chars = rule.chars();
+        if let (Some('s'), Some(delim)) = (chars.next(), chars.next()) {
+            if !delim.is_alphanumeric() && !delim.is_whitespace() {
+                let parts = split_unescaped(chars.as_str(), delim);
+                if parts.len() != 3 {
+                    return malformed("expected s/regex/replacement/[g]");
+                }
+                let global = match parts[2].as_str() {
+                    "" => false,
+                    "g" => true,
+                    _ => return malformed("the only supported flag is g"),
+                };
+                let regex = match Regex::new(&parts[0]) {
+                    Ok(regex) => regex,
+                    Err(e) => return malformed(&e.to_string()),
+                };
+                return Ok(Rename::Substitute {
+                    regex,
+                    replacement: sed_to_regex_replacement(&parts[1]),
+                    global,
+                });
+            }
+        }
+
+        let mut rest = rule;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => return malformed("unclosed {"),
+            };
+            let var = &rest[start + 1..end];
+            if !VARS.contains(&var) {
+                return malformed(&format!(
+                    "unknown placeholder {{{}}}; expected one of {}",
+                    var,
+                    VARS.join(", ")
+                ));
+            }
+            rest = &rest[end + 1..];
+        }
+        Ok(Rename::Template(rule.to_string()))
+    }
+
+    /// Rewrites `rel`, a destination relative to the output directory.
+    pub fn apply(&self, rel: &str, vars: &RenameVars<'_>) -> String {
+        match self {
+            Rename::Template(template) => {
+                let path = Path::new(rel);
+                let lossy = |s: Option<&std::ffi::OsStr>| {
+                    s.map(|s| s.to_string_lossy().into_owned())
+                        .unwrap_or_default()
+                };
+                let ext = lossy(path.extension());
+                let ext = if ext.is_empty() {
+                    ext
+                } else {
+                    format!(".{}", ext)
+                };
+
+                template
+                    .replace("{path}", rel)
+                    .replace("{dir}", &lossy(path.parent().map(Path::as_os_str)))
+                    .replace("{name}", &lossy(path.file_name()))
+                    .replace("{stem}", &lossy(path.file_stem()))
+                    .replace("{ext}", &ext)
+                    .replace("{owner}", vars.owner)
+                    .replace("{repo}", vars.repo)
+                    .replace("{ref}", vars.tree)
+                    // An empty `{dir}` leaves a leading slash behind.
+                    .trim_start_matches('/')
+                    .to_string()
+            }
+            Rename::Substitute {
+                regex,
+                replacement,
+                global,
+            } => {
+                let limit = if *global { 0 } else { 1 };
+                regex
+                    .replacen(rel, limit, replacement.as_str())
+                    .into_owned()
+            }
+        }
+    }
+}
+
+/// Splits `s` on `delim`, honouring `\<delim>` escapes.
+fn split_unescaped(s: &str, delim: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek() == Some(&delim) {
+            parts.last_mut().unwrap().push(chars.next().unwrap());
+        } else if c == delim {
+            parts.push(String::new());
+        } else {
+            parts.last_mut().unwrap().push(c);
+        }
+    }
+    parts
+}
+
+/// Converts sed's `\1` and `&` back-references to the regex crate's syntax.
+fn sed_to_regex_replacement(sed: &str) -> String {
+    let mut out = String::new();
+    let mut chars = sed.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(d) if d.is_ascii_digit() => out.push_str(&format!("${{{}}}", d)),
+                Some('$') => out.push_str("$$"),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            '&' => out.push_str("${0}"),
+            '$' => out.push_str("$$"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,354 @@
+//! Rendering known formats as they are downloaded, `--render`.
+//!
+//! `--render json,csv,ipynb:py` pretty-prints JSON, turns CSV into a
+//! Markdown table and Jupyter notebooks into Markdown (`ipynb` or
+//! `ipynb:md`) or a Python script with `# %%` cells (`ipynb:py`). Converted
+//! files are written with the extension of what they became. A file that
+//! does not parse as its format is written as it arrived.
+
+use crate::error::{Error, ErrorKind, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// What a file of a known format is turned into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rendering {
+    /// `.json`, indented, with its keys in the order they came.
+    PrettyJson,
+    /// `.csv` into a Markdown table.
+    CsvTable,
+    /// `.ipynb` into Markdown, with the code cells and their text outputs
+    /// fenced.
+    NotebookMarkdown,
+    /// `.ipynb` into a percent-format script.
+    NotebookScript,
+}
+
+impl Rendering {
+    /// The extension of the files this renders.
+    fn source(self) -> &'static str {
+        match self {
+            Rendering::PrettyJson => "json",
+            Rendering::CsvTable => "csv",
+            Rendering::NotebookMarkdown | Rendering::NotebookScript => "ipynb",
+        }
+    }
+
+    /// The extension of the rendered files.
+    fn target(self) -> &'static str {
+        match self {
+            Rendering::PrettyJson => "json",
+            Rendering::CsvTable | Rendering::NotebookMarkdown => "md",
+            Rendering::NotebookScript => "py",
+        }
+    }
+}
+
+/// The renderings of a run.
+#[derive(Debug, Clone, Default)]
+pub struct Render {
+    renderings: Vec<Rendering>,
+}
+
+impl Render {
+    /// Parses a comma-separated `--render` list.
+    pub fn parse(text: &str) -> Result<Render> {
+        let malformed = |reason: String| {
+            Error::err(ErrorKind::MalformedRender {
+                render: text.to_string(),
+                reason,
+            })
+        };
+        let mut renderings: Vec<Rendering> = Vec::new();
+        for item in text.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            let rendering = match item.to_ascii_lowercase().as_str() {
+                "json" => Rendering::PrettyJson,
+                "csv" | "csv:md" => Rendering::CsvTable,
+                "ipynb" | "ipynb:md" => Rendering::NotebookMarkdown,
+                "ipynb:py" => Rendering::NotebookScript,
+                _ => {
+                    return malformed(format!(
+                        "unknown format `{}`; expected json, csv, ipynb, ipynb:md or ipynb:py",
+                        item
+                    ))
+                }
+            };
+            if renderings.iter().any(|r| r.source() == rendering.source()) {
+                return malformed(format!("{} is rendered twice", rendering.source()));
+            }
+            renderings.push(rendering);
+        }
+        if renderings.is_empty() {
+            return malformed("expected a format".to_string());
+        }
+        Ok(Render { renderings })
+    }
+
+    /// How the file at `path` in the repository is rendered, if it is.
+    pub fn rendering(&self, path: &str) -> Option<Rendering> {
+        let ext = Path::new(path)
+            .extension()?
+            .to_string_lossy()
+            .to_lowercase();
+        self.renderings.iter().copied().find(|r| r.source() == ext)
+    }
+
+    /// Where the file at `path` is written once rendered, instead of `dest`.
+    pub fn dest(&self, path: &str, dest: &Path) -> PathBuf {
+        match self.rendering(path) {
+            Some(rendering) if rendering.target() != rendering.source() => {
+                dest.with_extension(rendering.target())
+            }
+            _ => dest.to_path_buf(),
+        }
+    }
+
+    /// Renders the content of the file at `path`; `Ok(None)` if it is not
+    /// of a format to render, and `Err` with the reason if it does not parse.
+    pub fn render(&self, path: &str, bytes: &[u8]) -> std::result::Result<Option<Vec<u8>>, String> {
+        let rendering = match self.rendering(path) {
+            Some(rendering) => rendering,
+            None => return Ok(None),
+        };
+        let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+        let rendered = match rendering {
+            Rendering::PrettyJson => pretty_json(text)?,
+            Rendering::CsvTable => csv_table(text)?,
+            Rendering::NotebookMarkdown => notebook(text, false)?,
+            Rendering::NotebookScript => notebook(text, true)?,
+        };
+        Ok(Some(rendered.into_bytes()))
+    }
+}
+
+/// Indents `text` by two spaces a level. serde_json would sort the keys of
+/// objects, so the document is only checked with it.
+fn pretty_json(text: &str) -> std::result::Result<String, String> {
+    serde_json::from_str::<serde::de::IgnoredAny>(text).map_err(|e| e.to_string())?;
+
+    let mut out = String::with_capacity(text.len() * 2);
+    let newline = |out: &mut String, depth: usize| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    };
+    let mut depth = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                out.push(c);
+                while let Some(c) = chars.next() {
+                    out.push(c);
+                    match c {
+                        '\\' => out.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '{' | '[' => {
+                out.push(c);
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                // Empty containers stay on one line.
+                match chars.peek() {
+                    Some('}') | Some(']') => out.extend(chars.next()),
+                    _ => {
+                        depth += 1;
+                        newline(&mut out, depth);
+                    }
+                }
+            }
+            '}' | ']' => {
+                depth -= 1;
+                newline(&mut out, depth);
+                out.push(c);
+            }
+            ',' => {
+                out.push(c);
+                newline(&mut out, depth);
+            }
+            ':' => out.push_str(": "),
+            c if c.is_whitespace() => {}
+            c => out.push(c),
+        }
+    }
+    out.push('\n');
+    Ok(out)
+}
+
+/// Splits CSV into rows of fields, with RFC 4180 quoting.
+fn parse_csv(text: &str) -> std::result::Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                c => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("a quoted field is not closed".to_string());
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// A Markdown table of CSV whose first row is the header, with the columns
+/// aligned.
+fn csv_table(text: &str) -> std::result::Result<String, String> {
+    let rows: Vec<Vec<String>> = parse_csv(text)?
+        .into_iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| {
+                    cell.replace('|', "\\|")
+                        .replace("\r\n", "<br>")
+                        .replace('\n', "<br>")
+                })
+                .collect()
+        })
+        .collect();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if columns == 0 {
+        return Err("the file has no rows".to_string());
+    }
+    let cell = |row: &[String], i: usize| row.get(i).map_or("", String::as_str).to_string();
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            rows.iter()
+                .map(|row| cell(row, i).chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(3)
+        })
+        .collect();
+
+    let line = |cells: Vec<String>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        format!("| {} |\n", padded.join(" | "))
+    };
+    let mut out = line((0..columns).map(|i| cell(&rows[0], i)).collect());
+    out += &line(widths.iter().map(|w| "-".repeat(*w)).collect());
+    for row in &rows[1..] {
+        out += &line((0..columns).map(|i| cell(row, i)).collect());
+    }
+    Ok(out)
+}
+
+/// A notebook as Markdown, or with `script`, as a percent-format script of
+/// its kernel's language.
+fn notebook(text: &str, script: bool) -> std::result::Result<String, String> {
+    let notebook: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let cells = match notebook.get("cells").and_then(Value::as_array) {
+        Some(cells) => cells,
+        None => return Err("it has no cells".to_string()),
+    };
+    let language = notebook
+        .pointer("/metadata/kernelspec/language")
+        .or_else(|| notebook.pointer("/metadata/language_info/name"))
+        .and_then(Value::as_str)
+        .unwrap_or("python");
+
+    let mut out = String::new();
+    for cell in cells {
+        let kind = cell
+            .get("cell_type")
+            .and_then(Value::as_str)
+            .unwrap_or("code");
+        let source = joined(cell.get("source"));
+        let source = source.trim_end_matches('\n');
+        if script {
+            match kind {
+                "code" => out += &format!("# %%\n{}\n\n", source),
+                _ => {
+                    let marker = if kind == "markdown" {
+                        "markdown"
+                    } else {
+                        "raw"
+                    };
+                    out += &format!("# %% [{}]\n", marker);
+                    for line in source.lines() {
+                        out.push('#');
+                        if !line.is_empty() {
+                            out += " ";
+                            out += line;
+                        }
+                        out.push('\n');
+                    }
+                    out.push('\n');
+                }
+            }
+            continue;
+        }
+        match kind {
+            "markdown" => out += &format!("{}\n\n", source),
+            "code" => {
+                out += &fenced(source, language);
+                let outputs = cell.get("outputs").and_then(Value::as_array);
+                for output in outputs.into_iter().flatten() {
+                    let text = match output.get("text") {
+                        Some(text) => joined(Some(text)),
+                        None => joined(output.pointer("/data/text~1plain")),
+                    };
+                    let text = text.trim_end_matches('\n');
+                    if !text.is_empty() {
+                        out += &fenced(text, "text");
+                    }
+                }
+            }
+            _ => out += &fenced(source, ""),
+        }
+    }
+    Ok(format!("{}\n", out.trim_end()))
+}
+
+/// A notebook's multi-line string, which is either a string or a list of
+/// lines.
+fn joined(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(lines)) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// `text` in a code fence longer than any run of backticks in it.
+fn fenced(text: &str, info: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let fence = "`".repeat((longest + 1).max(3));
+    format!("{}{}\n{}\n{}\n\n", fence, info, text, fence)
+}
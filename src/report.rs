@@ -0,0 +1,95 @@
+use crate::error::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The files that failed to download, as written by `--error-log`.
+///
+/// Every run replaces the entries of the files it attempted, so the report
+/// always describes the latest attempt and can be fed back into a retry.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ErrorReport {
+    #[serde(default)]
+    pub failures: Vec<Failure>,
+}
+
+/// A file that could not be downloaded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Failure {
+    /// The repository, as `user/repo`.
+    pub repo: String,
+    /// The ref the file was to be downloaded from.
+    #[serde(rename = "ref")]
+    pub tree: String,
+    /// The path in the repository.
+    pub path: String,
+    pub url: String,
+    /// Where the file was to be written.
+    pub dest: PathBuf,
+    /// The HTTP status of the response, if there was one.
+    pub status: Option<u16>,
+    /// The error, followed by its causes.
+    pub errors: Vec<String>,
+}
+
+impl ErrorReport {
+    /// Loads the report at `path`; a missing file is an empty report.
+    pub fn load(path: &Path) -> Result<ErrorReport> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ErrorReport::default()),
+            Err(_) => {
+                return Error::err(ErrorKind::ReadFailure {
+                    path: path.display().to_string(),
+                })
+            }
+        };
+
+        serde_json::from_str(&text).or_else(|e| {
+            Error::err(ErrorKind::MalformedReport {
+                path: path.display().to_string(),
+                reason: e.to_string(),
+            })
+        })
+    }
+
+    /// Writes the report to `path`, sorted by destination, or removes it if
+    /// nothing failed.
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        if self.failures.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+
+        self.failures.sort_by(|a, b| a.dest.cmp(&b.dest));
+        let text = match serde_json::to_string_pretty(self) {
+            Ok(text) => text,
+            Err(e) => {
+                return Error::err(ErrorKind::MalformedReport {
+                    path: path.display().to_string(),
+                    reason: e.to_string(),
+                })
+            }
+        };
+        fs::write(path, text + "\n")?;
+        Ok(())
+    }
+
+    /// Drops the entries of `dests`, which are being attempted again.
+    pub fn forget(&mut self, dests: &[PathBuf]) {
+        self.failures.retain(|f| !dests.contains(&f.dest));
+    }
+}
+
+/// Lists `error` and its sources, outermost first.
+pub fn error_chain(error: &dyn std::error::Error) -> Vec<String> {
+    let mut chain = vec![error.to_string()];
+    let mut source = error.source();
+    while let Some(e) = source {
+        chain.push(e.to_string());
+        source = e.source();
+    }
+    chain
+}
@@ -0,0 +1,70 @@
+use crate::shell::quote;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::Path;
+
+/// A file to be fetched by a generated script.
+#[derive(Debug, Clone)]
+pub struct ScriptFile<'a> {
+    /// The url to download from.
+    pub url: &'a str,
+    /// The destination path, relative to where the script is run.
+    pub dest: &'a Path,
+    /// The git blob SHA the downloaded file is checked against.
+    pub sha: Option<&'a str>,
+}
+
+const PRELUDE: &str = r#"set -eu
+
+fetch() {
+    if command -v curl >/dev/null 2>&1; then
+        curl -fsSL -o "$2" "$1"
+    else
+        wget -q -O "$2" "$1"
+    fi
+}
+
+if command -v sha1sum >/dev/null 2>&1; then
+    SHA1SUM=sha1sum
+else
+    SHA1SUM="shasum -a 1"
+fi
+
+# Checks that "$1" hashes to the git blob SHA "$2".
+verify() {
+    actual=$({ printf 'blob %s\0' "$(wc -c < "$1" | tr -d ' ')"; cat "$1"; } | $SHA1SUM | cut -d ' ' -f 1)
+    if [ "$actual" != "$2" ]; then
+        echo "checksum mismatch for $1: expected $2, got $actual" >&2
+        exit 1
+    fi
+}
+"#;
+
+/// Renders a POSIX shell script that downloads `files` with curl (or wget)
+/// and verifies each one against its blob SHA. `origin` is only used in the
+/// header comment.
+pub fn shell_script(origin: &str, files: &[ScriptFile<'_>]) -> String {
+    let mut script = String::from("#!/bin/sh\n");
+    writeln!(script, "# Generated by gitdown from {}.", origin).unwrap();
+    script.push_str(PRELUDE);
+    script.push('\n');
+
+    let dirs: BTreeSet<&Path> = files
+        .iter()
+        .filter_map(|f| f.dest.parent())
+        .filter(|d| !d.as_os_str().is_empty())
+        .collect();
+    for dir in dirs {
+        writeln!(script, "mkdir -p {}", quote(&dir.to_string_lossy())).unwrap();
+    }
+
+    for file in files {
+        let dest = file.dest.to_string_lossy();
+        writeln!(script, "fetch {} {}", quote(file.url), quote(&dest)).unwrap();
+        if let Some(sha) = file.sha {
+            writeln!(script, "verify {} {}", quote(&dest), quote(sha)).unwrap();
+        }
+    }
+
+    script
+}
@@ -0,0 +1,112 @@
+//! Named selections, for `save` and `load`.
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::filter::Rule;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What to download from a repository, saved under a name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Selection {
+    /// The repository, as `user/repo[@ref]`; without a ref, its default
+    /// branch is downloaded from.
+    pub repo: String,
+    /// The directory of the repository the selection is limited to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subtree: Option<String>,
+    /// Filter rules in the syntax of `--filter-from` files, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<String>,
+    /// Whether `.gitdownignore` files are skipped.
+    #[serde(default)]
+    pub no_ignore: bool,
+    /// The picked paths; `None` takes every path the rules keep, including
+    /// those added since the selection was saved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paths: Option<Vec<String>>,
+}
+
+impl Selection {
+    /// Parses the saved filter rules.
+    pub fn filter_rules(&self) -> Result<Vec<Rule>> {
+        let mut rules = Vec::new();
+        for line in &self.rules {
+            rules.extend(Rule::parse_line(line)?);
+        }
+        Ok(rules)
+    }
+}
+
+/// Every saved selection, kept in the user config dir.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Selections {
+    #[serde(default)]
+    pub selections: BTreeMap<String, Selection>,
+}
+
+impl Selections {
+    /// Where the selections are kept, if there is a config dir.
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("gitdown").join("selections.json"))
+    }
+
+    /// Loads the selections at `path`; a missing file has none.
+    pub fn load(path: &Path) -> Result<Selections> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Selections::default()),
+            Err(_) => {
+                return Error::err(ErrorKind::ReadFailure {
+                    path: path.display().to_string(),
+                })
+            }
+        };
+
+        serde_json::from_str(&text).or_else(|e| {
+            Error::err(ErrorKind::MalformedSelections {
+                path: path.display().to_string(),
+                reason: e.to_string(),
+            })
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = match serde_json::to_string_pretty(self) {
+            Ok(text) => text,
+            Err(e) => {
+                return Error::err(ErrorKind::MalformedSelections {
+                    path: path.display().to_string(),
+                    reason: e.to_string(),
+                })
+            }
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text + "\n")?;
+        Ok(())
+    }
+
+    /// The selection saved as `name`.
+    pub fn get(&self, name: &str) -> Result<&Selection> {
+        match self.selections.get(name) {
+            Some(selection) => Ok(selection),
+            None => Error::err(ErrorKind::NoSuchSelection {
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    /// Saves `selection` as `name`, replacing any selection of that name.
+    pub fn insert(&mut self, name: &str, selection: Selection) {
+        self.selections.insert(name.to_string(), selection);
+    }
+
+    /// Forgets the selection saved as `name`, returning whether there was
+    /// one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.selections.remove(name).is_some()
+    }
+}
@@ -0,0 +1,109 @@
+//! Where downloaded files go instead of the local disk: a command they are
+//! fed to (`--pipe`) or object storage (`--dest`).
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::hooks::{self, HookEnv};
+use std::io;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// A target that takes each downloaded file in place of the local disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sink {
+    /// Each file is fed to a shell command on stdin.
+    Command(String),
+    /// Each file is uploaded to an S3 bucket, below a prefix, with the AWS
+    /// CLI; its usual configuration, such as `AWS_PROFILE` or
+    /// `AWS_ENDPOINT_URL`, applies.
+    S3 { bucket: String, prefix: String },
+}
+
+impl Sink {
+    /// Parses a `--dest` target, `s3://bucket[/prefix]`.
+    pub fn parse_dest(dest: &str) -> Result<Sink> {
+        let malformed = |reason: &str| {
+            Error::err(ErrorKind::MalformedDest {
+                dest: dest.to_string(),
+                reason: reason.to_string(),
+            })
+        };
+        let rest = match dest.strip_prefix("s3://") {
+            Some(rest) => rest,
+            None => return malformed("only s3://bucket/prefix destinations are supported"),
+        };
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return malformed("the bucket is missing");
+        }
+        Ok(Sink::S3 {
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+        })
+    }
+
+    /// Where a file with destination path `key` goes, for status lines.
+    pub fn describe(&self, key: &str) -> String {
+        match self {
+            Sink::Command(command) => format!("| {}", command),
+            Sink::S3 { .. } => format!("-> {}", self.s3_url(key)),
+        }
+    }
+
+    /// Whether files have to be sent one at a time, so the output of
+    /// commands does not interleave.
+    pub fn is_exclusive(&self) -> bool {
+        matches!(self, Sink::Command(_))
+    }
+
+    /// Sends `bytes`, the file with destination path `key`, to the target;
+    /// a command gets `env` as with hooks.
+    pub async fn send(&self, key: &str, env: &HookEnv, bytes: &[u8]) -> Result<()> {
+        match self {
+            Sink::Command(command) => hooks::pipe(command, env, bytes).await,
+            Sink::S3 { .. } => {
+                let url = self.s3_url(key);
+                let mut child = Command::new("aws");
+                child
+                    .args(["s3", "cp", "--only-show-errors", "-", &url])
+                    .stdin(Stdio::piped());
+                upload(child, &format!("aws s3 cp - {}", url), bytes).await
+            }
+        }
+    }
+
+    fn s3_url(&self, key: &str) -> String {
+        match self {
+            Sink::S3 { bucket, prefix } if prefix.is_empty() => format!("s3://{}/{}", bucket, key),
+            Sink::S3 { bucket, prefix } => format!("s3://{}/{}/{}", bucket, prefix, key),
+            Sink::Command(_) => unreachable!("only S3 has urls"),
+        }
+    }
+}
+
+/// Runs an upload command, writing `bytes` to its stdin.
+async fn upload(mut child: Command, shown: &str, bytes: &[u8]) -> Result<()> {
+    let mut child = match child.spawn() {
+        Ok(child) => child,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Error::err(ErrorKind::MissingProgram {
+                program: "The AWS CLI (aws)".to_string(),
+                purpose: "to write to s3:// destinations".to_string(),
+            })
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(bytes).await?;
+    drop(stdin);
+
+    let status = child.wait().await?;
+    if status.success() {
+        Ok(())
+    } else {
+        Error::err(ErrorKind::HookFailure {
+            command: shown.to_string(),
+            status: status.to_string(),
+        })
+    }
+}
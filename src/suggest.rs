@@ -0,0 +1,44 @@
+//! "Did you mean" suggestions for misspelled repositories and paths.
+
+/// How many suggestions an error lists at most.
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// The candidates closest to `target`, nearest first, leaving out those too
+/// far off to be a typo of it. Case is ignored, so `Gitdown` finds
+/// `gitdown`.
+pub fn closest<'a, I>(target: &str, candidates: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let target = target.to_lowercase();
+    // About one edit in three characters, and at least two, so short names
+    // still get suggestions.
+    let allowed = (target.chars().count() / 3).max(2);
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .map(|c| (distance(&target, &c.to_lowercase()), c))
+        .filter(|(d, _)| *d <= allowed)
+        .collect();
+    scored.sort();
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c.to_string())
+        .collect()
+}
+
+/// The Levenshtein distance between `a` and `b`, in characters.
+pub fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
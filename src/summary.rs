@@ -0,0 +1,76 @@
+use crate::output::human_size;
+use std::fmt;
+use std::ops::AddAssign;
+
+/// What happened to the files selected for download.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Summary {
+    /// Files fetched and written.
+    pub downloaded: usize,
+    /// Files left alone, e.g. because they already existed.
+    pub skipped: usize,
+    /// Files that could not be fetched or written.
+    pub failed: usize,
+    /// Files selected again in the same run, e.g. by giving a repository
+    /// twice, which are only downloaded once.
+    pub duplicates: usize,
+    /// The bytes received for the downloaded files, which may be compressed.
+    pub wire_bytes: u64,
+    /// The bytes of the downloaded files once decoded.
+    pub bytes: u64,
+    /// The commits downloaded from, as `user/repo@sha`.
+    pub commits: Vec<String>,
+}
+
+impl Summary {
+    /// The number of files accounted for.
+    pub fn total(&self) -> usize {
+        self.downloaded + self.skipped + self.failed + self.duplicates
+    }
+}
+
+impl AddAssign for Summary {
+    fn add_assign(&mut self, other: Summary) {
+        self.downloaded += other.downloaded;
+        self.skipped += other.skipped;
+        self.failed += other.failed;
+        self.duplicates += other.duplicates;
+        self.wire_bytes += other.wire_bytes;
+        self.bytes += other.bytes;
+        for commit in other.commits {
+            if !self.commits.contains(&commit) {
+                self.commits.push(commit);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} downloaded, {} skipped, {} failed",
+            self.downloaded, self.skipped, self.failed
+        )?;
+        if self.duplicates > 0 {
+            write!(f, ", {} duplicate", self.duplicates)?;
+            if self.duplicates > 1 {
+                f.write_str("s")?;
+            }
+        }
+        if self.bytes > 0 {
+            write!(
+                f,
+                " ({} received for {})",
+                human_size(self.wire_bytes),
+                human_size(self.bytes)
+            )?;
+        }
+        match self.commits.as_slice() {
+            [] => {}
+            commits if commits.len() <= 3 => write!(f, " from {}", commits.join(", "))?,
+            commits => write!(f, " from {} commits", commits.len())?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use crate::error::{Error, ErrorKind, Result};
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Substitutes `{{name}}` placeholders in file paths and contents, for
+/// scaffolding a project out of a starter repository.
+///
+/// Placeholders without a definition are left as they are, so unrelated
+/// `{{...}}` syntax (e.g. in templating languages) survives.
+#[derive(Debug, Clone)]
+pub struct Template {
+    vars: HashMap<String, String>,
+    placeholder: Regex,
+}
+
+impl Template {
+    pub fn new(vars: HashMap<String, String>) -> Self {
+        Self {
+            vars,
+            placeholder: Regex::new(r"\{\{\s*([A-Za-z0-9_.-]+)\s*\}\}").unwrap(),
+        }
+    }
+
+    /// Parses a `--define name=value` argument.
+    pub fn parse_define(define: &str) -> Result<(String, String)> {
+        match define.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => {
+                Ok((name.trim().to_string(), value.to_string()))
+            }
+            _ => Error::err(ErrorKind::MalformedDefine {
+                define: define.to_string(),
+            }),
+        }
+    }
+
+    /// Substitutes the placeholders in `text`.
+    pub fn render<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        self.placeholder
+            .replace_all(text, |caps: &Captures| match self.vars.get(&caps[1]) {
+                Some(value) => value.clone(),
+                None => caps[0].to_string(),
+            })
+    }
+
+    /// Substitutes the placeholders in file contents; anything that is not
+    /// UTF-8 is assumed to be binary and returned unchanged.
+    pub fn render_bytes(&self, bytes: Vec<u8>) -> Vec<u8> {
+        match String::from_utf8(bytes) {
+            Ok(text) => self.render(&text).into_owned().into_bytes(),
+            Err(e) => e.into_bytes(),
+        }
+    }
+}
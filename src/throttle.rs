@@ -0,0 +1,99 @@
+//! Backing off from GitHub's secondary rate limits.
+//!
+//! Besides the hourly quota, GitHub limits bursts of requests. Hitting such a
+//! limit is answered with a 403 or 429, usually with a `Retry-After`; every
+//! request should then wait, and fewer should run at once for a while.
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// How long to wait when a secondary limit does not say, as GitHub advises.
+const DEFAULT_WAIT: Duration = Duration::from_secs(60);
+
+/// The longest `Retry-After` worth waiting for.
+pub const MAX_WAIT: Duration = Duration::from_secs(300);
+
+/// How long requests run one at a time after the last back-off.
+const SLOW_FOR: Duration = Duration::from_secs(60);
+
+/// How many times a request that hit a secondary limit is retried.
+pub const RETRIES: u32 = 3;
+
+/// How long to wait before retrying a response that hit a secondary rate
+/// limit, or `None` if it did not hit one.
+///
+/// A 403 with no requests left is the primary limit instead, which resets
+/// much later and is not worth waiting for.
+pub fn secondary_limit_wait(status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let retry_after = header(RETRY_AFTER.as_str()).and_then(|s| s.trim().parse().ok());
+    match retry_after {
+        Some(secs) => Some(Duration::from_secs(secs)),
+        None if header("x-ratelimit-remaining") == Some("0") => None,
+        None if status == StatusCode::TOO_MANY_REQUESTS => Some(DEFAULT_WAIT),
+        None => None,
+    }
+}
+
+/// Shared by concurrent requests, which wait for each other after one of
+/// them hit a secondary limit.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    /// When requests may start again.
+    resume_at: Mutex<Option<Instant>>,
+    /// Until when requests run one at a time.
+    slow_until: Mutex<Option<Instant>>,
+    slowed: AtomicBool,
+    serial: tokio::sync::Mutex<()>,
+}
+
+impl Throttle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits until a request may be sent. While backing off, the returned
+    /// guard keeps the other requests waiting until it is dropped.
+    pub async fn admit(&self) -> Option<tokio::sync::MutexGuard<'_, ()>> {
+        loop {
+            let resume_at = *self.resume_at.lock().unwrap();
+            match resume_at.map(|t| t.saturating_duration_since(Instant::now())) {
+                Some(wait) if !wait.is_zero() => tokio::time::sleep(wait).await,
+                _ => break,
+            }
+        }
+
+        let slow_until = *self.slow_until.lock().unwrap();
+        if slow_until.is_some_and(|t| t > Instant::now()) {
+            Some(self.serial.lock().await)
+        } else {
+            if self.slowed.swap(false, Ordering::Relaxed) {
+                warn!("no longer rate limited, downloading concurrently again");
+            }
+            None
+        }
+    }
+
+    /// Makes every request wait `wait`, then run one at a time for a while.
+    pub fn back_off(&self, wait: Duration) {
+        let resume_at = Instant::now() + wait;
+        let mut current = self.resume_at.lock().unwrap();
+        if current.is_none_or(|t| t < resume_at) {
+            *current = Some(resume_at);
+        }
+        *self.slow_until.lock().unwrap() = Some(resume_at + SLOW_FOR);
+        if !self.slowed.swap(true, Ordering::Relaxed) {
+            warn!(
+                wait_secs = wait.as_secs(),
+                "hit a secondary rate limit, slowing down"
+            );
+        }
+    }
+}
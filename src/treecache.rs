@@ -0,0 +1,157 @@
+//! The tree listings of earlier runs, which `--cache stale-while-revalidate`
+//! opens the picker from while the repository is listed again.
+
+use crate::client::GitHubDirEntry;
+use crate::error::{Error, ErrorKind, Result};
+use crate::pathname;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The listing of a repository at a ref, kept in the user cache dir.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CachedTree {
+    /// The commit the ref pointed at when it was listed.
+    pub commit: Option<String>,
+    pub entries: Vec<CachedEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedEntry {
+    pub path: String,
+    pub sha: Option<String>,
+    pub size: Option<usize>,
+    pub mode: Option<String>,
+}
+
+/// How a fresh listing differs from the cached one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Changes {
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.added + self.removed + self.modified == 0
+    }
+}
+
+impl std::fmt::Display for Changes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} added, {} removed, {} modified",
+            self.added, self.removed, self.modified
+        )
+    }
+}
+
+impl CachedTree {
+    /// Where the listing of `user/repo` at `tree` is kept, if there is a
+    /// cache dir.
+    pub fn path(user: &str, repo: &str, tree: &str) -> Option<PathBuf> {
+        // A ref like `release/1.0` must not become a directory.
+        let file = format!("{}.json", pathname::url_path(tree).replace('/', "%2F"));
+        dirs::cache_dir().map(|d| {
+            d.join("gitdown")
+                .join("trees")
+                .join(user)
+                .join(repo)
+                .join(file)
+        })
+    }
+
+    /// Loads the listing at `path`, if one was cached.
+    pub fn load(path: &Path) -> Result<Option<CachedTree>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(_) => {
+                return Error::err(ErrorKind::ReadFailure {
+                    path: path.display().to_string(),
+                })
+            }
+        };
+
+        serde_json::from_str(&text).map(Some).or_else(|e| {
+            Error::err(ErrorKind::MalformedCache {
+                path: path.display().to_string(),
+                reason: e.to_string(),
+            })
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = match serde_json::to_string(self) {
+            Ok(text) => text,
+            Err(e) => {
+                return Error::err(ErrorKind::MalformedCache {
+                    path: path.display().to_string(),
+                    reason: e.to_string(),
+                })
+            }
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text + "\n")?;
+        Ok(())
+    }
+
+    pub fn new(commit: Option<String>, entries: &[GitHubDirEntry]) -> CachedTree {
+        let entries = entries
+            .iter()
+            .filter_map(|gh| {
+                Some(CachedEntry {
+                    path: gh.path.clone()?,
+                    sha: gh.sha.clone(),
+                    size: gh.size,
+                    mode: gh.mode.clone(),
+                })
+            })
+            .collect();
+        CachedTree { commit, entries }
+    }
+
+    /// The cached entries, as the listing gave them.
+    pub fn entries(&self) -> Vec<GitHubDirEntry> {
+        self.entries
+            .iter()
+            .map(|entry| GitHubDirEntry {
+                path: Some(entry.path.clone()),
+                sha: entry.sha.clone(),
+                size: entry.size,
+                mode: entry.mode.clone(),
+                raw_path: None,
+                dest: None,
+            })
+            .collect()
+    }
+
+    /// How `fresh` differs from this listing.
+    pub fn changes(&self, fresh: &CachedTree) -> Changes {
+        let old: HashMap<&str, &Option<String>> = self
+            .entries
+            .iter()
+            .map(|e| (e.path.as_str(), &e.sha))
+            .collect();
+        let mut changes = Changes::default();
+        let mut kept = 0;
+        for entry in &fresh.entries {
+            match old.get(entry.path.as_str()) {
+                Some(sha) => {
+                    kept += 1;
+                    if **sha != entry.sha {
+                        changes.modified += 1;
+                    }
+                }
+                None => changes.added += 1,
+            }
+        }
+        changes.removed = old.len() - kept;
+        changes
+    }
+}
@@ -0,0 +1,248 @@
+//! Finding and installing newer releases of gitdown itself.
+
+use crate::client;
+use crate::error::{Error, ErrorKind, Result};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long a release check is trusted before asking again.
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Where the releases of gitdown are published.
+pub const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/steven-mathew/gitdown/releases/latest";
+
+/// The version of the running binary.
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    /// Whether this release is newer than the running binary.
+    pub fn is_newer(&self) -> bool {
+        match (
+            parse_version(&self.tag_name),
+            parse_version(CURRENT_VERSION),
+        ) {
+            (Some(latest), Some(current)) => latest > current,
+            _ => false,
+        }
+    }
+
+    /// The asset built for this platform, e.g.
+    /// `gitdown-v0.2.0-x86_64-unknown-linux-gnu.tar.gz`.
+    pub fn platform_asset(&self) -> Option<&Asset> {
+        let os: &[&str] = match env::consts::OS {
+            "macos" => &["macos", "darwin", "apple"],
+            os => &[os],
+        };
+        let arch: &[&str] = match env::consts::ARCH {
+            "x86_64" => &["x86_64", "amd64"],
+            "aarch64" => &["aarch64", "arm64"],
+            arch => &[arch],
+        };
+
+        self.assets.iter().find(|asset| {
+            let name = asset.name.to_lowercase();
+            !is_checksum(&name)
+                && os.iter().any(|os| name.contains(os))
+                && arch.iter().any(|arch| name.contains(arch))
+        })
+    }
+
+    /// The asset holding the SHA-256 of `asset`: either `<name>.sha256` or a
+    /// shared `SHA256SUMS`-style list.
+    pub fn checksum_asset(&self, asset: &Asset) -> Option<&Asset> {
+        let own = format!("{}.sha256", asset.name);
+        self.assets.iter().find(|a| a.name == own).or_else(|| {
+            self.assets.iter().find(|a| {
+                let name = a.name.to_lowercase();
+                name.contains("sha256sums") || name.contains("checksums")
+            })
+        })
+    }
+}
+
+fn is_checksum(name: &str) -> bool {
+    [".sha256", ".txt", ".sig", ".asc"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+        || name.contains("sha256sums")
+        || name.contains("checksums")
+}
+
+/// Parses `v1.2.3` or `1.2.3`, ignoring any pre-release suffix.
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(|p| p.parse::<u64>());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor)), patch) => {
+            Some((major, minor, patch.and_then(|p| p.ok()).unwrap_or(0)))
+        }
+        _ => None,
+    }
+}
+
+/// Fetches the latest release.
+pub async fn latest_release(client: &reqwest::Client) -> Result<Release> {
+    let res = client
+        .get(LATEST_RELEASE_URL)
+        .header("Accept", "application/vnd.github.v3+json")
+        .send()
+        .await?;
+    if !res.status().is_success() {
+        return Err(client::status_error(res).await);
+    }
+    Ok(res.json().await?)
+}
+
+/// Returns the tag of a release newer than the running binary, asking the
+/// API at most once a day and caching the answer in the user cache dir.
+///
+/// This never fails: being offline or rate limited just means no notice.
+pub async fn newer_release_notice(client: &reqwest::Client) -> Option<String> {
+    let cache = dirs::cache_dir()?.join("gitdown").join("latest-release");
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+
+    // The cache holds `<unix time of the check> <latest tag>`.
+    let cached = fs::read_to_string(&cache).ok().and_then(|text| {
+        let (checked, tag) = text.trim().split_once(' ')?;
+        let checked: u64 = checked.parse().ok()?;
+        (now.saturating_sub(checked) < CHECK_INTERVAL.as_secs()).then(|| tag.to_string())
+    });
+
+    let tag = match cached {
+        Some(tag) => tag,
+        None => {
+            let check = tokio::time::timeout(Duration::from_secs(2), latest_release(client));
+            // A failed check is cached too, so it is not retried on every run.
+            let tag = match check.await {
+                Ok(Ok(release)) => release.tag_name,
+                _ => format!("v{}", CURRENT_VERSION),
+            };
+            if let Some(dir) = cache.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            let _ = fs::write(&cache, format!("{} {}\n", now, tag));
+            tag
+        }
+    };
+
+    let release = Release {
+        tag_name: tag,
+        assets: Vec::new(),
+    };
+    release.is_newer().then_some(release.tag_name)
+}
+
+/// Finds the checksum of `name` in the contents of a checksum asset, which
+/// is either a bare digest or `<digest>  <name>` lines.
+pub fn find_checksum(sums: &str, name: &str) -> Option<String> {
+    let lines: Vec<&str> = sums
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    if let [line] = lines.as_slice() {
+        if !line.contains(char::is_whitespace) {
+            return Some(line.to_lowercase());
+        }
+    }
+
+    lines.iter().find_map(|line| {
+        let (digest, file) = line.split_once(char::is_whitespace)?;
+        let file = file.trim().trim_start_matches('*');
+        (file == name).then(|| digest.to_lowercase())
+    })
+}
+
+/// Checks `bytes` against the expected SHA-256 `digest`.
+pub fn verify_checksum(name: &str, bytes: &[u8], digest: &str) -> Result<()> {
+    let actual: String = Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if actual == digest {
+        Ok(())
+    } else {
+        Error::err(ErrorKind::ChecksumMismatch {
+            name: name.to_string(),
+            expected: digest.to_string(),
+            actual,
+        })
+    }
+}
+
+/// Takes the executable out of a downloaded asset, which is either the
+/// binary itself or a `.tar.gz` containing it.
+pub fn extract_binary(name: &str, bytes: Vec<u8>) -> Result<Vec<u8>> {
+    if !(name.ends_with(".tar.gz") || name.ends_with(".tgz")) {
+        if name.ends_with(".zip") {
+            return Error::err(ErrorKind::UpdateFailure {
+                reason: format!("{} is a zip archive, which is not supported", name),
+            });
+        }
+        return Ok(bytes);
+    }
+
+    let binary = if cfg!(windows) {
+        "gitdown.exe"
+    } else {
+        "gitdown"
+    };
+    let mut archive = tar::Archive::new(GzDecoder::new(bytes.as_slice()));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.file_name().is_some_and(|f| f == binary) {
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            return Ok(contents);
+        }
+    }
+    Error::err(ErrorKind::UpdateFailure {
+        reason: format!("{} does not contain {}", name, binary),
+    })
+}
+
+/// Replaces the running executable by `binary`, returning its path.
+///
+/// The new binary is written next to the old one and renamed over it, so a
+/// failure halfway leaves the old one in place.
+pub fn replace_current_exe(binary: &[u8]) -> Result<PathBuf> {
+    let exe = env::current_exe()?;
+    let staged = exe.with_extension("new");
+    fs::write(&staged, binary)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+
+    // Windows will not overwrite a running executable, but it can rename it.
+    if cfg!(windows) {
+        let old = exe.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(&exe, &old)?;
+    }
+    fs::rename(&staged, &exe)?;
+    Ok(exe)
+}
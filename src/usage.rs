@@ -0,0 +1,140 @@
+//! How many requests a run issued, and how much of the rate limit they used,
+//! so heavy users can see what their filters and caching save.
+
+use reqwest::header::HeaderMap;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+static API_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+static RAW_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+static QUOTAS: Mutex<BTreeMap<String, Quota>> = Mutex::new(BTreeMap::new());
+
+/// What the rate limit headers said of one resource (`core`, `search`,
+/// `graphql`, ...) over the run.
+#[derive(Debug, Clone, Copy)]
+struct Quota {
+    /// The requests counted in the windows that have already been reset.
+    spent: u64,
+    /// The reset time of the current window.
+    reset: u64,
+    /// `x-ratelimit-used` at the first and the latest response of the
+    /// current window.
+    first_used: u64,
+    used: u64,
+    remaining: u64,
+}
+
+impl Quota {
+    fn consumed(&self) -> u64 {
+        // The first response already counts its own request.
+        self.spent + self.used.saturating_sub(self.first_used) + 1
+    }
+}
+
+/// Counts an API request, noting the rate limit its response reports.
+pub fn record_api(headers: &HeaderMap) {
+    API_REQUESTS.fetch_add(1, Ordering::Relaxed);
+
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+    };
+    let (used, remaining, reset) = match (
+        header("x-ratelimit-used"),
+        header("x-ratelimit-remaining"),
+        header("x-ratelimit-reset"),
+    ) {
+        (Some(used), Some(remaining), Some(reset)) => (used, remaining, reset),
+        _ => return,
+    };
+    let resource = headers
+        .get("x-ratelimit-resource")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("core")
+        .to_string();
+
+    let mut quotas = QUOTAS.lock().unwrap();
+    let quota = quotas.entry(resource).or_insert(Quota {
+        spent: 0,
+        reset,
+        first_used: used,
+        used,
+        remaining,
+    });
+    if reset != quota.reset {
+        // A new window started, counting from zero again.
+        quota.spent = quota.consumed();
+        quota.reset = reset;
+        quota.first_used = used;
+    }
+    // Concurrent responses can arrive out of order.
+    quota.used = quota.used.max(used);
+    quota.remaining = quota.remaining.min(remaining);
+    quota.first_used = quota.first_used.min(used);
+}
+
+/// Counts a request for raw file contents, which has no rate limit of its
+/// own.
+pub fn record_raw() {
+    RAW_REQUESTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The requests of a resource's rate limit used by the run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub resource: String,
+    pub consumed: u64,
+    /// What was left of it at the last response.
+    pub remaining: u64,
+}
+
+/// The requests issued so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub api_requests: usize,
+    pub raw_requests: usize,
+    pub quotas: Vec<QuotaUsage>,
+}
+
+impl Usage {
+    pub fn is_empty(&self) -> bool {
+        self.api_requests == 0 && self.raw_requests == 0
+    }
+}
+
+/// The requests issued by the run so far.
+pub fn snapshot() -> Usage {
+    let quotas = QUOTAS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(resource, quota)| QuotaUsage {
+            resource: resource.clone(),
+            consumed: quota.consumed(),
+            remaining: quota.remaining,
+        })
+        .collect();
+    Usage {
+        api_requests: API_REQUESTS.load(Ordering::Relaxed),
+        raw_requests: RAW_REQUESTS.load(Ordering::Relaxed),
+        quotas,
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} API, {} raw", self.api_requests, self.raw_requests)?;
+        for quota in &self.quotas {
+            write!(
+                f,
+                "; {} of the {} rate limit used, {} left",
+                quota.consumed, quota.resource, quota.remaining
+            )?;
+        }
+        Ok(())
+    }
+}
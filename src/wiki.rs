@@ -0,0 +1,97 @@
+//! Repository wikis are separate git repositories (`<repo>.wiki.git`) that
+//! the API does not expose, so pages are listed with git and fetched from
+//! raw.githubusercontent.com.
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::pathname;
+use std::env;
+use std::fs;
+use std::process::Command;
+
+/// A file of a wiki.
+#[derive(Debug, Clone)]
+pub struct WikiPage {
+    pub path: String,
+    /// The blob SHA of the page.
+    pub sha: String,
+    pub size: usize,
+}
+
+/// Lists the files of the wiki of `user/repo` at its latest revision.
+///
+/// This needs `git`, and only clones the tree: no page contents are
+/// transferred.
+pub fn list_pages(user: &str, repo: &str) -> Result<Vec<WikiPage>> {
+    let url = format!("https://github.com/{}/{}.wiki.git", user, repo);
+    let dir = env::temp_dir().join(format!("gitdown-wiki-{}", std::process::id()));
+    let unavailable = |reason: String| {
+        Error::err(ErrorKind::WikiUnavailable {
+            repo: format!("{}/{}", user, repo),
+            reason,
+        })
+    };
+
+    let clone = Command::new("git")
+        .args([
+            "clone",
+            "--quiet",
+            "--bare",
+            "--depth",
+            "1",
+            "--filter=blob:none",
+        ])
+        .arg(&url)
+        .arg(&dir)
+        .output();
+    let clone = match clone {
+        Ok(output) => output,
+        Err(e) => return unavailable(format!("could not run git: {}", e)),
+    };
+    if !clone.status.success() {
+        let _ = fs::remove_dir_all(&dir);
+        return unavailable(String::from_utf8_lossy(&clone.stderr).trim().to_string());
+    }
+
+    let ls_tree = Command::new("git")
+        .arg("-C")
+        .arg(&dir)
+        .args(["ls-tree", "-r", "-l", "-z", "HEAD"])
+        .output();
+    let _ = fs::remove_dir_all(&dir);
+    let ls_tree = match ls_tree {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            return unavailable(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+        Err(e) => return unavailable(format!("could not run git: {}", e)),
+    };
+
+    // Each record is `<mode> <type> <sha> <size>\t<path>`.
+    Ok(ls_tree
+        .stdout
+        .split(|&b| b == 0)
+        .filter_map(|record| {
+            let record = String::from_utf8_lossy(record);
+            let (meta, path) = record.split_once('\t')?;
+            let fields: Vec<&str> = meta.split_whitespace().collect();
+            match fields.as_slice() {
+                [_, "blob", sha, size] => Some(WikiPage {
+                    path: path.to_string(),
+                    sha: sha.to_string(),
+                    size: size.parse().ok()?,
+                }),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+/// Builds the raw.githubusercontent.com url of a wiki file.
+pub fn raw_url(user: &str, repo: &str, path: &str) -> String {
+    format!(
+        "https://raw.githubusercontent.com/wiki/{}/{}/{}",
+        user,
+        repo,
+        pathname::url_path(path)
+    )
+}
@@ -0,0 +1,40 @@
+use gitdown::bundle::{relative_dest, Bundle};
+use gitdown::hash::git_blob_sha;
+use gitdown::lockfile::LockedFile;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn dests_must_stay_inside() {
+    assert_eq!(
+        relative_dest(Path::new("./src/main.c")),
+        Some(PathBuf::from("src/main.c"))
+    );
+    assert_eq!(relative_dest(Path::new("/etc/passwd")), None);
+    assert_eq!(relative_dest(Path::new("docs/../../x")), None);
+    assert_eq!(relative_dest(Path::new(".")), None);
+}
+
+#[test]
+fn bundles_round_trip() {
+    let content = b"int main() {}\n".to_vec();
+    let sha = git_blob_sha(&content);
+    let mut bundle = Bundle::default();
+    bundle.lock.files.push(LockedFile {
+        repo: "o/r".to_string(),
+        tree: "main".to_string(),
+        commit: None,
+        path: "src/main.c".to_string(),
+        dest: "./src/main.c".into(),
+        sha: Some(sha.clone()),
+    });
+    bundle.blobs.insert(sha.clone(), content.clone());
+
+    let path = std::env::temp_dir().join(format!("gitdown-{}.bundle", std::process::id()));
+    bundle.write(&path, true).unwrap();
+    assert!(bundle.write(&path, false).is_err());
+    let read = Bundle::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(read.lock.files, bundle.lock.files);
+    assert_eq!(read.blobs[&sha], content);
+}
@@ -0,0 +1,543 @@
+use futures::StreamExt;
+use gitdown::client::{Client, RetryPolicy};
+use gitdown::download::{Downloader, Event};
+use gitdown::error::ErrorKind;
+use reqwest::StatusCode;
+use serde_json::json;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Starts a mock server and returns it along with its url, which serves as
+/// both the API and the raw endpoint.
+async fn server() -> (MockServer, String) {
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    (server, uri)
+}
+
+fn client(uri: &str) -> Client {
+    Client::builder()
+        .base_url(uri)
+        .raw_url(uri)
+        .retry(RetryPolicy::none())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn lists_only_blobs() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/git/trees/main"))
+        .and(query_param("recursive", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "0a1b",
+            "tree": [
+                { "path": "src", "mode": "040000", "type": "tree", "sha": "aaaa" },
+                { "path": "src/lib.rs", "mode": "100644", "type": "blob", "sha": "bbbb", "size": 12 },
+                { "path": "vendor", "mode": "160000", "type": "commit", "sha": "cccc" }
+            ],
+            "truncated": false
+        })))
+        .mount(&server)
+        .await;
+
+    let entries = client(&uri)
+        .get_dentries("octo", "cat", None)
+        .await
+        .unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].path.as_deref(), Some("src/lib.rs"));
+    assert_eq!(entries[0].sha.as_deref(), Some("bbbb"));
+    assert_eq!(entries[0].size, Some(12));
+}
+
+#[tokio::test]
+async fn truncated_tree_keeps_listed_entries() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/git/trees/big"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "0a1b",
+            "tree": [
+                { "path": "a.txt", "mode": "100644", "type": "blob", "sha": "aaaa", "size": 1 }
+            ],
+            "truncated": true
+        })))
+        .mount(&server)
+        .await;
+
+    let entries = client(&uri)
+        .get_dentries("octo", "cat", Some("big"))
+        .await
+        .unwrap();
+    assert_eq!(entries.len(), 1);
+}
+
+#[tokio::test]
+async fn missing_tree_is_reported() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "message": "Not Found"
+        })))
+        .mount(&server)
+        .await;
+
+    let err = client(&uri)
+        .get_dentries("octo", "cat", Some("nope"))
+        .await
+        .unwrap_err();
+    match err.kind() {
+        ErrorKind::TreeDoesNotExist { tree, repo } => {
+            assert_eq!(tree, "nope");
+            assert_eq!(repo, "octo/cat");
+        }
+        kind => panic!("unexpected error: {:?}", kind),
+    }
+}
+
+#[tokio::test]
+async fn rate_limit_carries_reset_and_documentation() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(403)
+                .insert_header("x-ratelimit-remaining", "0")
+                .insert_header("x-ratelimit-reset", "1650000000")
+                .set_body_json(json!({
+                    "message": "API rate limit exceeded",
+                    "documentation_url": "https://docs.github.com/rest"
+                })),
+        )
+        .mount(&server)
+        .await;
+
+    let req = reqwest::Client::new().get(format!("{}/octo/cat", uri));
+    let err = client(&uri).send(req).await.unwrap_err();
+    match err.kind() {
+        ErrorKind::RateLimited {
+            status,
+            reset,
+            documentation_url,
+        } => {
+            assert_eq!(*status, StatusCode::FORBIDDEN);
+            assert_eq!(*reset, Some(1650000000));
+            assert_eq!(
+                documentation_url.as_deref(),
+                Some("https://docs.github.com/rest")
+            );
+        }
+        kind => panic!("unexpected error: {:?}", kind),
+    }
+}
+
+#[tokio::test]
+async fn malformed_response_is_an_error() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/commits/main"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "commit": {} })))
+        .mount(&server)
+        .await;
+
+    let err = client(&uri)
+        .resolve_commit("octo", "cat", "main")
+        .await
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::MalformedResponse { .. }));
+}
+
+#[tokio::test]
+async fn changed_since_skips_removed_files() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/compare/v1...main"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "files": [
+                { "filename": "kept.rs", "status": "modified" },
+                { "filename": "new.rs", "status": "added" },
+                { "filename": "gone.rs", "status": "removed" }
+            ]
+        })))
+        .mount(&server)
+        .await;
+
+    let changed = client(&uri)
+        .changed_since("octo", "cat", "main", "v1")
+        .await
+        .unwrap()
+        .unwrap();
+    let mut changed: Vec<String> = changed.into_iter().collect();
+    changed.sort();
+    assert_eq!(changed, ["kept.rs", "new.rs"]);
+}
+
+#[tokio::test]
+async fn changed_since_reads_every_page() {
+    let (server, uri) = server().await;
+    let first: Vec<_> = (0..300)
+        .map(|i| json!({ "filename": format!("src/{}.rs", i), "status": "modified" }))
+        .collect();
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/compare/v1...main"))
+        .and(query_param("page", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "files": first })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/compare/v1...main"))
+        .and(query_param("page", "2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "files": [{ "filename": "last.rs", "status": "added" }]
+        })))
+        .mount(&server)
+        .await;
+
+    let changed = client(&uri)
+        .changed_since("octo", "cat", "main", "v1")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(changed.len(), 301);
+    assert!(changed.contains("last.rs"));
+}
+
+#[tokio::test]
+async fn changed_since_gives_up_on_a_capped_comparison() {
+    let (server, uri) = server().await;
+    let first: Vec<_> = (0..300)
+        .map(|i| json!({ "filename": format!("src/{}.rs", i), "status": "modified" }))
+        .collect();
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/compare/v1...main"))
+        .and(query_param("page", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "files": first })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/compare/v1...main"))
+        .and(query_param("page", "2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "files": [] })))
+        .mount(&server)
+        .await;
+
+    let changed = client(&uri)
+        .changed_since("octo", "cat", "main", "v1")
+        .await
+        .unwrap();
+    assert!(changed.is_none());
+}
+
+#[tokio::test]
+async fn binary_payloads_are_untouched() {
+    let (server, uri) = server().await;
+    let payload: Vec<u8> = (0..=255).cycle().take(4096).collect();
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/tarball/v1"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(payload.clone()))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/main/logo.png"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(payload.clone()))
+        .mount(&server)
+        .await;
+
+    let client = client(&uri);
+    let tarball = client.get_tarball("octo", "cat", Some("v1")).await.unwrap();
+    assert_eq!(tarball, payload);
+
+    let url = client.raw_url("octo", "cat", "main", "logo.png");
+    assert_eq!(url, format!("{}/octo/cat/main/logo.png", uri));
+    let raw = reqwest::get(url).await.unwrap().bytes().await.unwrap();
+    assert_eq!(raw.as_ref(), payload.as_slice());
+}
+
+#[tokio::test]
+async fn tag_verification_follows_annotated_tags() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/git/ref/tags/v1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "object": { "sha": "dddd", "type": "tag" }
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/git/tags/dddd"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "verification": { "verified": true, "reason": "valid" }
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/git/ref/tags/light"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "object": { "sha": "eeee", "type": "commit" }
+        })))
+        .mount(&server)
+        .await;
+
+    let client = client(&uri);
+    let verification = client
+        .tag_verification("octo", "cat", "v1")
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(verification.verified);
+    assert_eq!(verification.reason, "valid");
+
+    let lightweight = client
+        .tag_verification("octo", "cat", "light")
+        .await
+        .unwrap();
+    assert!(lightweight.is_none());
+}
+
+#[tokio::test]
+async fn search_adds_the_language_qualifier() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/search/repositories"))
+        .and(query_param("q", "rate limiter language:rust"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "total_count": 1,
+            "items": [{
+                "full_name": "octo/governor",
+                "description": null,
+                "stargazers_count": 42,
+                "default_branch": "master"
+            }]
+        })))
+        .mount(&server)
+        .await;
+
+    let client = Client::builder()
+        .search_url(format!("{}/search/repositories", uri))
+        .retry(RetryPolicy::none())
+        .build()
+        .unwrap();
+    let found = client
+        .search_repositories("rate limiter", Some("rust"))
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].full_name, "octo/governor");
+    assert_eq!(found[0].default_branch, "master");
+}
+
+#[tokio::test]
+async fn secondary_rate_limits_are_waited_out() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/commits/main"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+        .up_to_n_times(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/commits/main"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "sha": "ffff" })))
+        .mount(&server)
+        .await;
+
+    let sha = client(&uri)
+        .resolve_commit("octo", "cat", "main")
+        .await
+        .unwrap();
+    assert_eq!(sha, "ffff");
+}
+
+#[tokio::test]
+async fn persistent_secondary_rate_limit_is_reported() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(403).insert_header("retry-after", "0"))
+        .mount(&server)
+        .await;
+
+    let err = client(&uri)
+        .resolve_commit("octo", "cat", "main")
+        .await
+        .unwrap_err();
+    match err.kind() {
+        ErrorKind::SecondaryRateLimited { retry_after, .. } => {
+            assert_eq!(*retry_after, Some(0))
+        }
+        kind => panic!("unexpected error: {:?}", kind),
+    }
+}
+
+#[tokio::test]
+async fn owner_repositories_tell_a_missing_repository() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "cat" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/users/octo/repos"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "name": "cat" },
+            { "name": "dog" }
+        ])))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "message": "Not Found"
+        })))
+        .mount(&server)
+        .await;
+
+    let client = Client::builder()
+        .base_url(&uri)
+        .users_url(format!("{}/users", uri))
+        .retry(RetryPolicy::none())
+        .build()
+        .unwrap();
+    assert!(client.repository_exists("octo", "cat").await.unwrap());
+    assert!(!client.repository_exists("octo", "cta").await.unwrap());
+    let repos = client.owner_repositories("octo").await.unwrap();
+    assert_eq!(repos, ["cat", "dog"]);
+}
+
+#[tokio::test]
+async fn content_comes_from_the_blob_or_the_contents_endpoint() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/git/blobs/bbbb"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("by sha"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/contents/src/lib.rs"))
+        .and(query_param("ref", "main"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("by path"))
+        .mount(&server)
+        .await;
+
+    let client = client(&uri);
+    let by_sha = client
+        .get_content("octo", "cat", "main", "src/lib.rs", Some("bbbb"))
+        .await
+        .unwrap();
+    assert_eq!(by_sha, b"by sha");
+    let by_path = client
+        .get_content("octo", "cat", "main", "src/lib.rs", None)
+        .await
+        .unwrap();
+    assert_eq!(by_path, b"by path");
+}
+
+#[tokio::test]
+async fn sorted_search_asks_for_the_order() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/search/repositories"))
+        .and(query_param(
+            "q",
+            "topic:cli created:>=2022-05-01 language:rust",
+        ))
+        .and(query_param("sort", "stars"))
+        .and(query_param("order", "desc"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "total_count": 0,
+            "items": []
+        })))
+        .mount(&server)
+        .await;
+
+    let client = Client::builder()
+        .search_url(format!("{}/search/repositories", uri))
+        .retry(RetryPolicy::none())
+        .build()
+        .unwrap();
+    let found = client
+        .search_repositories_by(
+            "topic:cli created:>=2022-05-01",
+            Some("rust"),
+            Some("stars"),
+        )
+        .await
+        .unwrap();
+    assert!(found.is_empty());
+}
+
+#[tokio::test]
+async fn default_branch_comes_from_the_repository() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "name": "cat",
+            "default_branch": "master"
+        })))
+        .mount(&server)
+        .await;
+
+    let branch = client(&uri).default_branch("octo", "cat").await.unwrap();
+    assert_eq!(branch, "master");
+}
+
+#[tokio::test]
+async fn commit_before_encodes_the_ref_and_the_date() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/commits"))
+        .and(query_param("sha", "feature/a+b&c"))
+        .and(query_param("until", "2022-05-01T12:00:00+02:00"))
+        .and(query_param("per_page", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "sha": "0a1b" }])))
+        .mount(&server)
+        .await;
+
+    let sha = client(&uri)
+        .commit_before("octo", "cat", "feature/a+b&c", "2022-05-01T12:00:00+02:00")
+        .await
+        .unwrap();
+    assert_eq!(sha.as_deref(), Some("0a1b"));
+}
+
+#[tokio::test]
+async fn downloader_lists_the_default_branch() {
+    let (server, uri) = server().await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "default_branch": "trunk" })),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/commits/trunk"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "sha": "c0ffee" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/octo/cat/git/trees/c0ffee"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "c0ffee",
+            "tree": [],
+            "truncated": false
+        })))
+        .mount(&server)
+        .await;
+
+    let client = client(&uri);
+    let out = std::env::temp_dir();
+    let downloader = Downloader::new(&client);
+    let mut events = downloader
+        .download("octo", "cat", None, &out)
+        .await
+        .unwrap();
+    match events.next().await {
+        Some(Event::Listed { files, commit, .. }) => {
+            assert_eq!(files, 0);
+            assert_eq!(commit, "c0ffee");
+        }
+        other => panic!("expected the listing first, got {:?}", other),
+    }
+}
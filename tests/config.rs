@@ -0,0 +1,30 @@
+use gitdown::config::{Config, RepoConfig};
+
+#[test]
+fn the_longest_matching_pattern_wins() {
+    let mut config = Config::default();
+    for (pattern, jobs) in [("mycorp/*", 2), ("mycorp/tools", 8), ("*/*", 4)] {
+        config.repos.insert(
+            pattern.to_string(),
+            RepoConfig {
+                jobs: Some(jobs),
+                ..RepoConfig::default()
+            },
+        );
+    }
+    let jobs = |user, repo| config.repo(user, repo).and_then(|(_, r)| r.jobs);
+
+    assert_eq!(jobs("mycorp", "tools"), Some(8));
+    assert_eq!(jobs("mycorp", "web"), Some(2));
+    assert_eq!(jobs("rust-lang", "rust"), Some(4));
+}
+
+#[test]
+fn stars_stay_within_the_owner() {
+    let mut config = Config::default();
+    config
+        .repos
+        .insert("mycorp*".to_string(), RepoConfig::default());
+    assert!(config.repo("mycorp", "web").is_none());
+    assert!(config.repo("mycorp-labs", "web").is_none());
+}
@@ -0,0 +1,62 @@
+use gitdown::expr::{parse_age, Expr, Facts};
+
+fn matches(expr: &str, path: &str, size: Option<u64>, age: Option<u64>) -> bool {
+    Expr::parse(expr)
+        .unwrap()
+        .is_match(&Facts { path, size, age })
+}
+
+#[test]
+fn conditions_combine_with_precedence() {
+    let expr = "ext==md && size<100kb && path~^docs/";
+    assert!(matches(expr, "docs/guide.MD", Some(2048), None));
+    assert!(!matches(expr, "docs/big.md", Some(200 * 1024), None));
+    assert!(!matches(expr, "README.md", Some(10), None));
+
+    // `&&` binds tighter than `||`.
+    assert!(matches(
+        "ext==rs || ext==md && size>1M",
+        "a.rs",
+        Some(1),
+        None
+    ));
+    assert!(!matches(
+        "(ext==rs || ext==md) && size>1M",
+        "a.rs",
+        Some(1),
+        None
+    ));
+    assert!(matches("!(name=='Cargo.lock')", "src/lib.rs", None, None));
+}
+
+#[test]
+fn unknown_numbers_fail_every_condition() {
+    assert!(!matches("size<1k", "a.rs", None, None));
+    assert!(!matches("size>=1k", "a.rs", None, None));
+    assert!(matches("!(age<30d)", "a.rs", None, None));
+    assert!(Expr::parse("age<30d").unwrap().uses_age());
+    assert!(!Expr::parse("size<30k").unwrap().uses_age());
+}
+
+#[test]
+fn ages_take_units() {
+    assert_eq!(parse_age("90m"), Some(90 * 60));
+    assert_eq!(parse_age("2w"), Some(14 * 24 * 60 * 60));
+    assert_eq!(parse_age("6mo"), Some(180 * 24 * 60 * 60));
+    assert_eq!(parse_age("3"), None);
+}
+
+#[test]
+fn malformed_expressions_are_rejected() {
+    for expr in [
+        "",
+        "ext=",
+        "size<abc",
+        "foo==1",
+        "(ext==md",
+        "path<3",
+        "ext==md &&",
+    ] {
+        assert!(Expr::parse(expr).is_err(), "{}", expr);
+    }
+}
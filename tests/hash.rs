@@ -0,0 +1,30 @@
+use gitdown::hash::{Checksum, Hashes, StreamHasher};
+
+#[tokio::test]
+async fn streamed_hashes_match_hashing_at_once() {
+    let content = b"fn main() {\n    println!(\"hi\");\n}\n";
+    for checksum in [
+        None,
+        Some(Checksum::Sha256),
+        Some(Checksum::Sha1),
+        Some(Checksum::Git),
+    ] {
+        let hasher = StreamHasher::start(content.len(), checksum);
+        for chunk in content.chunks(7) {
+            hasher.feed(chunk);
+        }
+        let streamed = hasher.finish(content.len()).await;
+        assert_eq!(streamed, Some(Hashes::of(content, checksum)));
+    }
+    assert_eq!(
+        Hashes::of(b"hello\n", None).blob,
+        "ce013625030ba8dba906f756967f9e9ca394464a"
+    );
+}
+
+#[tokio::test]
+async fn a_short_stream_has_no_hashes() {
+    let hasher = StreamHasher::start(10, None);
+    hasher.feed(b"12345");
+    assert_eq!(hasher.finish(5).await, None);
+}
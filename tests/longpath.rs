@@ -0,0 +1,65 @@
+use gitdown::longpath::{self, MAX_PATH};
+
+#[test]
+fn drive_paths_get_the_prefix() {
+    assert_eq!(
+        longpath::extended(r"C:\src\repo\file.rs").as_deref(),
+        Some(r"\\?\C:\src\repo\file.rs")
+    );
+}
+
+#[test]
+fn forward_slashes_become_backslashes() {
+    assert_eq!(
+        longpath::extended("D:/out/packages/core/src/lib.rs").as_deref(),
+        Some(r"\\?\D:\out\packages\core\src\lib.rs")
+    );
+}
+
+#[test]
+fn dot_components_are_resolved() {
+    assert_eq!(
+        longpath::extended(r"C:\out\.\a\..\b\\c.txt").as_deref(),
+        Some(r"\\?\C:\out\b\c.txt")
+    );
+}
+
+#[test]
+fn unc_paths_use_the_unc_prefix() {
+    assert_eq!(
+        longpath::extended(r"\\server\share\dir\file").as_deref(),
+        Some(r"\\?\UNC\server\share\dir\file")
+    );
+    assert_eq!(longpath::extended(r"\\server"), None);
+}
+
+#[test]
+fn prefixed_paths_are_kept() {
+    let path = r"\\?\C:\already\there";
+    assert_eq!(longpath::extended(path).as_deref(), Some(path));
+}
+
+#[test]
+fn relative_paths_are_not_extended() {
+    assert_eq!(longpath::extended(r"out\file"), None);
+    assert_eq!(longpath::extended("C:file"), None);
+}
+
+#[test]
+fn long_paths_are_detected() {
+    let nested = format!(r"C:\{}", "deeply\\".repeat(40));
+    assert!(nested.len() > MAX_PATH);
+    assert!(longpath::is_long(&nested));
+    assert!(!longpath::is_long(r"C:\short\path"));
+
+    let extended = longpath::extended(&nested).unwrap();
+    assert!(extended.starts_with(r"\\?\C:\deeply\deeply"));
+    assert!(!extended.ends_with('\\'));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn other_platforms_use_paths_as_they_are() {
+    let path = std::path::PathBuf::from("a/".repeat(200));
+    assert_eq!(longpath::for_io(&path), path.as_path());
+}
@@ -0,0 +1,128 @@
+use gitdown::layout::Layout;
+use gitdown::pathname::{self, NameStrategy};
+use gitdown::rename::RenameVars;
+use std::path::PathBuf;
+
+const NAMES: [NameStrategy; 3] = [
+    NameStrategy::Escape,
+    NameStrategy::Replace,
+    NameStrategy::Keep,
+];
+
+#[test]
+fn ordinary_names_are_untouched() {
+    for strategy in NAMES {
+        for windows in [false, true] {
+            assert_eq!(
+                strategy.local_path_for("src/my file (1).rs", windows),
+                PathBuf::from("src").join("my file (1).rs")
+            );
+        }
+    }
+}
+
+#[test]
+fn unusual_unicode_is_kept() {
+    for name in [
+        "数据/ファイル.txt",
+        "emoji/🦀.rs",
+        "cafe\u{301}.md",
+        "\u{202e}txt.exe",
+        "\u{fffd}.bin",
+    ] {
+        for strategy in NAMES {
+            let expected: PathBuf = name.split('/').collect();
+            assert_eq!(strategy.local_path_for(name, false), expected);
+        }
+    }
+}
+
+#[test]
+fn control_characters_are_escaped_or_replaced() {
+    let name = "bad\nname\t.txt";
+    assert_eq!(
+        NameStrategy::Escape.component(name, false),
+        "bad%0Aname%09.txt"
+    );
+    assert_eq!(
+        NameStrategy::Replace.component(name, false),
+        "bad_name_.txt"
+    );
+    assert_eq!(NameStrategy::Keep.component(name, false), name);
+}
+
+#[test]
+fn windows_reserved_characters_only_matter_on_windows() {
+    let name = "a:b?c*.txt";
+    assert_eq!(NameStrategy::Escape.component(name, false), name);
+    assert_eq!(
+        NameStrategy::Escape.component(name, true),
+        "a%3Ab%3Fc%2A.txt"
+    );
+    assert_eq!(NameStrategy::Replace.component(name, true), "a_b_c_.txt");
+}
+
+#[test]
+fn windows_device_names_and_trailing_dots_are_avoided() {
+    assert_eq!(NameStrategy::Replace.component("CON", true), "_CON");
+    assert_eq!(NameStrategy::Replace.component("nul.txt", true), "_nul.txt");
+    assert_eq!(
+        NameStrategy::Replace.component("console.txt", true),
+        "console.txt"
+    );
+    assert_eq!(
+        NameStrategy::Replace.component("trailing.", true),
+        "trailing_"
+    );
+    assert_eq!(NameStrategy::Escape.component("space ", true), "space%20");
+    assert_eq!(NameStrategy::Escape.component("CON", false), "CON");
+}
+
+#[test]
+fn paths_cannot_leave_the_output_directory() {
+    for strategy in NAMES {
+        assert_eq!(
+            strategy.local_path_for("../../etc/./passwd", false),
+            PathBuf::from("etc").join("passwd")
+        );
+        assert_eq!(
+            strategy.local_path_for("/abs//path", false),
+            PathBuf::from("abs").join("path")
+        );
+    }
+}
+
+#[test]
+fn layout_applies_the_strategy() {
+    let layout = Layout {
+        out_dir: PathBuf::from("out"),
+        names: NameStrategy::Replace,
+        ..Layout::default()
+    };
+    let vars = RenameVars {
+        owner: "octo",
+        repo: "cat",
+        tree: "main",
+    };
+    let dests = layout.destinations(&["docs/a\u{7}b.md", "../up.txt"], &vars);
+    assert_eq!(
+        dests,
+        [
+            PathBuf::from("out").join("docs").join("a_b.md"),
+            PathBuf::from("out").join("up.txt"),
+        ]
+    );
+}
+
+#[test]
+fn url_paths_escape_each_segment() {
+    assert_eq!(pathname::url_path("docs/a b#1?.md"), "docs/a%20b%231%3F.md");
+    assert_eq!(pathname::url_path("100%/ü.txt"), "100%25/%C3%BC.txt");
+    assert_eq!(pathname::url_path("plain/path.rs"), "plain/path.rs");
+}
+
+#[test]
+fn displayed_paths_escape_control_characters() {
+    assert_eq!(pathname::display("a\u{1b}[31mred"), "a\\u{1b}[31mred");
+    assert_eq!(pathname::display("日本/ok.txt"), "日本/ok.txt");
+}
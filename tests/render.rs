@@ -0,0 +1,60 @@
+use gitdown::render::Render;
+use std::path::{Path, PathBuf};
+
+fn render(formats: &str, path: &str, text: &str) -> String {
+    let rendered = Render::parse(formats)
+        .unwrap()
+        .render(path, text.as_bytes())
+        .unwrap()
+        .unwrap();
+    String::from_utf8(rendered).unwrap()
+}
+
+#[test]
+fn json_keeps_its_key_order() {
+    assert_eq!(
+        render("json", "a.json", r#"{"z":[1,{}],"a":"b\"}"}"#),
+        "{\n  \"z\": [\n    1,\n    {}\n  ],\n  \"a\": \"b\\\"}\"\n}\n"
+    );
+}
+
+#[test]
+fn csv_becomes_a_table() {
+    assert_eq!(
+        render(
+            "csv",
+            "t.CSV",
+            "a,b\r\n\"x, y\",\"say \"\"hi\"\"\"\r\n1\r\n"
+        ),
+        "| a    | b        |\n| ---- | -------- |\n| x, y | say \"hi\" |\n| 1    |          |\n"
+    );
+}
+
+#[test]
+fn notebooks_become_scripts() {
+    let notebook = r##"{"cells": [
+        {"cell_type": "markdown", "source": ["# Intro\n", "\n", "text"]},
+        {"cell_type": "code", "source": "import os\n", "outputs": []}
+    ]}"##;
+    assert_eq!(
+        render("json, ipynb:py", "nb.ipynb", notebook),
+        "# %% [markdown]\n# # Intro\n#\n# text\n\n# %%\nimport os\n"
+    );
+}
+
+#[test]
+fn conversions_change_the_extension() {
+    let render = Render::parse("csv,json").unwrap();
+    assert_eq!(
+        render.dest("data/t.csv", Path::new("out/t.csv")),
+        PathBuf::from("out/t.md")
+    );
+    assert_eq!(
+        render.dest("a.json", Path::new("a.json")),
+        PathBuf::from("a.json")
+    );
+    assert!(render.render("main.rs", b"fn main() {}").unwrap().is_none());
+    assert!(render.render("bad.json", b"{").is_err());
+    assert!(Render::parse("ipynb,ipynb:py").is_err());
+    assert!(Render::parse("yaml").is_err());
+}
@@ -0,0 +1,31 @@
+use gitdown::suggest::{closest, distance};
+
+#[test]
+fn distance_counts_edits() {
+    assert_eq!(distance("gitdown", "gitdown"), 0);
+    assert_eq!(distance("gitdwon", "gitdown"), 2);
+    assert_eq!(distance("kitten", "sitting"), 3);
+    assert_eq!(distance("", "abc"), 3);
+}
+
+#[test]
+fn closest_ranks_and_drops_far_candidates() {
+    let repos = ["gitdown", "gitup", "dotfiles", "git-down"];
+    assert_eq!(closest("gitdwn", repos), ["gitdown", "git-down"]);
+    assert_eq!(closest("gitdonw", repos), ["gitdown"]);
+    assert!(closest("kubernetes", repos).is_empty());
+}
+
+#[test]
+fn closest_ignores_case() {
+    assert_eq!(
+        closest("README.MD", ["README.md", "src/main.rs"]),
+        ["README.md"]
+    );
+}
+
+#[test]
+fn closest_suggests_at_most_three() {
+    let paths = ["a.rs", "b.rs", "c.rs", "d.rs"];
+    assert_eq!(closest("e.rs", paths).len(), 3);
+}